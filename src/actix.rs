@@ -0,0 +1,128 @@
+use std::{future::Future, pin::Pin, task::{Context, Poll}, time::Instant};
+
+use actix_web::{Error, HttpMessage, dev::{Service, ServiceRequest, ServiceResponse, Transform},
+                http::header::{AsHeaderName, USER_AGENT}};
+use slog::Logger;
+
+use summary::RequestSummary;
+
+/// User ID for a request's `request.summary` record, set by the app in the
+/// request's extensions, e.g. `req.extensions_mut().insert(RequestUid(..))`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequestUid(pub String);
+
+/// actix-web middleware logging a `request.summary` record per request
+///
+/// Records have Fields `method`, `path`, `code`, `t` (milliseconds), and if
+/// known `agent` (User-Agent), `uid` (from `RequestUid`) and `rid`
+/// (X-Request-Id), following Mozilla's operational logging conventions.
+///
+/// ```ignore
+/// App::new().wrap(ActixSummary::new(logger.clone()))
+/// ```
+#[derive(Clone)]
+pub struct ActixSummary {
+    logger: Logger,
+}
+
+impl ActixSummary {
+    /// Log request summaries to `logger`
+    pub fn new(logger: Logger) -> Self {
+        ActixSummary { logger }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for ActixSummary
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = ActixSummaryMiddleware<S>;
+    type InitError = ();
+    type Future = ::std::future::Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ::std::future::ready(Ok(ActixSummaryMiddleware {
+            service,
+            logger: self.logger.clone(),
+        }))
+    }
+}
+
+/// Service created by `ActixSummary`
+pub struct ActixSummaryMiddleware<S> {
+    service: S,
+    logger: Logger,
+}
+
+impl<S, B> Service<ServiceRequest> for ActixSummaryMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = SummaryFuture<S::Future>;
+
+    fn poll_ready(&self, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let summary = RequestSummary {
+            method: req.method().to_string(),
+            path: req.path().to_owned(),
+            agent: header(&req, USER_AGENT),
+            rid: header(&req, "x-request-id"),
+            ..Default::default()
+        };
+        SummaryFuture {
+            inner: Box::pin(self.service.call(req)),
+            started: Some((Instant::now(), summary)),
+            logger: self.logger.clone(),
+        }
+    }
+}
+
+fn header<K: AsHeaderName>(req: &ServiceRequest, name: K) -> Option<String> {
+    req.headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned)
+}
+
+/// Response future of `ActixSummaryMiddleware`, logging once it completes
+pub struct SummaryFuture<F> {
+    inner: Pin<Box<F>>,
+    started: Option<(Instant, RequestSummary)>,
+    logger: Logger,
+}
+
+impl<F, B> Future for SummaryFuture<F>
+where
+    F: Future<Output = Result<ServiceResponse<B>, Error>>,
+{
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let res = match self.inner.as_mut().poll(cx) {
+            Poll::Ready(res) => res,
+            Poll::Pending => return Poll::Pending,
+        };
+        if let Some((at, mut summary)) = self.started.take() {
+            match res {
+                Ok(ref resp) => {
+                    summary.code = resp.status().as_u16();
+                    let extensions = resp.request().extensions();
+                    summary.uid = extensions.get::<RequestUid>().map(|uid| uid.0.clone());
+                }
+                Err(ref e) => summary.code = e.as_response_error().status_code().as_u16(),
+            }
+            summary.set_elapsed(at.elapsed());
+            summary.log(&self.logger);
+        }
+        Poll::Ready(res)
+    }
+}
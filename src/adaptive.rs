@@ -0,0 +1,82 @@
+use std::{sync::{Mutex, PoisonError}, time::{Duration, Instant}};
+
+/// Output budget for adaptive sampling
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SampleBudget {
+    /// Records written per second
+    RecordsPerSec(f64),
+    /// Bytes written per second
+    BytesPerSec(f64),
+}
+
+struct State {
+    rate: f64,
+    /// Systematic sampling: a record is kept each time this passes 1
+    acc: f64,
+    window_start: Instant,
+    offered: u64,
+    kept: u64,
+    kept_bytes: u64,
+}
+
+/// Sampler adjusting its rate each second to keep output within a budget
+pub(crate) struct AdaptiveSampler {
+    budget: SampleBudget,
+    state: Mutex<State>,
+}
+
+impl AdaptiveSampler {
+    pub(crate) fn new(budget: SampleBudget) -> Self {
+        AdaptiveSampler {
+            budget,
+            state: Mutex::new(State {
+                rate: 1.0,
+                acc: 0.0,
+                window_start: Instant::now(),
+                offered: 0,
+                kept: 0,
+                kept_bytes: 0,
+            }),
+        }
+    }
+
+    /// The effective sample rate if a record is kept
+    pub(crate) fn sample(&self) -> Option<f64> {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let elapsed = now.duration_since(state.window_start);
+        if elapsed >= Duration::from_secs(1) {
+            let secs = elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) / 1e9;
+            let offered = state.offered as f64 / secs;
+            let demand = match self.budget {
+                SampleBudget::RecordsPerSec(budget) => offered / budget,
+                SampleBudget::BytesPerSec(budget) if state.kept > 0 => {
+                    offered * (state.kept_bytes as f64 / state.kept as f64) / budget
+                }
+                // No record size known yet
+                SampleBudget::BytesPerSec(_) => 1.0 / state.rate,
+            };
+            state.rate = if demand > 1.0 { 1.0 / demand } else { 1.0 };
+            state.window_start = now;
+            state.offered = 0;
+            state.kept = 0;
+            state.kept_bytes = 0;
+        }
+        state.offered += 1;
+        state.acc += state.rate;
+        if state.acc < 1.0 {
+            return None;
+        }
+        state.acc -= 1.0;
+        state.kept += 1;
+        Some(state.rate)
+    }
+
+    /// Account for a kept record's size
+    pub(crate) fn wrote(&self, bytes: usize) {
+        self.state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .kept_bytes += bytes as u64;
+    }
+}
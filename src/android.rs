@@ -0,0 +1,65 @@
+use std::{io, ffi::CString};
+
+use libc::{c_char, c_int};
+use serde_json::{self, Value};
+
+use util::LineBuffer;
+
+#[link(name = "log")]
+extern "C" {
+    fn __android_log_write(prio: c_int, tag: *const c_char, text: *const c_char) -> c_int;
+}
+
+/// `android_LogPriority` for a MozLog (syslog) `Severity`
+fn log_priority(severity: Option<u64>) -> c_int {
+    match severity {
+        Some(0) | Some(1) | Some(2) => 7, // ANDROID_LOG_FATAL
+        Some(3) => 6,                     // ANDROID_LOG_ERROR
+        Some(4) => 5,                     // ANDROID_LOG_WARN
+        Some(7) => 3,                     // ANDROID_LOG_DEBUG
+        _ => 4,                           // ANDROID_LOG_INFO
+    }
+}
+
+/// Android logcat `io::Write` sink
+///
+/// Each newline terminated record is written, without its newline, to
+/// logcat under a tag, at the priority mapped from its `Severity`. Logcat
+/// truncates entries beyond roughly 4KB. Android only.
+pub struct AndroidLogWriter {
+    tag: CString,
+    lines: LineBuffer,
+}
+
+impl AndroidLogWriter {
+    /// New `AndroidLogWriter` logging under `tag`
+    pub fn new(tag: &str) -> io::Result<Self> {
+        Ok(AndroidLogWriter {
+            tag: CString::new(tag)?,
+            lines: LineBuffer::new(),
+        })
+    }
+}
+
+impl io::Write for AndroidLogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let tag = &self.tag;
+        self.lines.push(buf, |mut record| {
+            record.pop();
+            let severity = serde_json::from_slice::<Value>(&record)
+                .ok()
+                .and_then(|parsed| parsed.get("Severity").and_then(Value::as_u64));
+            // Serialized JSON never contains a raw NUL
+            if let Ok(text) = CString::new(record) {
+                unsafe {
+                    __android_log_write(log_priority(severity), tag.as_ptr(), text.as_ptr());
+                }
+            }
+        });
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
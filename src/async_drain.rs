@@ -0,0 +1,66 @@
+use std::io;
+
+use slog::{self, Drain, Level, Never, OwnedKVList, Record};
+use slog_async::{Async, AsyncBuilder, OverflowStrategy};
+
+use sample::find_key;
+
+/// Message of slog-async's `DropAndReport` report
+const ASYNC_DROPPED: &str = "slog-async: logger dropped messages due to channel overflow";
+
+/// `slog_async::AsyncBuilder` over `drain` (typically a `MozLogJson`),
+/// set to drop records on a full channel and report them
+///
+/// Reports reach `drain` as Warning `mozlog.dropped` records, "N records
+/// dropped by async channel" with a `dropped` field, like
+/// `BackgroundWriter`'s, rather than slog-async's own Error record.
+///
+/// ```ignore
+/// let drain = mozlog_async(MozLogJson::default(io::stdout())).chan_size(4096).build();
+/// let logger = Logger::root(drain.fuse(), o!());
+/// ```
+pub fn mozlog_async<D>(drain: D) -> AsyncBuilder<AsyncDropReports<D>>
+where
+    D: Drain<Ok = (), Err = io::Error> + Send + 'static,
+{
+    Async::new(AsyncDropReports { drain })
+        .overflow_strategy(OverflowStrategy::DropAndReport)
+}
+
+/// `Drain` behind slog-async rewriting its reports of dropped records, see
+/// `mozlog_async`
+///
+/// slog-async's worker can't handle errors, so `drain`'s are discarded (a
+/// `MozLogJson` still counts them in its stats).
+pub struct AsyncDropReports<D> {
+    drain: D,
+}
+
+impl<D> Drain for AsyncDropReports<D>
+where
+    D: Drain<Ok = (), Err = io::Error>,
+{
+    type Ok = ();
+    type Err = Never;
+
+    fn log(&self, rinfo: &Record, logger_values: &OwnedKVList) -> Result<(), Never> {
+        let dropped = if rinfo.msg().as_str() == Some(ASYNC_DROPPED) {
+            find_key("count", rinfo, logger_values).and_then(|count| count.parse::<u64>().ok())
+        } else {
+            None
+        };
+        let _ = match dropped {
+            Some(dropped) => {
+                let rstatic = record_static!(Level::Warning, "mozlog.dropped");
+                let msg = format_args!("{} records dropped by async channel", dropped);
+                let kv = kv!("dropped" => dropped);
+                self.drain.log(
+                    &Record::new(&rstatic, &msg, slog::BorrowedKV(&kv)),
+                    logger_values,
+                )
+            }
+            None => self.drain.log(rinfo, logger_values),
+        };
+        Ok(())
+    }
+}
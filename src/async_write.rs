@@ -0,0 +1,164 @@
+use std::{io, future::Future, pin::Pin, sync::Arc, sync::atomic::{AtomicUsize, Ordering},
+          task::{Context, Poll}};
+
+use tokio::{self, io::AsyncWrite, sync::mpsc};
+
+use drain::{MozLogJson, MozLogJsonBuilder};
+use util::LineBuffer;
+
+enum Msg {
+    Record(Vec<u8>),
+    Flush,
+}
+
+/// Task feeding queued records to the underlying `AsyncWrite`
+///
+/// Once every `AsyncWriter` handle is gone, the remaining records are
+/// written and the writer is shut down.
+struct WriteTask<W> {
+    io: W,
+    rx: mpsc::Receiver<Msg>,
+    /// Record being written and how much of it has been
+    current: Option<(Vec<u8>, usize)>,
+    flushing: bool,
+    closing: bool,
+    dropped: Arc<AtomicUsize>,
+}
+
+impl<W: AsyncWrite + Unpin> Future for WriteTask<W> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        let task = &mut *self;
+        loop {
+            if let Some((record, pos)) = task.current.take() {
+                match Pin::new(&mut task.io).poll_write(cx, &record[pos..]) {
+                    Poll::Ready(Ok(n)) if n > 0 => {
+                        if pos + n < record.len() {
+                            task.current = Some((record, pos + n));
+                        }
+                    }
+                    Poll::Ready(_) => {
+                        task.dropped.fetch_add(1, Ordering::Relaxed);
+                    }
+                    Poll::Pending => {
+                        task.current = Some((record, pos));
+                        return Poll::Pending;
+                    }
+                }
+                continue;
+            }
+            if task.flushing {
+                if Pin::new(&mut task.io).poll_flush(cx).is_pending() {
+                    return Poll::Pending;
+                }
+                task.flushing = false;
+            }
+            if task.closing {
+                return Pin::new(&mut task.io).poll_shutdown(cx).map(|_| ());
+            }
+            match task.rx.poll_recv(cx) {
+                Poll::Ready(Some(Msg::Record(record))) => task.current = Some((record, 0)),
+                Poll::Ready(Some(Msg::Flush)) => task.flushing = true,
+                Poll::Ready(None) => task.closing = true,
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// `io::Write` sink bridging to a `tokio::io::AsyncWrite`
+///
+/// Newline terminated records are handed over a bounded channel to a task
+/// on the tokio runtime that writes them to an async socket or file, so
+/// logging never blocks a runtime worker thread. Records are dropped when
+/// the channel is full or the underlying write fails. `flush` requests a
+/// flush of the underlying writer without waiting for it.
+pub struct AsyncWriter {
+    tx: mpsc::Sender<Msg>,
+    lines: LineBuffer,
+    dropped: Arc<AtomicUsize>,
+}
+
+impl AsyncWriter {
+    /// New `AsyncWriter` over `io`, queuing up to 1024 records
+    ///
+    /// Spawns its writer task, so must be called within a tokio runtime.
+    pub fn new<W>(io: W) -> Self
+    where
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        Self::with_capacity(io, 1024)
+    }
+
+    /// New `AsyncWriter` over `io`, queuing up to `records` records
+    ///
+    /// Spawns its writer task, so must be called within a tokio runtime.
+    pub fn with_capacity<W>(io: W, records: usize) -> Self
+    where
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel(records);
+        let dropped = Arc::new(AtomicUsize::new(0));
+        tokio::spawn(WriteTask {
+            io,
+            rx,
+            current: None,
+            flushing: false,
+            closing: false,
+            dropped: dropped.clone(),
+        });
+        AsyncWriter {
+            tx,
+            lines: LineBuffer::new(),
+            dropped,
+        }
+    }
+
+    /// Number of records dropped on a full channel or failed write
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    fn send(&self, msg: Msg) -> io::Result<()> {
+        match self.tx.try_send(msg) {
+            Ok(()) => Ok(()),
+            Err(mpsc::error::TrySendError::Full(Msg::Record(_))) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                Ok(())
+            }
+            // Skip the flush rather than wait for room
+            Err(mpsc::error::TrySendError::Full(Msg::Flush)) => Ok(()),
+            Err(mpsc::error::TrySendError::Closed(_)) => Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "async writer task has stopped",
+            )),
+        }
+    }
+}
+
+impl io::Write for AsyncWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut records = vec![];
+        self.lines.push(buf, |record| records.push(record));
+        for record in records {
+            self.send(Msg::Record(record))?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.send(Msg::Flush)
+    }
+}
+
+impl MozLogJson<AsyncWriter> {
+    /// Build custom `Json` `Drain` writing to `io` from a task on the
+    /// current tokio runtime (see `AsyncWriter`)
+    pub fn new_async<W>(io: W) -> MozLogJsonBuilder<AsyncWriter>
+    where
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        MozLogJson::new(AsyncWriter::new(io))
+    }
+}
@@ -0,0 +1,328 @@
+use std::{env, io, sync::Mutex, time::Duration};
+
+use chrono::{self, DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde_json::{self, Value};
+use sha2::{Digest, Sha256};
+use ureq;
+
+type HmacSha256 = Hmac<Sha256>;
+
+fn other<E: ToString>(e: E) -> io::Error {
+    io::Error::other(e.to_string())
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub(crate) fn sha256_hex(data: &[u8]) -> String {
+    hex(&Sha256::digest(data))
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// AWS access key credentials
+#[derive(Clone)]
+pub(crate) struct Credentials {
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: Option<String>,
+    expiration: Option<DateTime<Utc>>,
+}
+
+impl Credentials {
+    fn from_json(json: &Value) -> io::Result<Self> {
+        let field = |name: &str| {
+            json.get(name)
+                .and_then(Value::as_str)
+                .map(str::to_owned)
+                .ok_or_else(|| other(format!("credentials response missing {}", name)))
+        };
+        Ok(Credentials {
+            access_key_id: field("AccessKeyId")?,
+            secret_access_key: field("SecretAccessKey")?,
+            session_token: field("Token").ok(),
+            expiration: field("Expiration")
+                .ok()
+                .and_then(|exp| DateTime::parse_from_rfc3339(&exp).ok())
+                .map(|exp| exp.with_timezone(&Utc)),
+        })
+    }
+
+    fn expiring(&self) -> bool {
+        match self.expiration {
+            Some(exp) => exp - chrono::Duration::minutes(5) <= Utc::now(),
+            None => false,
+        }
+    }
+}
+
+/// Resolves AWS credentials
+///
+/// Either fixed credentials, or the default chain: the `AWS_ACCESS_KEY_ID`
+/// environment variables, then the ECS container credentials endpoint,
+/// then the EC2 instance metadata service. Temporary credentials are
+/// cached until shortly before they expire.
+pub(crate) struct CredentialsProvider {
+    fixed: Option<Credentials>,
+    cached: Mutex<Option<Credentials>>,
+    agent: ureq::Agent,
+}
+
+impl CredentialsProvider {
+    pub(crate) fn default_chain() -> Self {
+        CredentialsProvider {
+            fixed: None,
+            cached: Mutex::new(None),
+            agent: ureq::AgentBuilder::new().timeout(Duration::from_secs(2)).build(),
+        }
+    }
+
+    pub(crate) fn fixed(
+        access_key_id: String,
+        secret_access_key: String,
+        session_token: Option<String>,
+    ) -> Self {
+        let mut provider = Self::default_chain();
+        provider.fixed = Some(Credentials {
+            access_key_id,
+            secret_access_key,
+            session_token,
+            expiration: None,
+        });
+        provider
+    }
+
+    pub(crate) fn get(&self) -> io::Result<Credentials> {
+        if let Some(ref creds) = self.fixed {
+            return Ok(creds.clone());
+        }
+        let mut cached = self.cached.lock().unwrap();
+        if let Some(ref creds) = *cached {
+            if !creds.expiring() {
+                return Ok(creds.clone());
+            }
+        }
+        let creds = self.resolve()?;
+        *cached = Some(creds.clone());
+        Ok(creds)
+    }
+
+    fn resolve(&self) -> io::Result<Credentials> {
+        if let (Ok(id), Ok(secret)) = (env::var("AWS_ACCESS_KEY_ID"), env::var("AWS_SECRET_ACCESS_KEY")) {
+            return Ok(Credentials {
+                access_key_id: id,
+                secret_access_key: secret,
+                session_token: env::var("AWS_SESSION_TOKEN").ok(),
+                expiration: None,
+            });
+        }
+        if let Ok(uri) = env::var("AWS_CONTAINER_CREDENTIALS_RELATIVE_URI") {
+            return self.fetch(self.agent.get(&format!("http://169.254.170.2{}", uri)));
+        }
+        if let Ok(url) = env::var("AWS_CONTAINER_CREDENTIALS_FULL_URI") {
+            let mut request = self.agent.get(&url);
+            if let Ok(token) = env::var("AWS_CONTAINER_AUTHORIZATION_TOKEN") {
+                request = request.set("Authorization", &token);
+            }
+            return self.fetch(request);
+        }
+        self.resolve_imds()
+    }
+
+    /// Credentials for the instance role via IMDSv2
+    fn resolve_imds(&self) -> io::Result<Credentials> {
+        let base = "http://169.254.169.254/latest";
+        let token = self.agent
+            .put(&format!("{}/api/token", base))
+            .set("X-aws-ec2-metadata-token-ttl-seconds", "21600")
+            .call()
+            .map_err(other)?
+            .into_string()?;
+        let roles_url = format!("{}/meta-data/iam/security-credentials/", base);
+        let role = self.agent
+            .get(&roles_url)
+            .set("X-aws-ec2-metadata-token", &token)
+            .call()
+            .map_err(other)?
+            .into_string()?;
+        let role = role.lines().next().unwrap_or("").trim();
+        if role.is_empty() {
+            return Err(other("no instance role found"));
+        }
+        self.fetch(
+            self.agent
+                .get(&format!("{}{}", roles_url, role))
+                .set("X-aws-ec2-metadata-token", &token),
+        )
+    }
+
+    fn fetch(&self, request: ureq::Request) -> io::Result<Credentials> {
+        let body = request.call().map_err(other)?.into_string()?;
+        Credentials::from_json(&serde_json::from_str(&body)?)
+    }
+}
+
+/// Canonical request, and its signed header names, for `headers` sorted
+/// by name
+fn canonical_request(
+    method: &str,
+    path: &str,
+    headers: &[(String, String)],
+    payload_hash: &str,
+) -> (String, String) {
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{}:{}\n", name, value))
+        .collect();
+    let signed_headers = headers
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical_request = format!(
+        "{}\n{}\n\n{}\n{}\n{}",
+        method, path, canonical_headers, signed_headers, payload_hash
+    );
+    (canonical_request, signed_headers)
+}
+
+fn string_to_sign(amz_date: &str, scope: &str, canonical_request: &str) -> String {
+    format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        sha256_hex(canonical_request.as_bytes())
+    )
+}
+
+fn signature(
+    secret_access_key: &str,
+    date: &str,
+    region: &str,
+    service: &str,
+    string_to_sign: &str,
+) -> String {
+    let key = hmac(format!("AWS4{}", secret_access_key).as_bytes(), date.as_bytes());
+    let key = hmac(&key, region.as_bytes());
+    let key = hmac(&key, service.as_bytes());
+    let key = hmac(&key, b"aws4_request");
+    hex(&hmac(&key, string_to_sign.as_bytes()))
+}
+
+/// Sign a request with AWS Signature Version 4
+///
+/// Returns the headers to add: `Authorization`, `X-Amz-Date`, the payload
+/// hash and, for temporary credentials, `X-Amz-Security-Token`. `headers`
+/// must include `host` and any other headers to be signed, with lowercase
+/// names.
+pub(crate) fn sign(
+    creds: &Credentials,
+    region: &str,
+    service: &str,
+    method: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    payload: &[u8],
+) -> Vec<(String, String)> {
+    let now = Utc::now();
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = now.format("%Y%m%d").to_string();
+    let payload_hash = sha256_hex(payload);
+
+    let mut signed: Vec<(String, String)> = headers
+        .iter()
+        .map(|&(name, value)| (name.to_owned(), value.trim().to_owned()))
+        .collect();
+    signed.push(("x-amz-date".to_owned(), amz_date.clone()));
+    signed.push(("x-amz-content-sha256".to_owned(), payload_hash.clone()));
+    if let Some(ref token) = creds.session_token {
+        signed.push(("x-amz-security-token".to_owned(), token.clone()));
+    }
+    signed.sort();
+
+    let (canonical_request, signed_headers) =
+        canonical_request(method, path, &signed, &payload_hash);
+    let scope = format!("{}/{}/{}/aws4_request", date, region, service);
+    let string_to_sign = string_to_sign(&amz_date, &scope, &canonical_request);
+    let signature = signature(&creds.secret_access_key, &date, region, service, &string_to_sign);
+
+    let mut out = vec![
+        (
+            "Authorization".to_owned(),
+            format!(
+                "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+                creds.access_key_id, scope, signed_headers, signature
+            ),
+        ),
+        ("X-Amz-Date".to_owned(), amz_date),
+        ("X-Amz-Content-Sha256".to_owned(), payload_hash),
+    ];
+    if let Some(ref token) = creds.session_token {
+        out.push(("X-Amz-Security-Token".to_owned(), token.clone()));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{canonical_request, sha256_hex, signature, string_to_sign};
+
+    // From AWS's Signature Version 4 test suite
+    const SECRET: &str = "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY";
+    const AMZ_DATE: &str = "20150830T123600Z";
+    const SCOPE: &str = "20150830/us-east-1/service/aws4_request";
+
+    /// String to sign and signature of a request with the suite's
+    /// credentials, checking its canonical request along the way
+    fn sign(method: &str, expected_canonical: &str) -> (String, String) {
+        let headers = vec![
+            ("host".to_owned(), "example.amazonaws.com".to_owned()),
+            ("x-amz-date".to_owned(), AMZ_DATE.to_owned()),
+        ];
+        let (canonical, signed_headers) =
+            canonical_request(method, "/", &headers, &sha256_hex(b""));
+        assert_eq!(canonical, expected_canonical);
+        assert_eq!(signed_headers, "host;x-amz-date");
+        let string_to_sign = string_to_sign(AMZ_DATE, SCOPE, &canonical);
+        let signature = signature(SECRET, "20150830", "us-east-1", "service", &string_to_sign);
+        (string_to_sign, signature)
+    }
+
+    #[test]
+    fn signs_get_vanilla() {
+        let (string_to_sign, signature) = sign(
+            "GET",
+            "GET\n/\n\nhost:example.amazonaws.com\nx-amz-date:20150830T123600Z\n\n\
+             host;x-amz-date\n\
+             e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+        );
+        assert_eq!(
+            string_to_sign,
+            "AWS4-HMAC-SHA256\n20150830T123600Z\n20150830/us-east-1/service/aws4_request\n\
+             bb579772317eb040ac9ed261061d46c1f17a8133879d6129b6e1c25292927e63"
+        );
+        assert_eq!(signature, "5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31");
+    }
+
+    #[test]
+    fn signs_post_vanilla() {
+        let (string_to_sign, signature) = sign(
+            "POST",
+            "POST\n/\n\nhost:example.amazonaws.com\nx-amz-date:20150830T123600Z\n\n\
+             host;x-amz-date\n\
+             e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+        );
+        assert_eq!(
+            string_to_sign,
+            "AWS4-HMAC-SHA256\n20150830T123600Z\n20150830/us-east-1/service/aws4_request\n\
+             553f88c9e4d10fc9e109e2aeb65f030801b70c2f6468faca261d401ae622fc87"
+        );
+        assert_eq!(signature, "5da7c1a2acd57cee7505fc6676e4e544621c30862966e37dddb68e92efbe5d6b");
+    }
+}
@@ -0,0 +1,56 @@
+use std::{env, time::Duration};
+
+use serde_json::{self, Value};
+use ureq;
+
+/// Static fields describing the ECS task or Lambda function we run in, if
+/// any. A failed metadata lookup just yields fewer fields.
+///
+/// Lambda request ids change per invocation, so they can't be static
+/// fields; log them from the handler instead.
+pub(crate) fn fields() -> Vec<(&'static str, String)> {
+    let mut fields = lambda();
+    if let Ok(uri) = env::var("ECS_CONTAINER_METADATA_URI_V4") {
+        fields.extend(ecs_task(&uri));
+    }
+    fields
+}
+
+fn lambda() -> Vec<(&'static str, String)> {
+    [
+        ("lambda_function_name", "AWS_LAMBDA_FUNCTION_NAME"),
+        ("lambda_function_version", "AWS_LAMBDA_FUNCTION_VERSION"),
+        ("lambda_log_stream", "AWS_LAMBDA_LOG_STREAM_NAME"),
+    ]
+    .iter()
+    .filter_map(|&(key, var)| env::var(var).ok().map(|value| (key, value)))
+    .collect()
+}
+
+fn ecs_task(uri: &str) -> Vec<(&'static str, String)> {
+    let agent = ureq::AgentBuilder::new().timeout(Duration::from_secs(2)).build();
+    let task: Value = match agent
+        .get(&format!("{}/task", uri))
+        .call()
+        .ok()
+        .and_then(|resp| resp.into_string().ok())
+        .and_then(|body| serde_json::from_str(&body).ok())
+    {
+        Some(task) => task,
+        None => return vec![],
+    };
+    [
+        ("ecs_task_arn", "TaskARN"),
+        ("ecs_cluster", "Cluster"),
+        ("ecs_task_family", "Family"),
+        ("ecs_task_revision", "Revision"),
+        ("ecs_availability_zone", "AvailabilityZone"),
+    ]
+    .iter()
+    .filter_map(|&(key, field)| {
+        task.get(field)
+            .and_then(Value::as_str)
+            .map(|value| (key, value.to_owned()))
+    })
+    .collect()
+}
@@ -0,0 +1,561 @@
+use std::{cmp, io, mem, thread, collections::VecDeque,
+          sync::{Arc, Condvar, Mutex, PoisonError}, time::{Duration, Instant}};
+
+use slog::Level;
+
+use clock::now_nanos;
+use context;
+use drain::{encode_value, write_framed, Encoding};
+use util::{process_id, LineBuffer};
+
+/// What `BackgroundWriter` does with a record when its queue is full
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Wait for the writer thread to make room
+    Block,
+    /// Drop the oldest queued record
+    DropOldest,
+    /// Drop the new record
+    DropNewest,
+}
+
+struct State {
+    /// Records, with their level if written by the drain
+    queue: VecDeque<(Option<Level>, Vec<u8>)>,
+    /// Flushes requested and completed, as sequence numbers
+    flush_requested: u64,
+    flushed: u64,
+    /// Records dropped in total, and since the last report
+    dropped: u64,
+    unreported: u64,
+    closed: bool,
+    /// Whether the writer thread is still running, rather than having
+    /// panicked (e.g. in the underlying writer)
+    running: bool,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    cond: Condvar,
+}
+
+/// Marks the writer thread stopped however it exits, waking anyone waiting
+/// on it
+struct Running<'a>(&'a Shared);
+
+impl<'a> Drop for Running<'a> {
+    fn drop(&mut self) {
+        self.0.state.lock().unwrap_or_else(PoisonError::into_inner).running = false;
+        self.0.cond.notify_all();
+    }
+}
+
+/// Splits the bytes written into records, as framed by their encoding
+enum Records {
+    Lines(LineBuffer),
+    /// Length prefixed, by a 4 byte big-endian length or a varint
+    Framed(Encoding, Vec<u8>),
+    /// Written whole, with no framing
+    #[cfg(feature = "cbor")]
+    Writes,
+}
+
+impl Records {
+    fn new(encoding: Encoding) -> Self {
+        match encoding {
+            Encoding::Json => Records::Lines(LineBuffer::new()),
+            #[cfg(feature = "cbor")]
+            Encoding::Cbor => Records::Writes,
+            _ => Records::Framed(encoding, vec![]),
+        }
+    }
+
+    /// Append `buf`, passing each completed record (including its framing)
+    /// to `f`
+    fn push<F: FnMut(Vec<u8>)>(&mut self, buf: &[u8], mut f: F) {
+        match *self {
+            Records::Lines(ref mut lines) => lines.push(buf, f),
+            Records::Framed(encoding, ref mut partial) => {
+                partial.extend_from_slice(buf);
+                while let Some(len) = frame_len(encoding, partial) {
+                    let rest = partial.split_off(len);
+                    f(mem::replace(partial, rest));
+                }
+            }
+            #[cfg(feature = "cbor")]
+            Records::Writes => f(buf.to_vec()),
+        }
+    }
+}
+
+/// Length of the complete record, with its prefix, at the start of `buf`
+fn frame_len(encoding: Encoding, buf: &[u8]) -> Option<usize> {
+    let (prefix, len) = match encoding {
+        Encoding::LogEntry => {
+            let mut len = 0;
+            let mut prefix = 0;
+            loop {
+                let byte = *buf.get(prefix)?;
+                len |= u64::from(byte & 0x7f) << (7 * prefix);
+                prefix += 1;
+                if byte & 0x80 == 0 || prefix == 10 {
+                    break;
+                }
+            }
+            (prefix, len as usize)
+        }
+        _ => {
+            let len = buf.get(..4)?;
+            (4, u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize)
+        }
+    };
+    if buf.len() >= prefix + len {
+        Some(prefix + len)
+    } else {
+        None
+    }
+}
+
+/// Report of records dropped since the last one, as a MozLog record
+fn dropped_record(logger: &str, pid: Option<u32>, dropped: u64, encoding: Encoding) -> Vec<u8> {
+    let mut record = json!({
+        "Timestamp": now_nanos(),
+        "Logger": logger,
+        "Type": "mozlog.dropped",
+        "Severity": 4,
+        "Fields": {
+            "msg": format!("{} log records dropped", dropped),
+            "dropped": dropped,
+        },
+    });
+    if let Some(pid) = pid {
+        record["Pid"] = json!(pid);
+    }
+    let mut payload = vec![];
+    let mut framed = vec![];
+    // Writing to a `Vec` can't fail, nor can encoding a `Value` map
+    let _ = encode_value(encoding, &record, &mut payload)
+        .and_then(|_| write_framed(encoding, true, true, &mut framed, &mut payload));
+    framed
+}
+
+fn run<W: io::Write>(
+    mut io: W,
+    shared: &Shared,
+    logger: &str,
+    pid: Option<u32>,
+    report_interval: Option<Duration>,
+    encoding: Encoding,
+) {
+    let _running = Running(shared);
+    let mut last_report = Instant::now();
+    loop {
+        let (records, flush_target, unreported, closed) = {
+            let mut state = shared.state.lock().unwrap_or_else(PoisonError::into_inner);
+            while state.queue.is_empty() && state.flushed == state.flush_requested
+                && !state.closed
+            {
+                let report_interval = match report_interval {
+                    Some(report_interval) => report_interval,
+                    None => {
+                        state = shared.cond.wait(state).unwrap_or_else(PoisonError::into_inner);
+                        continue;
+                    }
+                };
+                let elapsed = last_report.elapsed();
+                if state.unreported > 0 && elapsed >= report_interval {
+                    break;
+                }
+                let wait = if state.unreported > 0 {
+                    report_interval - elapsed
+                } else {
+                    report_interval
+                };
+                state = shared.cond.wait_timeout(state, wait).unwrap_or_else(PoisonError::into_inner).0;
+            }
+            let unreported = match report_interval {
+                Some(report_interval) if last_report.elapsed() >= report_interval => {
+                    mem::replace(&mut state.unreported, 0)
+                }
+                _ => 0,
+            };
+            let records = mem::take(&mut state.queue);
+            // Wake writers waiting for room
+            shared.cond.notify_all();
+            (records, state.flush_requested, unreported, state.closed)
+        };
+
+        let mut failed = 0;
+        for (level, record) in records {
+            let res = match level {
+                Some(level) => context::with_record_level(level, || io.write_all(&record)),
+                None => io.write_all(&record),
+            };
+            if res.is_err() {
+                failed += 1;
+            }
+        }
+        if unreported > 0 {
+            last_report = Instant::now();
+            let _ = io.write_all(&dropped_record(logger, pid, unreported, encoding));
+        }
+        let _ = io.flush();
+
+        let mut state = shared.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.dropped += failed;
+        state.unreported += failed;
+        state.flushed = flush_target;
+        shared.cond.notify_all();
+        if closed && state.queue.is_empty() {
+            return;
+        }
+    }
+}
+
+/// `io::Write` adapter writing to an underlying writer on a dedicated
+/// thread
+///
+/// Complete records are queued, up to a limit, and written by a background
+/// thread, so logging doesn't wait on slow destinations. When the queue is
+/// full, the `OverflowPolicy` decides between blocking and dropping. The
+/// number of dropped records is periodically written to the destination as
+/// a `mozlog.dropped` record, in the encoding set with `set_encoding`.
+/// `flush` waits for queued records to be written and flushed; dropping
+/// the writer drains the queue first.
+pub struct BackgroundWriter {
+    shared: Arc<Shared>,
+    records: Records,
+    queue_limit: usize,
+    overflow: OverflowPolicy,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl BackgroundWriter {
+    /// Build a `BackgroundWriter` over `io`
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new<W>(io: W) -> BackgroundWriterBuilder<W>
+    where
+        W: io::Write + Send + 'static,
+    {
+        BackgroundWriterBuilder {
+            io,
+            queue_limit: 1024,
+            overflow: OverflowPolicy::DropOldest,
+            report_interval: Some(Duration::from_secs(60)),
+            logger_name: "mozlog".to_owned(),
+            pid: process_id(),
+            encoding: Encoding::Json,
+        }
+    }
+
+    /// Number of records dropped on a full queue or failed write
+    pub fn dropped(&self) -> u64 {
+        self.shared.state.lock().unwrap_or_else(PoisonError::into_inner).dropped
+    }
+
+    /// Handle reading this writer's queue once it's been moved into a
+    /// drain
+    pub fn queue_handle(&self) -> QueueHandle {
+        QueueHandle {
+            shared: self.shared.clone(),
+        }
+    }
+
+    fn push(&self, level: Option<Level>, record: Vec<u8>) {
+        let mut state = self.shared.state.lock().unwrap_or_else(PoisonError::into_inner);
+        while state.queue.len() >= self.queue_limit {
+            match self.overflow {
+                OverflowPolicy::Block if state.running => {
+                    state = self.shared.cond.wait(state).unwrap_or_else(PoisonError::into_inner)
+                }
+                // Nothing is left to make room
+                OverflowPolicy::Block => {
+                    state.dropped += 1;
+                    state.unreported += 1;
+                    return;
+                }
+                OverflowPolicy::DropOldest => {
+                    state.queue.pop_front();
+                    state.dropped += 1;
+                    state.unreported += 1;
+                }
+                OverflowPolicy::DropNewest => {
+                    state.dropped += 1;
+                    state.unreported += 1;
+                    return;
+                }
+            }
+        }
+        state.queue.push_back((level, record));
+        self.shared.cond.notify_all();
+    }
+}
+
+impl io::Write for BackgroundWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut records = vec![];
+        self.records.push(buf, |record| records.push(record));
+        let level = context::record_level();
+        for record in records {
+            self.push(level, record);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let mut state = self.shared.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.flush_requested += 1;
+        let target = state.flush_requested;
+        self.shared.cond.notify_all();
+        while state.flushed < target {
+            if !state.running {
+                return Err(io::Error::new(
+                    io::ErrorKind::BrokenPipe,
+                    "background writer thread stopped",
+                ));
+            }
+            state = self.shared.cond.wait(state).unwrap_or_else(PoisonError::into_inner);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for BackgroundWriter {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap_or_else(PoisonError::into_inner).closed = true;
+        self.shared.cond.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Handle reading a `BackgroundWriter`'s queue, e.g. for exporting as
+/// metrics
+///
+/// Create with `BackgroundWriter::queue_handle`.
+#[derive(Clone)]
+pub struct QueueHandle {
+    shared: Arc<Shared>,
+}
+
+impl QueueHandle {
+    /// Records waiting to be written
+    pub fn depth(&self) -> usize {
+        self.shared.state.lock().unwrap_or_else(PoisonError::into_inner).queue.len()
+    }
+
+    /// Number of records dropped on a full queue or failed write
+    pub fn dropped(&self) -> u64 {
+        self.shared.state.lock().unwrap_or_else(PoisonError::into_inner).dropped
+    }
+}
+
+/// `BackgroundWriter` builder
+///
+/// Create with `BackgroundWriter::new`.
+pub struct BackgroundWriterBuilder<W> {
+    io: W,
+    queue_limit: usize,
+    overflow: OverflowPolicy,
+    report_interval: Option<Duration>,
+    logger_name: String,
+    pid: Option<u32>,
+    encoding: Encoding,
+}
+
+impl<W> BackgroundWriterBuilder<W>
+where
+    W: io::Write + Send + 'static,
+{
+    /// Build `BackgroundWriter`, starting its writer thread
+    pub fn build(self) -> io::Result<BackgroundWriter> {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                queue: VecDeque::new(),
+                flush_requested: 0,
+                flushed: 0,
+                dropped: 0,
+                unreported: 0,
+                closed: false,
+                running: true,
+            }),
+            cond: Condvar::new(),
+        });
+        let thread = {
+            let shared = shared.clone();
+            let io = self.io;
+            let logger_name = self.logger_name;
+            let pid = self.pid;
+            let report_interval = self.report_interval;
+            let encoding = self.encoding;
+            thread::Builder::new()
+                .name("mozlog-background".to_owned())
+                .spawn(move || run(io, &shared, &logger_name, pid, report_interval, encoding))?
+        };
+        Ok(BackgroundWriter {
+            shared,
+            records: Records::new(self.encoding),
+            queue_limit: self.queue_limit,
+            overflow: self.overflow,
+            thread: Some(thread),
+        })
+    }
+
+    /// Set the maximum number of queued records (default 1024, at least 1)
+    pub fn set_queue_limit(mut self, records: usize) -> Self {
+        self.queue_limit = cmp::max(records, 1);
+        self
+    }
+
+    /// Set what happens to records when the queue is full (default
+    /// `DropOldest`)
+    pub fn set_overflow(mut self, overflow: OverflowPolicy) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    /// Set how often dropped records are reported (default every minute,
+    /// zero for never)
+    pub fn set_report_interval(mut self, interval: Duration) -> Self {
+        self.report_interval = if interval.is_zero() { None } else { Some(interval) };
+        self
+    }
+
+    /// Set the encoding of the drain writing to this writer, by which
+    /// records are split and the dropped records report is encoded
+    /// (default `Encoding::Json`)
+    pub fn set_encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Set the `Logger` of the dropped records report
+    pub fn set_logger_name(mut self, logger_name: String) -> Self {
+        self.logger_name = logger_name;
+        self
+    }
+
+    /// Set the `Pid` of the dropped records report, or omit it with `None`,
+    /// as with the drain's `set_pid` (default the process id, omitted on
+    /// WebAssembly)
+    pub fn set_pid(mut self, pid: Option<u32>) -> Self {
+        self.pid = pid;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, io::{self, Write}, sync::{Arc, Mutex}, time::Duration};
+
+    use serde_json::{self, Value};
+
+    use super::{BackgroundWriter, BackgroundWriterBuilder, OverflowPolicy};
+
+    /// Fails records containing `fail`, appending others to a buffer
+    struct Picky(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Picky {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if buf.windows(4).any(|w| w == b"fail") {
+                return Err(io::Error::other("rejected"));
+            }
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// What a writer `configure` sets up writes after failing to write
+    /// `record`
+    fn after_failure<F>(configure: F, record: &[u8]) -> Vec<u8>
+    where
+        F: FnOnce(BackgroundWriterBuilder<Picky>) -> BackgroundWriterBuilder<Picky>,
+    {
+        let out = Arc::new(Mutex::new(vec![]));
+        {
+            let mut writer = configure(BackgroundWriter::new(Picky(out.clone())))
+                .build()
+                .unwrap();
+            writer.write_all(record).unwrap();
+            writer.flush().unwrap();
+            thread::sleep(Duration::from_millis(10));
+        }
+        let out = out.lock().unwrap();
+        out.clone()
+    }
+
+    fn dropped_report(pid: Option<u32>) -> Value {
+        let out = after_failure(
+            |builder| builder.set_report_interval(Duration::from_millis(1)).set_pid(pid),
+            b"fail\n",
+        );
+        serde_json::from_slice(&out).unwrap()
+    }
+
+    #[test]
+    fn reports_dropped_with_pid() {
+        let report = dropped_report(Some(42));
+        assert_eq!(report["Type"], "mozlog.dropped");
+        assert_eq!(report["Fields"]["dropped"], 1);
+        assert_eq!(report["Pid"], 42);
+        assert!(dropped_report(None).get("Pid").is_none());
+    }
+
+    #[test]
+    fn never_reports_at_zero_interval() {
+        let out = after_failure(|builder| builder.set_report_interval(Duration::ZERO), b"fail\n");
+        assert!(out.is_empty());
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn reports_dropped_in_msgpack() {
+        use drain::Encoding;
+
+        let out = after_failure(
+            |builder| {
+                builder
+                    .set_report_interval(Duration::from_millis(1))
+                    .set_encoding(Encoding::MessagePack)
+            },
+            b"\0\0\0\x04fail",
+        );
+        let len = u32::from_be_bytes([out[0], out[1], out[2], out[3]]) as usize;
+        assert_eq!(out.len(), 4 + len);
+        let report: Value = rmp_serde::from_slice(&out[4..]).unwrap();
+        assert_eq!(report["Type"], "mozlog.dropped");
+        assert_eq!(report["Fields"]["dropped"], 1);
+    }
+
+    struct Panicking;
+
+    impl Write for Panicking {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            panic!("writer failed");
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn stops_waiting_for_dead_thread() {
+        let mut writer = BackgroundWriter::new(Panicking)
+            .set_queue_limit(1)
+            .set_overflow(OverflowPolicy::Block)
+            .build()
+            .unwrap();
+        writer.write_all(b"{}\n").unwrap();
+        assert!(writer.flush().is_err());
+        // The queue is full with nothing to drain it
+        writer.write_all(b"{}\n{}\n").unwrap();
+        assert!(writer.flush().is_err());
+        assert!(writer.dropped() >= 1);
+    }
+}
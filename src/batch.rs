@@ -0,0 +1,301 @@
+use std::{cmp, io, thread, sync::Arc, sync::atomic::{AtomicUsize, Ordering}, sync::mpsc,
+          time::{Duration, Instant}};
+
+use util::LineBuffer;
+
+/// Why delivering a batch failed
+pub(crate) enum SendError {
+    /// Worth retrying (network failure, throttling, server error)
+    Transient(io::Error),
+    /// Retrying won't help (e.g. rejected request)
+    #[cfg_attr(not(feature = "http"), allow(dead_code))]
+    Permanent(io::Error),
+}
+
+/// Delivers batches of records for `BatchWriter`
+pub(crate) trait BatchSink: Send + 'static {
+    /// Deliver `batch`, each record without its trailing newline
+    fn send(&mut self, batch: &[Vec<u8>]) -> Result<(), SendError>;
+
+    /// Called with a batch that is being dropped after failing delivery
+    fn failed(&mut self, _batch: &[Vec<u8>], _err: &io::Error) {}
+}
+
+/// Batching and backpressure settings for batching sinks
+///
+/// A batch is sent once it reaches `max_records` or `max_bytes`, or
+/// `max_delay` after its first record arrived, whichever comes first.
+#[derive(Clone, Debug)]
+pub struct BatchConfig {
+    max_records: usize,
+    max_bytes: usize,
+    max_delay: Duration,
+    queue_limit: usize,
+    block_on_full: bool,
+    max_retries: u32,
+    retry_backoff: Duration,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        BatchConfig {
+            max_records: 500,
+            max_bytes: 1024 * 1024,
+            max_delay: Duration::from_secs(1),
+            queue_limit: 10_000,
+            block_on_full: false,
+            max_retries: 3,
+            retry_backoff: Duration::from_millis(200),
+        }
+    }
+}
+
+impl BatchConfig {
+    /// New `BatchConfig` with default settings
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the maximum number of records per batch
+    pub fn set_max_records(mut self, records: usize) -> Self {
+        self.max_records = records;
+        self
+    }
+
+    /// Set the maximum size of a batch in bytes
+    pub fn set_max_bytes(mut self, bytes: usize) -> Self {
+        self.max_bytes = bytes;
+        self
+    }
+
+    /// Set how long a record may wait for its batch to fill
+    pub fn set_max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    /// Set the number of records queued for the sender thread before
+    /// backpressure applies
+    pub fn set_queue_limit(mut self, records: usize) -> Self {
+        self.queue_limit = records;
+        self
+    }
+
+    /// Set whether logging blocks when the queue is full, rather than
+    /// dropping the record
+    pub fn set_block_on_full(mut self, enabled: bool) -> Self {
+        self.block_on_full = enabled;
+        self
+    }
+
+    /// Set how many times a failed batch is retried, and the initial delay
+    /// between attempts (doubled on each retry)
+    pub fn set_retries(mut self, retries: u32, backoff: Duration) -> Self {
+        self.max_retries = retries;
+        self.retry_backoff = backoff;
+        self
+    }
+
+    /// Cap the batch size to a destination's limits
+    #[cfg_attr(
+        not(any(feature = "azure", feature = "cloud-logging", feature = "cloudwatch")),
+        allow(dead_code)
+    )]
+    pub(crate) fn limit(mut self, max_records: usize, max_bytes: usize) -> Self {
+        self.max_records = cmp::min(self.max_records, max_records);
+        self.max_bytes = cmp::min(self.max_bytes, max_bytes);
+        self
+    }
+
+    #[cfg_attr(not(feature = "cloudwatch"), allow(dead_code))]
+    pub(crate) fn max_records(&self) -> usize {
+        self.max_records
+    }
+
+    /// Maximum retries and initial delay between them
+    #[cfg_attr(not(feature = "elasticsearch"), allow(dead_code))]
+    pub(crate) fn retries(&self) -> (u32, Duration) {
+        (self.max_retries, self.retry_backoff)
+    }
+}
+
+enum Msg {
+    Record(Vec<u8>),
+    Flush(mpsc::SyncSender<()>),
+}
+
+/// `io::Write` that hands newline terminated records to a `BatchSink` on a
+/// dedicated thread
+pub(crate) struct BatchWriter {
+    tx: Option<mpsc::SyncSender<Msg>>,
+    handle: Option<thread::JoinHandle<()>>,
+    lines: LineBuffer,
+    block_on_full: bool,
+    dropped: Arc<AtomicUsize>,
+}
+
+impl BatchWriter {
+    pub(crate) fn spawn<S: BatchSink>(name: &str, sink: S, config: BatchConfig) -> io::Result<Self> {
+        let (tx, rx) = mpsc::sync_channel(config.queue_limit);
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let worker = Worker {
+            sink,
+            config: config.clone(),
+            dropped: dropped.clone(),
+        };
+        let handle = thread::Builder::new()
+            .name(name.to_owned())
+            .spawn(move || worker.run(&rx))?;
+        Ok(BatchWriter {
+            tx: Some(tx),
+            handle: Some(handle),
+            lines: LineBuffer::new(),
+            block_on_full: config.block_on_full,
+            dropped,
+        })
+    }
+
+    /// Number of records dropped on a full queue or failed delivery
+    pub(crate) fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    fn queue(&self, msg: Msg) -> io::Result<()> {
+        let tx = self.tx.as_ref().unwrap();
+        let res = if self.block_on_full {
+            tx.send(msg).map_err(|_| ())
+        } else {
+            match tx.try_send(msg) {
+                Ok(()) => Ok(()),
+                Err(mpsc::TrySendError::Full(_)) => {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                    Ok(())
+                }
+                Err(mpsc::TrySendError::Disconnected(_)) => Err(()),
+            }
+        };
+        res.map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "batch sender thread exited"))
+    }
+}
+
+impl io::Write for BatchWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut records = vec![];
+        self.lines.push(buf, |mut record| {
+            record.pop();
+            records.push(record);
+        });
+        for record in records {
+            self.queue(Msg::Record(record))?;
+        }
+        Ok(buf.len())
+    }
+
+    /// Send any partially filled batch and wait for it to be delivered
+    fn flush(&mut self) -> io::Result<()> {
+        let (ack_tx, ack_rx) = mpsc::sync_channel(1);
+        self.tx
+            .as_ref()
+            .unwrap()
+            .send(Msg::Flush(ack_tx))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "batch sender thread exited"))?;
+        ack_rx
+            .recv()
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "batch sender thread exited"))
+    }
+}
+
+impl Drop for BatchWriter {
+    fn drop(&mut self) {
+        // Closing the channel makes the worker send what's left and exit
+        self.tx.take();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+struct Worker<S: BatchSink> {
+    sink: S,
+    config: BatchConfig,
+    dropped: Arc<AtomicUsize>,
+}
+
+impl<S: BatchSink> Worker<S> {
+    fn run(mut self, rx: &mpsc::Receiver<Msg>) {
+        let mut batch = vec![];
+        let mut bytes = 0;
+        let mut deadline = None;
+        loop {
+            let msg = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        Err(mpsc::RecvTimeoutError::Timeout)
+                    } else {
+                        rx.recv_timeout(deadline - now)
+                    }
+                }
+                None => rx.recv().map_err(|_| mpsc::RecvTimeoutError::Disconnected),
+            };
+            match msg {
+                Ok(Msg::Record(record)) => {
+                    if !batch.is_empty() && bytes + record.len() > self.config.max_bytes {
+                        self.send(&mut batch);
+                        bytes = 0;
+                    }
+                    if batch.is_empty() {
+                        deadline = Some(Instant::now() + self.config.max_delay);
+                    }
+                    bytes += record.len();
+                    batch.push(record);
+                    if batch.len() >= self.config.max_records {
+                        self.send(&mut batch);
+                        bytes = 0;
+                        deadline = None;
+                    }
+                }
+                Ok(Msg::Flush(ack)) => {
+                    self.send(&mut batch);
+                    bytes = 0;
+                    deadline = None;
+                    let _ = ack.send(());
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    self.send(&mut batch);
+                    bytes = 0;
+                    deadline = None;
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    self.send(&mut batch);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Deliver and clear `batch`, retrying transient failures
+    fn send(&mut self, batch: &mut Vec<Vec<u8>>) {
+        if batch.is_empty() {
+            return;
+        }
+        let mut backoff = self.config.retry_backoff;
+        let mut attempt = 0;
+        loop {
+            match self.sink.send(batch) {
+                Ok(()) => break,
+                Err(SendError::Transient(_)) if attempt < self.config.max_retries => {
+                    thread::sleep(backoff);
+                    backoff *= 2;
+                    attempt += 1;
+                }
+                Err(SendError::Transient(err)) | Err(SendError::Permanent(err)) => {
+                    self.dropped.fetch_add(batch.len(), Ordering::Relaxed);
+                    self.sink.failed(batch, &err);
+                    break;
+                }
+            }
+        }
+        batch.clear();
+    }
+}
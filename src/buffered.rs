@@ -0,0 +1,150 @@
+use std::{cmp, io, mem, thread, io::IoSlice, sync::{Arc, Mutex, Weak},
+          time::{Duration, Instant}};
+
+struct Inner<W: io::Write> {
+    io: W,
+    buf: Vec<u8>,
+    /// When the oldest buffered record was written
+    since: Option<Instant>,
+}
+
+impl<W: io::Write> Inner<W> {
+    /// Write out buffered complete records, keeping any partial one
+    fn write_records(&mut self) -> io::Result<()> {
+        let end = match self.buf.iter().rposition(|b| *b == b'\n') {
+            Some(pos) => pos + 1,
+            None => return Ok(()),
+        };
+        let rest = self.buf.split_off(end);
+        let records = mem::replace(&mut self.buf, rest);
+        self.since = if self.buf.is_empty() {
+            None
+        } else {
+            Some(Instant::now())
+        };
+        self.io.write_all(&records)?;
+        self.io.flush()
+    }
+}
+
+/// Flush records that have waited longer than `max_delay`, until the
+/// writer is dropped
+fn run<W: io::Write>(inner: &Weak<Mutex<Inner<W>>>, max_delay: Duration) {
+    let tick = cmp::max(max_delay / 2, Duration::from_millis(10));
+    loop {
+        thread::sleep(tick);
+        let inner = match inner.upgrade() {
+            Some(inner) => inner,
+            None => return,
+        };
+        let mut inner = inner.lock().unwrap();
+        if inner.since.is_some_and(|since| since.elapsed() >= max_delay) {
+            let _ = inner.write_records();
+        }
+    }
+}
+
+/// `io::Write` adapter coalescing records into fewer, larger writes
+///
+/// Records are buffered and written to the underlying writer, with one
+/// flush, once the buffer reaches `max_bytes` or its oldest record has
+/// waited `max_delay` (checked by a background thread). Only complete
+/// records are written out early. `flush` and dropping the writer write
+/// everything buffered.
+pub struct BufferedWriter<W: io::Write + Send + 'static> {
+    inner: Arc<Mutex<Inner<W>>>,
+    max_bytes: usize,
+}
+
+impl<W: io::Write + Send + 'static> BufferedWriter<W> {
+    /// Build a `BufferedWriter` over `io`
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(io: W) -> BufferedWriterBuilder<W> {
+        BufferedWriterBuilder {
+            io,
+            max_bytes: 64 * 1024,
+            max_delay: Duration::from_secs(1),
+        }
+    }
+
+    fn append(&mut self, bufs: &[IoSlice]) -> io::Result<usize> {
+        let mut inner = self.inner.lock().unwrap();
+        let mut len = 0;
+        for buf in bufs {
+            inner.buf.extend_from_slice(buf);
+            len += buf.len();
+        }
+        if inner.since.is_none() && !inner.buf.is_empty() {
+            inner.since = Some(Instant::now());
+        }
+        if inner.buf.len() >= self.max_bytes {
+            inner.write_records()?;
+        }
+        Ok(len)
+    }
+}
+
+impl<W: io::Write + Send + 'static> io::Write for BufferedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.append(&[IoSlice::new(buf)])
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice]) -> io::Result<usize> {
+        self.append(bufs)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        let buf = mem::take(&mut inner.buf);
+        inner.since = None;
+        inner.io.write_all(&buf)?;
+        inner.io.flush()
+    }
+}
+
+impl<W: io::Write + Send + 'static> Drop for BufferedWriter<W> {
+    fn drop(&mut self) {
+        let _ = io::Write::flush(self);
+    }
+}
+
+/// `BufferedWriter` builder
+///
+/// Create with `BufferedWriter::new`.
+pub struct BufferedWriterBuilder<W> {
+    io: W,
+    max_bytes: usize,
+    max_delay: Duration,
+}
+
+impl<W: io::Write + Send + 'static> BufferedWriterBuilder<W> {
+    /// Build `BufferedWriter`, starting its flusher thread
+    pub fn build(self) -> io::Result<BufferedWriter<W>> {
+        let inner = Arc::new(Mutex::new(Inner {
+            io: self.io,
+            buf: Vec::with_capacity(self.max_bytes),
+            since: None,
+        }));
+        let weak = Arc::downgrade(&inner);
+        let max_delay = self.max_delay;
+        thread::Builder::new()
+            .name("mozlog-buffered".to_owned())
+            .spawn(move || run(&weak, max_delay))?;
+        Ok(BufferedWriter {
+            inner,
+            max_bytes: self.max_bytes,
+        })
+    }
+
+    /// Set the buffer size that triggers a write (default 64KiB)
+    pub fn set_max_bytes(mut self, bytes: usize) -> Self {
+        self.max_bytes = bytes;
+        self
+    }
+
+    /// Set how long a record may wait in the buffer (default 1s)
+    pub fn set_max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+}
@@ -0,0 +1,36 @@
+/// Deploy metadata for `MozLogJsonBuilder::add_build_info`, usually
+/// captured with `build_info!()`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BuildInfo {
+    /// Crate version
+    pub version: &'static str,
+    /// Git commit SHA
+    pub commit: Option<&'static str>,
+    /// Build timestamp
+    pub build_time: Option<&'static str>,
+}
+
+/// Capture the calling crate's `BuildInfo`: its version, and the
+/// `GIT_COMMIT` and `BUILD_TIMESTAMP` environment variables at compile time
+/// (e.g. exported by CI or a build script's `cargo:rustc-env`), if set
+///
+/// ```
+/// #[macro_use]
+/// extern crate slog_mozlog_json;
+///
+/// fn main() {
+///     let drain = slog_mozlog_json::MozLogJson::new(std::io::stderr())
+///         .add_build_info(build_info!())
+///         .build();
+/// }
+/// ```
+#[macro_export]
+macro_rules! build_info {
+    () => {
+        $crate::BuildInfo {
+            version: env!("CARGO_PKG_VERSION"),
+            commit: option_env!("GIT_COMMIT"),
+            build_time: option_env!("BUILD_TIMESTAMP"),
+        }
+    };
+}
@@ -0,0 +1,103 @@
+use std::{io::{self, BufRead}, sync::{Mutex, PoisonError}};
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+const CHAIN_FIELD: &[u8] = b",\"Chain\":\"";
+
+fn link(key: &[u8], prev: &[u8], record: &[u8]) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(prev);
+    mac.update(record);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn unhex(hex: &[u8]) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    hex.chunks(2)
+        .map(|pair| {
+            let pair = ::std::str::from_utf8(pair).ok()?;
+            u8::from_str_radix(pair, 16).ok()
+        })
+        .collect()
+}
+
+/// Per output stream HMAC chains over JSON records
+pub(crate) struct HashChain {
+    key: Vec<u8>,
+    /// Previous link of each stream, empty before its first record
+    prev: Vec<Mutex<Vec<u8>>>,
+}
+
+impl HashChain {
+    pub(crate) fn new(key: Vec<u8>, streams: usize) -> Self {
+        HashChain {
+            key,
+            prev: (0..streams).map(|_| Mutex::new(vec![])).collect(),
+        }
+    }
+
+    /// Append the `Chain` field to a JSON record written to `stream`.
+    ///
+    /// Must be called with the stream's writer locked, so links follow the
+    /// order records are written in.
+    pub(crate) fn append(&self, stream: usize, record: &mut Vec<u8>) {
+        let mut prev = self.prev[stream]
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let next = link(&self.key, &prev, record);
+        record.pop();
+        record.extend_from_slice(CHAIN_FIELD);
+        record.extend_from_slice(hex(&next).as_bytes());
+        record.extend_from_slice(b"\"}");
+        *prev = next;
+    }
+}
+
+/// Verify the `Chain` fields of a stream of newline delimited records
+/// written with `MozLogJsonBuilder::set_hash_chain(key)`, returning the
+/// number of records verified.
+///
+/// Fails with `InvalidData` at the first record that was altered, removed,
+/// reordered or inserted.
+pub fn verify_chain<R: BufRead>(key: &[u8], input: R) -> io::Result<u64> {
+    let mut prev = vec![];
+    let mut count = 0;
+    for (i, line) in input.split(b'\n').enumerate() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let broken = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("hash chain broken at line {}", i + 1),
+            )
+        };
+        let start = line
+            .windows(CHAIN_FIELD.len())
+            .rposition(|window| window == CHAIN_FIELD)
+            .ok_or_else(broken)?;
+        if !line.ends_with(b"\"}") {
+            return Err(broken());
+        }
+        let found = unhex(&line[start + CHAIN_FIELD.len()..line.len() - 2]).ok_or_else(broken)?;
+        let mut record = line[..start].to_vec();
+        record.push(b'}');
+        let expected = link(key, &prev, &record);
+        if found != expected {
+            return Err(broken());
+        }
+        prev = expected;
+        count += 1;
+    }
+    Ok(count)
+}
@@ -0,0 +1,210 @@
+use std::{panic::RefUnwindSafe, sync::Arc, sync::atomic::{AtomicI64, Ordering}, time::Duration};
+#[cfg(not(target_os = "linux"))]
+use std::{thread, sync::Once, sync::atomic::AtomicBool};
+#[cfg(not(feature = "chrono"))]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "chrono")]
+use chrono;
+#[cfg(target_os = "linux")]
+use libc;
+
+/// Source of record `Timestamp`s, see `MozLogJsonBuilder::set_clock`
+pub trait Clock: Send + Sync + RefUnwindSafe {
+    /// Current time in nanoseconds since the epoch
+    fn now_nanos(&self) -> i64;
+}
+
+/// The system clock, the default
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_nanos(&self) -> i64 {
+        now_nanos()
+    }
+}
+
+/// The system clock, read with `coarse_now_nanos`
+pub(crate) struct CoarseClock;
+
+impl Clock for CoarseClock {
+    fn now_nanos(&self) -> i64 {
+        coarse_now_nanos()
+    }
+}
+
+/// Clock stuck at a time, in nanoseconds since the epoch, e.g. so tests
+/// can match whole records
+#[derive(Clone, Copy, Debug)]
+pub struct FixedClock(pub i64);
+
+impl Clock for FixedClock {
+    fn now_nanos(&self) -> i64 {
+        self.0
+    }
+}
+
+/// Clock moved only by hand, e.g. to replay recorded events at their
+/// original times
+///
+/// Clones share the time, so keep one to move a drain's clock.
+#[derive(Clone, Debug, Default)]
+pub struct ManualClock {
+    nanos: Arc<AtomicI64>,
+}
+
+impl ManualClock {
+    /// New `ManualClock` at `nanos` since the epoch
+    pub fn new(nanos: i64) -> Self {
+        ManualClock {
+            nanos: Arc::new(AtomicI64::new(nanos)),
+        }
+    }
+
+    /// Set the time, in nanoseconds since the epoch
+    pub fn set(&self, nanos: i64) {
+        self.nanos.store(nanos, Ordering::Relaxed);
+    }
+
+    /// Move the time forward by `by`
+    pub fn advance(&self, by: Duration) {
+        self.nanos.fetch_add(by.as_nanos() as i64, Ordering::Relaxed);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now_nanos(&self) -> i64 {
+        self.nanos.load(Ordering::Relaxed)
+    }
+}
+
+/// Current time in nanoseconds since the epoch
+#[cfg(feature = "chrono")]
+pub(crate) fn now_nanos() -> i64 {
+    let now = chrono::Utc::now();
+    now.timestamp() * 1_000_000_000 + i64::from(now.timestamp_subsec_nanos())
+}
+
+/// Current time in nanoseconds since the epoch
+#[cfg(not(feature = "chrono"))]
+pub(crate) fn now_nanos() -> i64 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(since) => since.as_nanos() as i64,
+        Err(before) => -(before.duration().as_nanos() as i64),
+    }
+}
+
+/// Current UTC time for naming files, e.g. `20240131T235959.123Z`
+#[cfg(feature = "chrono")]
+pub(crate) fn file_stamp() -> String {
+    chrono::Utc::now().format("%Y%m%dT%H%M%S%.3fZ").to_string()
+}
+
+/// Current UTC time for naming files, e.g. `20240131T235959.123Z`
+#[cfg(not(feature = "chrono"))]
+pub(crate) fn file_stamp() -> String {
+    let nanos = now_nanos();
+    let secs = nanos.div_euclid(1_000_000_000);
+    let (year, month, day) = civil_date(secs.div_euclid(86_400));
+    let time = secs.rem_euclid(86_400);
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60,
+        nanos.rem_euclid(1_000_000_000) / 1_000_000
+    )
+}
+
+/// Year, month and day of `days` since the epoch, in the proleptic
+/// Gregorian calendar (Howard Hinnant's `civil_from_days`)
+#[cfg(not(feature = "chrono"))]
+fn civil_date(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    // Months counted from March, so the leap day comes last
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Current time to within a few milliseconds, in nanoseconds since the
+/// epoch
+///
+/// Reads `CLOCK_REALTIME_COARSE`, which the vDSO serves without a syscall.
+#[cfg(target_os = "linux")]
+// `time_t` and `c_long` are only 32 bits on some targets
+#[allow(clippy::unnecessary_cast)]
+pub(crate) fn coarse_now_nanos() -> i64 {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    if unsafe { libc::clock_gettime(libc::CLOCK_REALTIME_COARSE, &mut ts) } != 0 {
+        return now_nanos();
+    }
+    ts.tv_sec as i64 * 1_000_000_000 + ts.tv_nsec as i64
+}
+
+/// Current time to within about a millisecond, in nanoseconds since the
+/// epoch
+///
+/// Reads a timestamp cached by a background thread ticking every
+/// millisecond, started on first use.
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn coarse_now_nanos() -> i64 {
+    static START: Once = Once::new();
+    static TICKING: AtomicBool = AtomicBool::new(false);
+    static NOW: AtomicI64 = AtomicI64::new(0);
+
+    START.call_once(|| {
+        NOW.store(now_nanos(), Ordering::Relaxed);
+        let tick = thread::Builder::new()
+            .name("mozlog-clock".to_owned())
+            .spawn(|| loop {
+                thread::sleep(Duration::from_millis(1));
+                NOW.store(now_nanos(), Ordering::Relaxed);
+            });
+        TICKING.store(tick.is_ok(), Ordering::Relaxed);
+    });
+    if TICKING.load(Ordering::Relaxed) {
+        NOW.load(Ordering::Relaxed)
+    } else {
+        now_nanos()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Clock, FixedClock, ManualClock};
+
+    #[test]
+    fn fixed_clock_stands_still() {
+        let clock = FixedClock(1_500_000_000_000_000_000);
+        assert_eq!(clock.now_nanos(), 1_500_000_000_000_000_000);
+        assert_eq!(clock.now_nanos(), clock.now_nanos());
+    }
+
+    #[test]
+    fn manual_clock_moves_by_hand() {
+        let clock = ManualClock::new(1_000);
+        // Clones share the time
+        let handle = clock.clone();
+        handle.advance(Duration::from_micros(2));
+        assert_eq!(clock.now_nanos(), 3_000);
+        handle.set(10);
+        assert_eq!(clock.now_nanos(), 10);
+    }
+}
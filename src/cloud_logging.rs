@@ -0,0 +1,234 @@
+use std::{io, time::Duration};
+
+use chrono::{self, SecondsFormat, TimeZone};
+use serde_json::{self, Map, Value};
+use ureq;
+
+use batch::{BatchConfig, BatchSink, BatchWriter, SendError};
+use gcp::{self, AccessToken};
+use http::ureq_error;
+
+/// `entries.write` limits, leaving headroom for the request envelope
+const MAX_BATCH_ENTRIES: usize = 1000;
+const MAX_BATCH_BYTES: usize = 9 * 1_048_576;
+
+/// Cloud Logging `LogSeverity` for a MozLog (syslog) `Severity`
+fn log_severity(severity: Option<u64>) -> &'static str {
+    match severity {
+        Some(0) => "EMERGENCY",
+        Some(1) => "ALERT",
+        Some(2) => "CRITICAL",
+        Some(3) => "ERROR",
+        Some(4) => "WARNING",
+        Some(5) => "NOTICE",
+        Some(6) => "INFO",
+        Some(7) => "DEBUG",
+        _ => "DEFAULT",
+    }
+}
+
+/// `LogEntry` for one record: the record as `jsonPayload`, with its
+/// `Timestamp` and `Severity` mapped to the entry's own fields
+fn log_entry(record: &[u8]) -> Value {
+    let payload = match serde_json::from_slice(record) {
+        Ok(Value::Object(object)) => object,
+        _ => {
+            let mut object = Map::new();
+            object.insert(
+                "message".to_owned(),
+                Value::String(String::from_utf8_lossy(record).into_owned()),
+            );
+            object
+        }
+    };
+    let ns = payload.get("Timestamp").and_then(Value::as_i64);
+    let severity = log_severity(payload.get("Severity").and_then(Value::as_u64));
+    let mut entry = json!({
+        "severity": severity,
+        "jsonPayload": payload,
+    });
+    if let Some(ns) = ns {
+        if let Some(time) = chrono::Utc
+            .timestamp_opt(ns / 1_000_000_000, (ns % 1_000_000_000) as u32)
+            .single()
+        {
+            entry["timestamp"] = Value::String(time.to_rfc3339_opts(SecondsFormat::Nanos, true));
+        }
+    }
+    entry
+}
+
+struct CloudLoggingSink {
+    agent: ureq::Agent,
+    endpoint: String,
+    token: AccessToken,
+    log_name: String,
+    resource: Value,
+}
+
+impl BatchSink for CloudLoggingSink {
+    fn send(&mut self, batch: &[Vec<u8>]) -> Result<(), SendError> {
+        let body = json!({
+            "logName": self.log_name,
+            "resource": self.resource,
+            "entries": batch.iter().map(|record| log_entry(record)).collect::<Vec<_>>(),
+            // Write the valid entries of a batch even if some are rejected
+            "partialSuccess": true,
+        });
+        let body = serde_json::to_vec(&body)
+            .map_err(|e| SendError::Permanent(io::Error::other(e)))?;
+        let token = self.token.get().map_err(SendError::Transient)?;
+        self.agent
+            .post(&self.endpoint)
+            .set("Authorization", &format!("Bearer {}", token))
+            .set("Content-Type", "application/json")
+            .send_bytes(&body)
+            .map_err(ureq_error)?;
+        Ok(())
+    }
+}
+
+/// Google Cloud Logging `io::Write` sink
+///
+/// Records are batched on a background thread (see `BatchConfig`, capped
+/// to the `entries.write` limits) and written directly through the Cloud
+/// Logging API, authenticating with Application Default Credentials, for
+/// hosts without the ops agent. Each record becomes an entry's
+/// `jsonPayload`, with its `Timestamp` and `Severity` as the entry's time
+/// and severity. Requires compact output with newlines enabled.
+pub struct CloudLoggingWriter {
+    inner: BatchWriter,
+}
+
+impl CloudLoggingWriter {
+    /// Build a `CloudLoggingWriter` writing to the log `log_id`
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(log_id: String) -> CloudLoggingWriterBuilder {
+        CloudLoggingWriterBuilder {
+            endpoint: "https://logging.googleapis.com/v2/entries:write".to_owned(),
+            log_id,
+            project: None,
+            resource: None,
+            timeout: Duration::from_secs(10),
+            batching: BatchConfig::default(),
+        }
+    }
+
+    /// Number of records dropped on a full queue or failed delivery
+    pub fn dropped(&self) -> usize {
+        self.inner.dropped()
+    }
+}
+
+impl io::Write for CloudLoggingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// `CloudLoggingWriter` builder
+///
+/// Create with `CloudLoggingWriter::new`.
+pub struct CloudLoggingWriterBuilder {
+    endpoint: String,
+    log_id: String,
+    project: Option<String>,
+    resource: Option<(String, Vec<(String, String)>)>,
+    timeout: Duration,
+    batching: BatchConfig,
+}
+
+impl CloudLoggingWriterBuilder {
+    /// Build `CloudLoggingWriter`, starting its sender thread
+    ///
+    /// Without an explicit resource, a `gce_instance` resource is detected
+    /// from the metadata server, falling back to `global`.
+    pub fn build(self) -> io::Result<CloudLoggingWriter> {
+        let token = AccessToken::application_default()?;
+        let project = match self.project.or_else(|| token.project_id()) {
+            Some(project) => project,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "no Cloud Logging project configured or detected",
+                ))
+            }
+        };
+        let resource = match self.resource {
+            Some((kind, labels)) => {
+                let labels: Map<String, Value> = labels
+                    .into_iter()
+                    .map(|(name, value)| (name, Value::String(value)))
+                    .collect();
+                json!({"type": kind, "labels": labels})
+            }
+            None => detect_resource(&project),
+        };
+        let sink = CloudLoggingSink {
+            agent: ureq::AgentBuilder::new().timeout(self.timeout).build(),
+            endpoint: self.endpoint,
+            token,
+            log_name: format!("projects/{}/logs/{}", project, self.log_id.replace('/', "%2F")),
+            resource,
+        };
+        let batching = self.batching.limit(MAX_BATCH_ENTRIES, MAX_BATCH_BYTES);
+        Ok(CloudLoggingWriter {
+            inner: BatchWriter::spawn("mozlog-cloud-logging", sink, batching)?,
+        })
+    }
+
+    /// Set the project, rather than the credentials' or metadata server's
+    pub fn set_project(mut self, project: String) -> Self {
+        self.project = Some(project);
+        self
+    }
+
+    /// Set the monitored resource descriptor, e.g. `gce_instance` with
+    /// `instance_id` and `zone` labels
+    pub fn set_resource(mut self, kind: String, labels: Vec<(String, String)>) -> Self {
+        self.resource = Some((kind, labels));
+        self
+    }
+
+    /// Set the API endpoint, e.g. for Private Google Access
+    pub fn set_endpoint(mut self, endpoint: String) -> Self {
+        self.endpoint = endpoint;
+        self
+    }
+
+    /// Set the timeout for each request
+    pub fn set_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set batching and backpressure behavior
+    pub fn set_batching(mut self, batching: BatchConfig) -> Self {
+        self.batching = batching;
+        self
+    }
+}
+
+/// `gce_instance` when running on Compute Engine, otherwise `global`
+fn detect_resource(project: &str) -> Value {
+    let agent = ureq::AgentBuilder::new().timeout(Duration::from_secs(1)).build();
+    let instance = gcp::metadata(&agent, "instance/id").and_then(|id| {
+        let zone = gcp::metadata(&agent, "instance/zone")?;
+        Ok((id, zone.rsplit('/').next().unwrap_or("").to_owned()))
+    });
+    match instance {
+        Ok((instance_id, zone)) => json!({
+            "type": "gce_instance",
+            "labels": {
+                "project_id": project,
+                "instance_id": instance_id,
+                "zone": zone,
+            },
+        }),
+        Err(_) => json!({"type": "global", "labels": {"project_id": project}}),
+    }
+}
@@ -0,0 +1,313 @@
+use std::{io, time::Duration};
+
+use chrono;
+use serde_json::{self, Value};
+use ureq;
+
+use aws::{self, CredentialsProvider};
+use batch::{BatchConfig, BatchSink, BatchWriter, SendError};
+
+/// `PutLogEvents` limits
+const MAX_BATCH_EVENTS: usize = 10_000;
+const MAX_BATCH_BYTES: usize = 1_048_576;
+const EVENT_OVERHEAD: usize = 26;
+const MAX_EVENT_BYTES: usize = 262_144 - EVENT_OVERHEAD;
+
+enum CallError {
+    Status(u16, Value),
+    Transport(io::Error),
+}
+
+fn error_type(body: &Value) -> &str {
+    let ty = body.get("__type").and_then(Value::as_str).unwrap_or("");
+    ty.rsplit('#').next().unwrap_or(ty)
+}
+
+fn call_error(err: CallError) -> SendError {
+    match err {
+        CallError::Status(code, ref body)
+            if code >= 500 || error_type(body) == "ThrottlingException" =>
+        {
+            SendError::Transient(io::Error::other(body.to_string()))
+        }
+        CallError::Status(_, body) => {
+            SendError::Permanent(io::Error::other(body.to_string()))
+        }
+        CallError::Transport(e) => SendError::Transient(e),
+    }
+}
+
+struct CloudWatchSink {
+    agent: ureq::Agent,
+    endpoint: String,
+    region: String,
+    group: String,
+    stream: String,
+    credentials: CredentialsProvider,
+    create_stream: bool,
+    sequence_token: Option<String>,
+}
+
+impl CloudWatchSink {
+    /// Make a signed CloudWatch Logs API call
+    fn call(&self, action: &str, body: &Value) -> Result<Value, CallError> {
+        let payload = serde_json::to_vec(body).map_err(|e| CallError::Transport(e.into()))?;
+        let creds = self.credentials.get().map_err(CallError::Transport)?;
+        let host = self.endpoint
+            .trim_start_matches("https://")
+            .trim_start_matches("http://")
+            .trim_end_matches('/');
+        let target = format!("Logs_20140328.{}", action);
+        let signed = aws::sign(
+            &creds,
+            &self.region,
+            "logs",
+            "POST",
+            "/",
+            &[
+                ("content-type", "application/x-amz-json-1.1"),
+                ("host", host),
+                ("x-amz-target", &target),
+            ],
+            &payload,
+        );
+        let mut request = self.agent
+            .post(&self.endpoint)
+            .set("Content-Type", "application/x-amz-json-1.1")
+            .set("X-Amz-Target", &target);
+        for (name, value) in &signed {
+            request = request.set(name, value);
+        }
+        match request.send_bytes(&payload) {
+            Ok(resp) => {
+                let body = resp.into_string().map_err(CallError::Transport)?;
+                Ok(serde_json::from_str(&body).unwrap_or(Value::Null))
+            }
+            Err(ureq::Error::Status(code, resp)) => {
+                let body = resp.into_string().unwrap_or_default();
+                Err(CallError::Status(
+                    code,
+                    serde_json::from_str(&body).unwrap_or(Value::Null),
+                ))
+            }
+            Err(ureq::Error::Transport(e)) => Err(CallError::Transport(io::Error::other(
+                e.to_string(),
+            ))),
+        }
+    }
+
+    fn create_log_stream(&self) -> Result<(), CallError> {
+        let body = json!({
+            "logGroupName": self.group,
+            "logStreamName": self.stream,
+        });
+        match self.call("CreateLogStream", &body) {
+            Err(CallError::Status(_, ref body))
+                if error_type(body) == "ResourceAlreadyExistsException" =>
+            {
+                Ok(())
+            }
+            res => res.map(|_| ()),
+        }
+    }
+}
+
+/// Event for one record: the record's `Timestamp` in milliseconds (or the
+/// current time) and its JSON, truncated to the maximum event size
+fn log_event(record: &[u8]) -> (i64, String) {
+    let timestamp = serde_json::from_slice::<Value>(record)
+        .ok()
+        .and_then(|parsed| parsed.get("Timestamp").and_then(Value::as_i64))
+        .map(|ns| ns / 1_000_000)
+        .unwrap_or_else(|| {
+            let now = chrono::Utc::now();
+            now.timestamp() * 1000 + i64::from(now.timestamp_subsec_millis())
+        });
+    let mut message = String::from_utf8_lossy(record).into_owned();
+    if message.len() > MAX_EVENT_BYTES {
+        let mut end = MAX_EVENT_BYTES;
+        while !message.is_char_boundary(end) {
+            end -= 1;
+        }
+        message.truncate(end);
+    }
+    (timestamp, message)
+}
+
+impl BatchSink for CloudWatchSink {
+    fn send(&mut self, batch: &[Vec<u8>]) -> Result<(), SendError> {
+        let mut events: Vec<(i64, String)> = batch.iter().map(|record| log_event(record)).collect();
+        // Events must be in chronological order
+        events.sort_by_key(|&(timestamp, _)| timestamp);
+        let events: Vec<Value> = events
+            .into_iter()
+            .map(|(timestamp, message)| json!({"timestamp": timestamp, "message": message}))
+            .collect();
+
+        let mut created = false;
+        let mut resynced = false;
+        loop {
+            let mut body = json!({
+                "logGroupName": self.group,
+                "logStreamName": self.stream,
+                "logEvents": events,
+            });
+            if let Some(ref token) = self.sequence_token {
+                body["sequenceToken"] = Value::String(token.clone());
+            }
+            let err = match self.call("PutLogEvents", &body) {
+                Ok(resp) => {
+                    self.sequence_token = resp.get("nextSequenceToken")
+                        .and_then(Value::as_str)
+                        .map(str::to_owned);
+                    return Ok(());
+                }
+                Err(err) => err,
+            };
+            if let CallError::Status(_, ref body) = err {
+                match error_type(body) {
+                    "DataAlreadyAcceptedException" => {
+                        self.sequence_token = body.get("expectedSequenceToken")
+                            .and_then(Value::as_str)
+                            .map(str::to_owned);
+                        return Ok(());
+                    }
+                    "InvalidSequenceTokenException" if !resynced => {
+                        resynced = true;
+                        self.sequence_token = body.get("expectedSequenceToken")
+                            .and_then(Value::as_str)
+                            .map(str::to_owned);
+                        continue;
+                    }
+                    "ResourceNotFoundException" if self.create_stream && !created => {
+                        created = true;
+                        self.sequence_token = None;
+                        self.create_log_stream().map_err(call_error)?;
+                        continue;
+                    }
+                    _ => (),
+                }
+            }
+            return Err(call_error(err));
+        }
+    }
+}
+
+/// AWS CloudWatch Logs `io::Write` sink
+///
+/// Records are batched on a background thread (see `BatchConfig`, capped
+/// to the `PutLogEvents` limits) and shipped to a log stream, tracking the
+/// stream's sequence token. Each record's `Timestamp` becomes the event
+/// time. Requires compact output with newlines enabled.
+pub struct CloudWatchWriter {
+    inner: BatchWriter,
+}
+
+impl CloudWatchWriter {
+    /// Build a `CloudWatchWriter` for `log_group`/`log_stream` in `region`
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(region: String, log_group: String, log_stream: String) -> CloudWatchWriterBuilder {
+        CloudWatchWriterBuilder {
+            endpoint: format!("https://logs.{}.amazonaws.com/", region),
+            region,
+            group: log_group,
+            stream: log_stream,
+            credentials: None,
+            create_stream: true,
+            timeout: Duration::from_secs(10),
+            batching: BatchConfig::default(),
+        }
+    }
+
+    /// Number of records dropped on a full queue or failed delivery
+    pub fn dropped(&self) -> usize {
+        self.inner.dropped()
+    }
+}
+
+impl io::Write for CloudWatchWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// `CloudWatchWriter` builder
+///
+/// Create with `CloudWatchWriter::new`.
+pub struct CloudWatchWriterBuilder {
+    endpoint: String,
+    region: String,
+    group: String,
+    stream: String,
+    credentials: Option<CredentialsProvider>,
+    create_stream: bool,
+    timeout: Duration,
+    batching: BatchConfig,
+}
+
+impl CloudWatchWriterBuilder {
+    /// Build `CloudWatchWriter`, starting its sender thread
+    pub fn build(self) -> io::Result<CloudWatchWriter> {
+        let batching = self.batching.limit(MAX_BATCH_EVENTS, MAX_BATCH_BYTES);
+        let max_bytes = MAX_BATCH_BYTES - EVENT_OVERHEAD * batching.max_records();
+        let batching = batching.limit(MAX_BATCH_EVENTS, max_bytes);
+        let sink = CloudWatchSink {
+            agent: ureq::AgentBuilder::new().timeout(self.timeout).build(),
+            endpoint: self.endpoint,
+            region: self.region,
+            group: self.group,
+            stream: self.stream,
+            credentials: self.credentials
+                .unwrap_or_else(CredentialsProvider::default_chain),
+            create_stream: self.create_stream,
+            sequence_token: None,
+        };
+        Ok(CloudWatchWriter {
+            inner: BatchWriter::spawn("mozlog-cloudwatch", sink, batching)?,
+        })
+    }
+
+    /// Use fixed credentials rather than the default chain (environment,
+    /// ECS container credentials, then EC2 instance role)
+    pub fn set_credentials(
+        mut self,
+        access_key_id: String,
+        secret_access_key: String,
+        session_token: Option<String>,
+    ) -> Self {
+        self.credentials = Some(CredentialsProvider::fixed(
+            access_key_id,
+            secret_access_key,
+            session_token,
+        ));
+        self
+    }
+
+    /// Set the API endpoint, e.g. for a VPC endpoint
+    pub fn set_endpoint(mut self, endpoint: String) -> Self {
+        self.endpoint = endpoint;
+        self
+    }
+
+    /// Set whether a missing log stream is created
+    pub fn set_create_stream(mut self, enabled: bool) -> Self {
+        self.create_stream = enabled;
+        self
+    }
+
+    /// Set the timeout for each request
+    pub fn set_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set batching and backpressure behavior
+    pub fn set_batching(mut self, batching: BatchConfig) -> Self {
+        self.batching = batching;
+        self
+    }
+}
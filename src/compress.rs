@@ -0,0 +1,121 @@
+use std::io;
+
+#[cfg(feature = "gzip")]
+use flate2::{Compression, write::GzEncoder};
+#[cfg(feature = "zstd")]
+use zstd;
+
+enum Encoder<W: io::Write> {
+    #[cfg(feature = "gzip")]
+    Gzip(GzEncoder<W>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::stream::write::Encoder<'static, W>),
+}
+
+impl<W: io::Write> Encoder<W> {
+    fn get_mut(&mut self) -> &mut dyn io::Write {
+        match *self {
+            #[cfg(feature = "gzip")]
+            Encoder::Gzip(ref mut encoder) => encoder,
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(ref mut encoder) => encoder,
+        }
+    }
+
+    fn finish(self) -> io::Result<W> {
+        match self {
+            #[cfg(feature = "gzip")]
+            Encoder::Gzip(encoder) => encoder.finish(),
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(encoder) => encoder.finish(),
+        }
+    }
+}
+
+/// Compressing `io::Write` adapter
+///
+/// Compresses the record stream with gzip or zstd before writing it to the
+/// underlying writer. The compressor is flushed (so everything written so
+/// far can be decompressed) every so many uncompressed bytes or records;
+/// larger boundaries compress better. The stream is finished when the
+/// writer is dropped, or explicitly with `finish`.
+pub struct CompressedWriter<W: io::Write> {
+    encoder: Option<Encoder<W>>,
+    flush_bytes: usize,
+    flush_records: usize,
+    pending_bytes: usize,
+    pending_records: usize,
+}
+
+impl<W: io::Write> CompressedWriter<W> {
+    fn with_encoder(encoder: Encoder<W>) -> Self {
+        CompressedWriter {
+            encoder: Some(encoder),
+            flush_bytes: 64 * 1024,
+            flush_records: 0,
+            pending_bytes: 0,
+            pending_records: 0,
+        }
+    }
+
+    /// New gzip `CompressedWriter` at compression `level` (0-9)
+    #[cfg(feature = "gzip")]
+    pub fn gzip(io: W, level: u32) -> Self {
+        Self::with_encoder(Encoder::Gzip(GzEncoder::new(io, Compression::new(level))))
+    }
+
+    /// New zstd `CompressedWriter` at compression `level` (1-22, or 0 for
+    /// zstd's default)
+    #[cfg(feature = "zstd")]
+    pub fn zstd(io: W, level: i32) -> io::Result<Self> {
+        Ok(Self::with_encoder(Encoder::Zstd(
+            zstd::stream::write::Encoder::new(io, level)?,
+        )))
+    }
+
+    /// Flush after this many uncompressed bytes (0 to disable, default
+    /// 64KiB)
+    pub fn set_flush_bytes(mut self, bytes: usize) -> Self {
+        self.flush_bytes = bytes;
+        self
+    }
+
+    /// Flush after this many records (0 to disable, the default)
+    pub fn set_flush_records(mut self, records: usize) -> Self {
+        self.flush_records = records;
+        self
+    }
+
+    /// Finish the compressed stream, returning the underlying writer
+    pub fn finish(mut self) -> io::Result<W> {
+        self.encoder.take().unwrap().finish()
+    }
+}
+
+impl<W: io::Write> io::Write for CompressedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.encoder.as_mut().unwrap().get_mut().write(buf)?;
+        self.pending_bytes += written;
+        self.pending_records += buf[..written].iter().filter(|b| **b == b'\n').count();
+        if (self.flush_bytes > 0 && self.pending_bytes >= self.flush_bytes)
+            || (self.flush_records > 0 && self.pending_records >= self.flush_records)
+        {
+            self.flush()?;
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.pending_bytes = 0;
+        self.pending_records = 0;
+        self.encoder.as_mut().unwrap().get_mut().flush()
+    }
+}
+
+impl<W: io::Write> Drop for CompressedWriter<W> {
+    fn drop(&mut self) {
+        if let Some(encoder) = self.encoder.take() {
+            let _ = encoder.finish();
+        }
+    }
+}
@@ -0,0 +1,340 @@
+use std::{io, collections::BTreeMap, time::Duration};
+
+#[cfg(feature = "redact")]
+use regex::Regex;
+use serde::Deserialize;
+use slog::Level;
+
+use adaptive::SampleBudget;
+use drain::{Encoding, LoggerFallback, MozLogJsonBuilder};
+use env::{invalid, open_output};
+use filter::parse_level;
+#[cfg(feature = "redact")]
+use scrub::Pii;
+
+/// `MozLogJsonBuilder` settings, e.g. from a service's TOML or YAML config
+///
+/// Every setting is optional, leaving the builder's default. Outputs are
+/// named as by `MOZLOG_OUTPUT` (`stdout`, `file:<path>`, ..); levels as in
+/// `MOZLOG_LEVEL` (`info`, `warn`, ..); durations are in seconds. Unknown
+/// settings are an error, so typos don't go unnoticed.
+///
+/// ```toml
+/// [logging]
+/// level = "info,hyper=warn"
+/// logger_name = "autopush"
+/// secret_keys = ["password", "token", "fxa_uid"]
+/// rate_limit = { per_sec = 1000, burst = 5000 }
+/// ```
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MozLogConfig {
+    /// Main writer, stdout by default
+    pub output: Option<String>,
+    /// Minimum level and per-module directives, e.g. `info,hyper=warn`
+    pub level: Option<String>,
+    pub logger_name: Option<String>,
+    /// `module_path` or `crate_name`
+    pub logger_fallback: Option<String>,
+    pub msg_type: Option<String>,
+    pub hostname: Option<String>,
+    pub env_version: Option<String>,
+    /// `json`, `cbor`, `log_entry` or `msgpack`
+    pub encoding: Option<String>,
+    pub pretty: Option<bool>,
+    pub newlines: Option<bool>,
+    pub single_write: Option<bool>,
+    pub coarse_clock: Option<bool>,
+    pub seq: Option<bool>,
+    pub uptime: Option<bool>,
+    pub start_time: Option<bool>,
+    #[cfg(feature = "session-id")]
+    pub session_id: Option<bool>,
+    pub location_fields: Option<bool>,
+    pub thread_fields: Option<bool>,
+    pub stack_traces: Option<bool>,
+    /// Writer for records at this level or more severe
+    pub split: Option<SplitConfig>,
+    pub route_key: Option<String>,
+    /// Writers for records whose route key has a given value
+    pub routes: BTreeMap<String, String>,
+    pub echo_on_error: Option<usize>,
+    pub field_allowlist: Option<Vec<String>>,
+    pub field_denylist: Option<Vec<String>>,
+    pub mask_secrets: Option<bool>,
+    pub secret_keys: Option<Vec<String>>,
+    pub max_value_len: Option<usize>,
+    pub max_fields: Option<usize>,
+    /// Patterns redacted from `msg`
+    #[cfg(feature = "redact")]
+    pub redactions: Vec<String>,
+    /// `email`, `ipv4`, `ipv6` and/or `phone`
+    #[cfg(feature = "redact")]
+    pub pii_masking: Vec<String>,
+    #[cfg(feature = "pseudonymize")]
+    pub pseudonymize: Option<PseudonymizeConfig>,
+    /// Key of the `Chain` HMAC
+    #[cfg(feature = "audit")]
+    pub hash_chain_key: Option<String>,
+    pub sampling: Option<SamplingConfig>,
+    pub adaptive_sampling: Option<AdaptiveSamplingConfig>,
+    pub repeat_suppression: Option<RepeatConfig>,
+    pub rate_limit: Option<RateLimitConfig>,
+    pub level_rate_limits: Vec<LevelRateLimitConfig>,
+    pub rate_limit_report_interval: Option<f64>,
+}
+
+/// `MozLogConfig::split`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SplitConfig {
+    pub level: String,
+    pub output: String,
+}
+
+/// `MozLogConfig::pseudonymize`
+#[cfg(feature = "pseudonymize")]
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PseudonymizeConfig {
+    pub keys: Vec<String>,
+    pub salt: String,
+}
+
+/// `MozLogConfig::sampling`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SamplingConfig {
+    pub key: String,
+    pub rate: f64,
+}
+
+/// `MozLogConfig::adaptive_sampling`: one of the budgets
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdaptiveSamplingConfig {
+    pub records_per_sec: Option<f64>,
+    pub bytes_per_sec: Option<f64>,
+}
+
+/// `MozLogConfig::repeat_suppression`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RepeatConfig {
+    pub window: f64,
+    #[serde(default)]
+    pub keys: Vec<String>,
+}
+
+/// `MozLogConfig::rate_limit`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimitConfig {
+    pub per_sec: u32,
+    pub burst: u32,
+}
+
+/// An entry of `MozLogConfig::level_rate_limits`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LevelRateLimitConfig {
+    pub level: String,
+    pub per_sec: u32,
+    pub burst: u32,
+}
+
+fn level(value: &str) -> io::Result<Level> {
+    parse_level(value).ok_or_else(|| invalid("level", value))
+}
+
+fn encoding(value: &str) -> io::Result<Encoding> {
+    match value {
+        "json" => Ok(Encoding::Json),
+        #[cfg(feature = "cbor")]
+        "cbor" => Ok(Encoding::Cbor),
+        "log_entry" => Ok(Encoding::LogEntry),
+        #[cfg(feature = "msgpack")]
+        "msgpack" => Ok(Encoding::MessagePack),
+        _ => Err(invalid("encoding", value)),
+    }
+}
+
+#[cfg(feature = "redact")]
+pub(crate) fn pii(value: &str) -> io::Result<Pii> {
+    match value {
+        "email" => Ok(Pii::Email),
+        "ipv4" => Ok(Pii::Ipv4),
+        "ipv6" => Ok(Pii::Ipv6),
+        "phone" => Ok(Pii::Phone),
+        _ => Err(invalid("pii_masking", value)),
+    }
+}
+
+fn secs(secs: f64, name: &str) -> io::Result<Duration> {
+    if secs.is_finite() && secs >= 0.0 {
+        Ok(Duration::from_secs_f64(secs))
+    } else {
+        Err(invalid(name, &secs.to_string()))
+    }
+}
+
+impl MozLogJsonBuilder<Box<dyn io::Write + Send>> {
+    /// Builder configured by `cfg`
+    ///
+    /// Fails on unknown names (of outputs, levels, ..) or outputs that
+    /// can't be opened. `MOZLOG_LEVEL`, if set, is overridden by `level`.
+    pub fn from_config(cfg: MozLogConfig) -> io::Result<Self> {
+        let output = cfg.output.as_ref().map_or("stdout", String::as_str);
+        let mut builder = MozLogJsonBuilder::new(open_output(output)?);
+        if let Some(ref spec) = cfg.level {
+            builder = builder.set_directives(spec)?;
+        }
+        if let Some(logger_name) = cfg.logger_name {
+            builder = builder.logger_name(logger_name);
+        }
+        if let Some(fallback) = cfg.logger_fallback {
+            builder = builder.set_logger_fallback(match fallback.as_str() {
+                "module_path" => LoggerFallback::ModulePath,
+                "crate_name" => LoggerFallback::CrateName,
+                _ => return Err(invalid("logger_fallback", &fallback)),
+            });
+        }
+        if let Some(msg_type) = cfg.msg_type {
+            builder = builder.msg_type(msg_type);
+        }
+        if let Some(hostname) = cfg.hostname {
+            builder = builder.hostname(hostname);
+        }
+        if let Some(env_version) = cfg.env_version {
+            builder = builder.env_version(env_version);
+        }
+        if let Some(ref value) = cfg.encoding {
+            builder = builder.set_encoding(encoding(value)?);
+        }
+        if let Some(pretty) = cfg.pretty {
+            builder = builder.set_pretty(pretty);
+        }
+        if let Some(newlines) = cfg.newlines {
+            builder = builder.set_newlines(newlines);
+        }
+        if let Some(single_write) = cfg.single_write {
+            builder = builder.set_single_write(single_write);
+        }
+        if let Some(coarse_clock) = cfg.coarse_clock {
+            builder = builder.set_coarse_clock(coarse_clock);
+        }
+        if let Some(seq) = cfg.seq {
+            builder = builder.set_seq(seq);
+        }
+        if let Some(uptime) = cfg.uptime {
+            builder = builder.set_uptime(uptime);
+        }
+        if let Some(start_time) = cfg.start_time {
+            builder = builder.set_start_time(start_time);
+        }
+        #[cfg(feature = "session-id")]
+        {
+            if let Some(session_id) = cfg.session_id {
+                builder = builder.set_session_id(session_id);
+            }
+        }
+        if let Some(location_fields) = cfg.location_fields {
+            builder = builder.set_location_fields(location_fields);
+        }
+        if let Some(thread_fields) = cfg.thread_fields {
+            builder = builder.set_thread_fields(thread_fields);
+        }
+        if let Some(stack_traces) = cfg.stack_traces {
+            builder = builder.set_stack_traces(stack_traces);
+        }
+        if let Some(split) = cfg.split {
+            builder = builder.set_split(level(&split.level)?, open_output(&split.output)?);
+        }
+        if let Some(route_key) = cfg.route_key {
+            builder = builder.set_route_key(route_key);
+        }
+        for (value, output) in cfg.routes {
+            let io = open_output(&output)?;
+            builder = builder.set_route(value, io);
+        }
+        if let Some(capacity) = cfg.echo_on_error {
+            builder = builder.set_echo_on_error(capacity);
+        }
+        if let Some(keys) = cfg.field_allowlist {
+            builder = builder.set_field_allowlist(keys);
+        }
+        if let Some(keys) = cfg.field_denylist {
+            builder = builder.set_field_denylist(keys);
+        }
+        if let Some(mask_secrets) = cfg.mask_secrets {
+            builder = builder.set_mask_secrets(mask_secrets);
+        }
+        if let Some(keys) = cfg.secret_keys {
+            builder = builder.set_secret_keys(keys);
+        }
+        if let Some(len) = cfg.max_value_len {
+            builder = builder.set_max_value_len(len);
+        }
+        if let Some(max) = cfg.max_fields {
+            builder = builder.set_max_fields(max);
+        }
+        #[cfg(feature = "redact")]
+        {
+            for pattern in &cfg.redactions {
+                let regex = Regex::new(pattern).map_err(|_| invalid("redactions", pattern))?;
+                builder = builder.add_redaction(regex);
+            }
+            if !cfg.pii_masking.is_empty() {
+                let kinds = cfg.pii_masking
+                    .iter()
+                    .map(|kind| pii(kind))
+                    .collect::<io::Result<Vec<_>>>()?;
+                builder = builder.set_pii_masking(&kinds);
+            }
+        }
+        #[cfg(feature = "pseudonymize")]
+        {
+            if let Some(pseudonymize) = cfg.pseudonymize {
+                builder = builder
+                    .set_pseudonymized_fields(pseudonymize.keys, pseudonymize.salt.as_bytes());
+            }
+        }
+        #[cfg(feature = "audit")]
+        {
+            if let Some(ref key) = cfg.hash_chain_key {
+                builder = builder.set_hash_chain(key.as_bytes());
+            }
+        }
+        if let Some(sampling) = cfg.sampling {
+            builder = builder.set_sampling(sampling.key, sampling.rate);
+        }
+        if let Some(adaptive) = cfg.adaptive_sampling {
+            let budget = match (adaptive.records_per_sec, adaptive.bytes_per_sec) {
+                (Some(records), None) => SampleBudget::RecordsPerSec(records),
+                (None, Some(bytes)) => SampleBudget::BytesPerSec(bytes),
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "adaptive_sampling needs one of records_per_sec or bytes_per_sec",
+                    ))
+                }
+            };
+            builder = builder.set_adaptive_sampling(budget);
+        }
+        if let Some(repeats) = cfg.repeat_suppression {
+            let window = secs(repeats.window, "repeat_suppression.window")?;
+            builder = builder.set_repeat_suppression(window, repeats.keys);
+        }
+        if let Some(limit) = cfg.rate_limit {
+            builder = builder.set_rate_limit(limit.per_sec, limit.burst);
+        }
+        for limit in &cfg.level_rate_limits {
+            builder = builder.set_level_rate_limit(level(&limit.level)?, limit.per_sec, limit.burst);
+        }
+        if let Some(interval) = cfg.rate_limit_report_interval {
+            let interval = secs(interval, "rate_limit_report_interval")?;
+            builder = builder.set_rate_limit_report_interval(interval);
+        }
+        Ok(builder)
+    }
+}
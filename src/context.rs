@@ -0,0 +1,74 @@
+use std::cell::{Cell, RefCell};
+#[cfg(feature = "tokio")]
+use std::future::Future;
+
+use slog::Level;
+#[cfg(feature = "tokio")]
+use tokio::{self, task::futures::TaskLocalFuture};
+
+thread_local! {
+    static REQUEST_ID: RefCell<Option<String>> = const { RefCell::new(None) };
+    /// Level of the record the drain is writing on this thread, if any
+    static RECORD_LEVEL: Cell<Option<Level>> = const { Cell::new(None) };
+}
+
+#[cfg(feature = "tokio")]
+tokio::task_local! {
+    static TASK_REQUEST_ID: String;
+}
+
+/// Restores the thread's previous request ID when dropped
+///
+/// Create with `enter_request_id`.
+pub struct RequestIdGuard {
+    prev: Option<String>,
+}
+
+impl Drop for RequestIdGuard {
+    fn drop(&mut self) {
+        let prev = self.prev.take();
+        REQUEST_ID.with(|id| *id.borrow_mut() = prev);
+    }
+}
+
+/// Set the current thread's request ID, added to the Fields of every
+/// record it emits as `request_id` until the returned guard is dropped
+///
+/// For async handlers, which may hop threads, use `scope_request_id`
+/// instead.
+pub fn enter_request_id(id: String) -> RequestIdGuard {
+    let prev = REQUEST_ID.with(|current| current.borrow_mut().replace(id));
+    RequestIdGuard { prev }
+}
+
+/// Run `future` with a request ID, added to the Fields of every record
+/// emitted while polling it as `request_id`
+#[cfg(feature = "tokio")]
+pub fn scope_request_id<F: Future>(id: String, future: F) -> TaskLocalFuture<String, F> {
+    TASK_REQUEST_ID.scope(id, future)
+}
+
+/// Call `f` with the request ID of the current task, or else thread, if any
+pub(crate) fn with_request_id<F: FnOnce(Option<&str>) -> R, R>(f: F) -> R {
+    #[cfg(feature = "tokio")]
+    {
+        if let Ok(id) = TASK_REQUEST_ID.try_with(|id| id.clone()) {
+            return f(Some(&id));
+        }
+    }
+    REQUEST_ID.with(|id| f(id.borrow().as_ref().map(String::as_str)))
+}
+
+/// Run `f`, which writes a record of `level`, letting writers look the
+/// level up with `record_level`
+pub(crate) fn with_record_level<F: FnOnce() -> R, R>(level: Level, f: F) -> R {
+    let prev = RECORD_LEVEL.with(|current| current.replace(Some(level)));
+    let res = f();
+    RECORD_LEVEL.with(|current| current.set(prev));
+    res
+}
+
+/// Level of the record being written on this thread, if any
+pub(crate) fn record_level() -> Option<Level> {
+    RECORD_LEVEL.with(Cell::get)
+}
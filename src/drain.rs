@@ -7,11 +7,10 @@
 //! extern crate slog_mozlog_json;
 //!
 //! use slog::Drain;
-//! use std::sync::Mutex;
 //!
 //! fn main() {
 //!     let root = slog::Logger::root(
-//!         Mutex::new(slog_mozlog_json::MozLogJson::default(std::io::stderr())).map(slog::Fuse),
+//!         slog_mozlog_json::MozLogJson::default(std::io::stderr()).fuse(),
 //!         o!("version" => env!("CARGO_PKG_VERSION"))
 //!     );
 //! }
@@ -19,23 +18,115 @@
 // }}}
 
 // {{{ Imports & meta
-use std::{fmt, io, process, result, cell::RefCell, fmt::Write};
+use std::{env, fmt, io, result, thread, cell::RefCell, fmt::Write,
+          panic::{RefUnwindSafe, UnwindSafe},
+          sync::{Arc, Mutex, PoisonError, RwLock, atomic::{AtomicU64, Ordering}},
+          time::{Duration, Instant}};
+#[cfg(feature = "statsd")]
+use std::panic::AssertUnwindSafe;
 
-use chrono;
 use serde;
 use serde_json;
 use slog;
+#[cfg(feature = "statsd")]
+use cadence::{CountedExt, StatsdClient};
+#[cfg(feature = "cbor")]
+use ciborium;
+#[cfg(feature = "msgpack")]
+use rmp_serde;
+#[cfg(feature = "redact")]
+use regex::Regex;
+#[cfg(feature = "sonic")]
+use sonic_rs;
+#[cfg(feature = "session-id")]
+use uuid::Uuid;
 
 use serde::ser::SerializeMap;
-use slog::{FnValue, Key, OwnedKVList, Record, SendSyncRefUnwindSafeKV, KV};
+use serde_json::Value;
+use slog::{FnValue, Key, Level, OwnedKVList, Record, SendSyncRefUnwindSafeKV, KV};
 
-use util::level_to_severity;
+use clock::{Clock, CoarseClock, SystemClock};
+use adaptive::{AdaptiveSampler, SampleBudget};
+#[cfg(feature = "aws-metadata")]
+use aws_metadata;
+use build_info::BuildInfo;
+#[cfg(feature = "audit")]
+use chain::HashChain;
+use context;
+use echo::EchoBuffer;
+use env::{fields, flag};
+use extras::FieldExtras;
+use filter::{self, Directive, FieldFilter, LevelHandle, Predicate, RecordFields};
+use flush::{Flush, FlushHandle};
+use log_entry;
+#[cfg(feature = "prometheus")]
+use metrics::MozLogCollector;
+use rate_limit::RateLimiter;
+#[cfg(feature = "config")]
+use reload::ReloadHandle;
+use repeat::{RepeatSuppressor, Repeated};
+use sample::{find_key, Sampler};
+#[cfg(feature = "redact")]
+use scrub::Pii;
+use scrub::{FieldRules, Scrubbed};
+use stats::{Counters, DrainStats, StatsHandle};
+use util::{level_to_severity, process_id, secs, write_all_vectored};
 
 // }}}
 
 // {{{ Serialize
 thread_local! {
-    static TL_BUF: RefCell<String> = RefCell::new(String::with_capacity(128))
+    static TL_BUF: RefCell<String> = RefCell::new(String::with_capacity(128));
+    static TL_RECORD: RefCell<RecordBufs> = RefCell::new(RecordBufs::default())
+}
+
+/// Serializer for compact records: `serde_json`, or with the `sonic`
+/// feature the SIMD accelerated `sonic-rs`, as string escaping dominates
+/// the cost of logging
+#[cfg(not(feature = "sonic"))]
+type CompactSerializer<'a> = serde_json::Serializer<&'a mut Vec<u8>>;
+#[cfg(feature = "sonic")]
+type CompactSerializer<'a> = sonic_rs::Serializer<&'a mut Vec<u8>>;
+
+fn compact_serializer<'a>(buf: &'a mut Vec<u8>) -> CompactSerializer<'a> {
+    CompactSerializer::new(buf)
+}
+
+/// Largest buffer kept around for reuse by the next record
+const MAX_RETAINED_BUF: usize = 64 * 1024;
+
+/// StatsD counters of written records, most severe level first
+#[cfg(feature = "statsd")]
+const STATSD_KEYS: [&str; 6] = [
+    "mozlog.records.critical",
+    "mozlog.records.error",
+    "mozlog.records.warning",
+    "mozlog.records.info",
+    "mozlog.records.debug",
+    "mozlog.records.trace",
+];
+
+/// Per-thread buffers for assembling a record
+#[derive(Default)]
+struct RecordBufs {
+    /// Record with the `Fields` placeholder
+    body: Vec<u8>,
+    /// `Fields` map, missing its closing brace
+    fields: Vec<u8>,
+    /// Assembled record
+    out: Vec<u8>,
+}
+
+impl RecordBufs {
+    fn clear(&mut self) {
+        for buf in &mut [&mut self.body, &mut self.fields, &mut self.out] {
+            if buf.capacity() > MAX_RETAINED_BUF {
+                **buf = Vec::new();
+            } else {
+                buf.clear();
+            }
+        }
+    }
 }
 
 /// `slog::Serializer` adapter for `serde::Serializer`
@@ -146,18 +237,269 @@ where
         impl_m!(self, key, value.as_serde())
     }
 }
+
+/// Serialize a record's `Fields` entries into a started map: `msg`, the
+/// drain's `extras`, then the logger's and record's key-values, scrubbed by
+/// `rules`
+fn serialize_fields<S: serde::Serializer>(
+    serializer: &mut SerdeSerializer<S>,
+    rinfo: &Record,
+    logger_values: &OwnedKVList,
+    rules: &FieldRules,
+    extras: &FieldExtras,
+) -> slog::Result {
+    let msg = format!("{}", rinfo.msg());
+    let msg = rules.scrub(&msg);
+    kv!("msg" => &*msg).serialize(rinfo, serializer)?;
+    extras.serialize(rinfo, serializer)?;
+    if rules.is_empty() {
+        logger_values.serialize(rinfo, serializer)?;
+        return rinfo.kv().serialize(rinfo, serializer);
+    }
+    let mut scrubbed = Scrubbed::new(serializer, rules);
+    logger_values.serialize(rinfo, &mut scrubbed)?;
+    rinfo.kv().serialize(rinfo, &mut scrubbed)?;
+    if scrubbed.overflow > 0 {
+        kv!("fields_overflow" => scrubbed.overflow).serialize(rinfo, scrubbed.inner)?;
+    }
+    Ok(())
+}
+
+/// Where the `Logger` of records comes from when `logger_name` isn't set
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoggerFallback {
+    /// The record's module path, e.g. `my_crate::db`
+    ModulePath,
+    /// The crate of the record's module path, e.g. `my_crate`
+    CrateName,
+}
+
+impl LoggerFallback {
+    fn logger_name<'a>(self, rinfo: &Record<'a>) -> &'a str {
+        let module = rinfo.module();
+        match self {
+            LoggerFallback::ModulePath => module,
+            LoggerFallback::CrateName => module.split("::").next().unwrap_or(module),
+        }
+    }
+}
+
+impl KV for LoggerFallback {
+    fn serialize(&self, rinfo: &Record, serializer: &mut dyn slog::Serializer) -> slog::Result {
+        serializer.emit_str("Logger", self.logger_name(rinfo))
+    }
+}
+
+/// `Type`: the record's tag if it has one, else the builder's `msg_type`
+struct MsgType(Option<String>);
+
+impl KV for MsgType {
+    fn serialize(&self, rinfo: &Record, serializer: &mut dyn slog::Serializer) -> slog::Result {
+        let tag = rinfo.tag();
+        if !tag.is_empty() {
+            serializer.emit_str("Type", tag)
+        } else if let Some(ref msg_type) = self.0 {
+            serializer.emit_str("Type", msg_type)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// A record's `Fields` map, for encodings nesting it directly rather than
+/// splicing it in
+struct Fields<'a, 'b: 'a> {
+    rinfo: &'a Record<'b>,
+    logger_values: &'a OwnedKVList,
+    rules: &'a FieldRules,
+    extras: &'a FieldExtras,
+}
+
+impl<'a, 'b> serde::Serialize for Fields<'a, 'b> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> result::Result<S::Ok, S::Error> {
+        let error = |_| serde::ser::Error::custom("serde serialization error");
+        let mut serializer = SerdeSerializer::start(serializer, None).map_err(error)?;
+        serialize_fields(
+            &mut serializer,
+            self.rinfo,
+            self.logger_values,
+            self.rules,
+            self.extras,
+        )
+            .map_err(error)?;
+        serializer.end()
+    }
+}
+
+/// Encode a record the drain generated as JSON, e.g. a heartbeat, as
+/// `encoding`
+pub(crate) fn encode_value(
+    encoding: Encoding,
+    record: &Value,
+    out: &mut Vec<u8>,
+) -> io::Result<()> {
+    match encoding {
+        Encoding::Json => serde_json::to_writer(out, record).map_err(io::Error::from),
+        #[cfg(feature = "cbor")]
+        Encoding::Cbor => {
+            ciborium::ser::into_writer(record, out).map_err(|e| io::Error::other(e.to_string()))
+        }
+        Encoding::LogEntry => {
+            if let Value::Object(ref record) = *record {
+                log_entry::encode(record, out);
+            }
+            Ok(())
+        }
+        #[cfg(feature = "msgpack")]
+        Encoding::MessagePack => {
+            serde::Serialize::serialize(record, &mut rmp_serde::Serializer::new(out))
+                .map_err(|e| io::Error::other(e.to_string()))
+        }
+    }
+}
+
+/// Write an encoded record with the framing `encoding` needs, returning
+/// the number of bytes written
+pub(crate) fn write_framed(
+    encoding: Encoding,
+    newlines: bool,
+    single_write: bool,
+    io: &mut dyn io::Write,
+    payload: &mut Vec<u8>,
+) -> io::Result<usize> {
+    let mut prefix = vec![];
+    match encoding {
+        Encoding::Json => {
+            if !newlines {
+                io.write_all(payload)?;
+                return Ok(payload.len());
+            } else if single_write {
+                payload.push(b'\n');
+                io.write_all(payload)?;
+                return Ok(payload.len());
+            } else {
+                write_all_vectored(io, payload, b"\n")?;
+                return Ok(payload.len() + 1);
+            }
+        }
+        #[cfg(feature = "cbor")]
+        Encoding::Cbor => {
+            io.write_all(payload)?;
+            return Ok(payload.len());
+        }
+        Encoding::LogEntry => log_entry::write_varint(&mut prefix, payload.len() as u64),
+        #[cfg(feature = "msgpack")]
+        Encoding::MessagePack => prefix.extend_from_slice(&(payload.len() as u32).to_be_bytes()),
+    }
+    if single_write {
+        payload.splice(0..0, prefix.iter().cloned());
+        io.write_all(payload)?;
+        Ok(payload.len())
+    } else {
+        write_all_vectored(io, &prefix, payload)?;
+        Ok(prefix.len() + payload.len())
+    }
+}
 // }}}
 
 // {{{ MozLogJson
+/// Encoding of records written by `MozLogJson`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    /// JSON, newline delimited unless disabled with `set_newlines`
+    Json,
+    /// CBOR with the same layout as the JSON, as a CBOR sequence (RFC
+    /// 8742) needing no further framing
+    #[cfg(feature = "cbor")]
+    Cbor,
+    /// `google.logging.v2.LogEntry` protobufs, each prefixed by its length
+    /// as a varint: the record as `json_payload`, with its `Timestamp` and
+    /// `Severity` as the entry's own
+    LogEntry,
+    /// MessagePack with the same layout as the JSON, each record framed by
+    /// its length as a 4 byte big-endian prefix
+    #[cfg(feature = "msgpack")]
+    MessagePack,
+}
+
 /// Json `Drain`
 ///
 /// Each record will be printed as a Json map
 /// to a given `io`
+///
+/// Records are serialized without holding any lock; only the final write
+/// to `io` is serialized by a `Mutex`, so the drain is `Send + Sync` for
+/// any `Send` writer and needs no external synchronization.
 pub struct MozLogJson<W: io::Write> {
     newlines: bool,
+    single_write: bool,
     values: Vec<OwnedKVList>,
-    io: RefCell<W>,
+    /// Number of leading `values` added with `add_key_value`
+    custom_values: usize,
+    /// Builder constants, serialized once: `"Logger":..,` and so on
+    statics: Vec<u8>,
+    /// `"Pid":..,`, serialized once, or nothing if omitted
+    pid: Vec<u8>,
+    /// `statics` for heartbeat records, with `"Type":"heartbeat",`
+    heartbeat_statics: Vec<u8>,
+    /// `Type` of records without a tag
+    msg_type: Option<String>,
+    /// How to name the `Logger` of records, if not static
+    logger_fallback: Option<LoggerFallback>,
+    clock: Arc<dyn Clock>,
+    /// When the drain was built, if adding `Uptime`
+    started: Option<Instant>,
+    /// Next `Seq`, if numbering records
+    seq: Option<Arc<AtomicU64>>,
+    writers: Arc<Writers<W>>,
+    counters: Arc<Counters>,
+    level: LevelHandle,
+    predicate: Option<Predicate>,
+    /// Shared with `ReloadHandle`s, as is `field_rules`
+    sampler: Arc<RwLock<Option<Sampler>>>,
+    repeats: Option<RepeatSuppressor>,
+    rate_limiter: Option<RateLimiter>,
+    field_rules: Arc<RwLock<FieldRules>>,
+    extras: FieldExtras,
+    route_key: String,
+    echo: Option<EchoBuffer>,
+    adaptive: Option<AdaptiveSampler>,
+    #[cfg(feature = "audit")]
+    chain: Option<HashChain>,
+    /// Asserted unwind safe, so the drain can still go in a `Logger`: a
+    /// panicking send leaves nothing half updated
+    #[cfg(feature = "statsd")]
+    statsd: Option<AssertUnwindSafe<StatsdClient>>,
     pretty: bool,
+    encoding: Encoding,
+}
+
+/// A drain's writers, shared with its `FlushHandle`s
+struct Writers<W: io::Write> {
+    io: Mutex<W>,
+    split: Option<(Level, Mutex<Box<dyn io::Write + Send>>)>,
+    /// Writers for records whose route key has a given value
+    routes: Vec<(String, Mutex<Box<dyn io::Write + Send>>)>,
+}
+
+impl<W: io::Write> Writers<W> {
+    // A panic mid-write leaves at worst a partial record, so carry on past
+    // a poisoned lock
+    fn flush(&self) -> io::Result<()> {
+        for (_, io) in &self.routes {
+            io.lock().unwrap_or_else(PoisonError::into_inner).flush()?;
+        }
+        if let Some((_, ref io)) = self.split {
+            io.lock().unwrap_or_else(PoisonError::into_inner).flush()?;
+        }
+        self.io.lock().unwrap_or_else(PoisonError::into_inner).flush()
+    }
+}
+
+impl<W: io::Write + Send> Flush for Writers<W> {
+    fn flush(&self) -> io::Result<()> {
+        Writers::flush(self)
+    }
 }
 
 impl<W> MozLogJson<W>
@@ -175,9 +517,487 @@ where
         MozLogJsonBuilder::new(io)
     }
 
+    /// Flush the underlying writers
+    pub fn flush(&self) -> io::Result<()> {
+        self.writers.flush()
+    }
+
+    /// Handle for changing this drain's minimum level and module
+    /// directives once it's been moved into a `Logger`
+    pub fn level_handle(&self) -> LevelHandle {
+        self.level.clone()
+    }
+
+    /// Current counters
+    pub fn stats(&self) -> DrainStats {
+        self.counters.snapshot()
+    }
+
+    /// Handle for reading this drain's counters once it's been moved into
+    /// a `Logger`
+    pub fn stats_handle(&self) -> StatsHandle {
+        StatsHandle::new(self.counters.clone())
+    }
+
+    /// Handle for applying changed settings to this drain once it's been
+    /// moved into a `Logger`
+    #[cfg(feature = "config")]
+    pub fn reload_handle(&self) -> ReloadHandle {
+        ReloadHandle::new(
+            self.level.clone(),
+            self.sampler.clone(),
+            self.field_rules.clone(),
+        )
+    }
+
+    /// Prometheus collector exporting this drain's counters, for
+    /// registering alongside the app's metrics
+    #[cfg(feature = "prometheus")]
+    pub fn collector(&self) -> MozLogCollector {
+        MozLogCollector::new(self.stats_handle())
+    }
+
+    /// Counters, for wrapping drains to count their own losses
+    pub(crate) fn counters(&self) -> &Counters {
+        &self.counters
+    }
+
+    /// Write a record with its framing, returning the bytes written
+    fn write_payload(&self, io: &mut dyn io::Write, payload: &mut Vec<u8>) -> io::Result<usize> {
+        write_framed(self.encoding, self.newlines, self.single_write, io, payload)
+    }
+
+    /// Serialize a record, passing it (without a newline) to `f`
+    pub(crate) fn with_record<F>(
+        &self,
+        rinfo: &Record,
+        logger_values: &OwnedKVList,
+        f: F,
+    ) -> io::Result<()>
+    where
+        F: FnOnce(&mut Vec<u8>) -> io::Result<()>,
+    {
+        if !self.passes(rinfo, logger_values) {
+            self.counters.dropped();
+            return Ok(());
+        }
+        let adaptive = match self.adaptive {
+            Some(ref adaptive) => adaptive,
+            None => {
+                self.echo(rinfo.level());
+                return self.assembled(rinfo, logger_values, f);
+            }
+        };
+        let rate = match adaptive.sample() {
+            Some(rate) => rate,
+            None => {
+                self.counters.dropped();
+                return Ok(());
+            }
+        };
+        self.echo(rinfo.level());
+        // Record the effective rate so downstream can re-weight counts
+        let rstatic = slog::RecordStatic {
+            location: rinfo.location(),
+            tag: rinfo.tag(),
+            level: rinfo.level(),
+        };
+        let marked = (rinfo.kv(), kv!("sample_rate" => rate));
+        self.assembled(
+            &Record::new(&rstatic, rinfo.msg(), slog::BorrowedKV(&marked)),
+            logger_values,
+            |payload| {
+                adaptive.wrote(payload.len());
+                f(payload)
+            },
+        )
+    }
+
+    /// Whether a record passes the level and predicate filters, sampling,
+    /// repeat suppression and rate limiting
+    fn passes(&self, rinfo: &Record, logger_values: &OwnedKVList) -> bool {
+        if !self.level.enabled(rinfo) {
+            if let Some(ref echo) = self.echo {
+                self.keep_for_echo(echo, rinfo, logger_values);
+            }
+            return false;
+        }
+        if let Some(ref predicate) = self.predicate {
+            if !predicate(&RecordFields::new(rinfo, logger_values)) {
+                return false;
+            }
+        }
+        if let Some(ref sampler) = *self.sampler.read().unwrap_or_else(PoisonError::into_inner) {
+            if !sampler.keep(rinfo, logger_values) {
+                return false;
+            }
+        }
+        if let Some(ref repeats) = self.repeats {
+            let (suppressed, repeated) = repeats.check(rinfo, logger_values);
+            if let Some(repeated) = repeated {
+                self.report_repeated(repeated);
+            }
+            if suppressed {
+                return false;
+            }
+        }
+        if let Some(ref rate_limiter) = self.rate_limiter {
+            let (admitted, report) = rate_limiter.admit(rinfo.level());
+            if let Some((dropped, window)) = report {
+                self.write_report(
+                    Level::Warning,
+                    format_args!(
+                        "dropped {} records in the last {}s due to rate limiting",
+                        dropped,
+                        window.as_secs()
+                    ),
+                    b!("dropped" => dropped),
+                );
+            }
+            return admitted;
+        }
+        true
+    }
+
+    /// Keep a record below the minimum level, marked, to be echoed with the
+    /// next error
+    fn keep_for_echo(&self, echo: &EchoBuffer, rinfo: &Record, logger_values: &OwnedKVList) {
+        let rstatic = slog::RecordStatic {
+            location: rinfo.location(),
+            tag: rinfo.tag(),
+            level: rinfo.level(),
+        };
+        let marked = (rinfo.kv(), kv!("echo_on_error" => true));
+        let _ = self.assembled(
+            &Record::new(&rstatic, rinfo.msg(), slog::BorrowedKV(&marked)),
+            logger_values,
+            |payload| {
+                let route = self.route(rinfo, logger_values);
+                echo.push((rinfo.level(), route, payload.clone()));
+                Ok(())
+            },
+        );
+    }
+
+    /// Write the records kept for echoing ahead of an error
+    fn echo(&self, level: Level) {
+        if let Some(ref echo) = self.echo {
+            if level.is_at_least(Level::Error) {
+                for (level, route, mut payload) in echo.take() {
+                    let _ = self.write_record(level, route, &mut payload);
+                }
+            }
+        }
+    }
+
+    /// Write a record generated by the drain itself, e.g. a summary of
+    /// dropped records
+    fn write_report(&self, level: Level, msg: fmt::Arguments, kv: slog::BorrowedKV) {
+        let rstatic = record_static!(level, "");
+        let logger_values: OwnedKVList = o!().into();
+        let _ = self.assembled(
+            &Record::new(&rstatic, &msg, kv),
+            &logger_values,
+            |payload| self.write_record(level, None, payload),
+        );
+    }
+
+    fn report_repeated(&self, repeated: Repeated) {
+        self.write_report(
+            repeated.level,
+            format_args!(
+                "last message repeated {} times: {}",
+                repeated.count, repeated.msg
+            ),
+            b!("repeated" => repeated.count),
+        );
+    }
+
+    /// Serialize a record regardless of filtering, passing it to `f`
+    fn assembled<F>(&self, rinfo: &Record, logger_values: &OwnedKVList, f: F) -> io::Result<()>
+    where
+        F: FnOnce(&mut Vec<u8>) -> io::Result<()>,
+    {
+        TL_RECORD.with(|bufs| match bufs.try_borrow_mut() {
+            Ok(mut bufs) => {
+                let res = match self.assemble(&mut bufs, rinfo, logger_values) {
+                    Ok(_) => f(&mut bufs.out),
+                    Err(e) => {
+                        self.counters.write_error();
+                        Err(e)
+                    }
+                };
+                bufs.clear();
+                res
+            }
+            // Logging from within a value's serialization
+            Err(_) => {
+                let mut bufs = RecordBufs::default();
+                if let Err(e) = self.assemble(&mut bufs, rinfo, logger_values) {
+                    self.counters.write_error();
+                    return Err(e);
+                }
+                f(&mut bufs.out)
+            }
+        })
+    }
+
+    /// The route of a record: the index of the writer its route key's
+    /// value maps to, if any
+    pub(crate) fn route(&self, rinfo: &Record, logger_values: &OwnedKVList) -> Option<usize> {
+        if self.writers.routes.is_empty() {
+            return None;
+        }
+        let value = find_key(&self.route_key, rinfo, logger_values)?;
+        self.writers
+            .routes
+            .iter()
+            .position(|(route, _)| *route == value)
+    }
+
+    /// Write a serialized record at `level` on `route` to the appropriate
+    /// writer
+    pub(crate) fn write_record(
+        &self,
+        level: Level,
+        route: Option<usize>,
+        payload: &mut Vec<u8>,
+    ) -> io::Result<()> {
+        match context::with_record_level(level, || self.write_level(level, route, payload)) {
+            Ok(bytes) => {
+                self.counters.written(level, bytes);
+                #[cfg(feature = "statsd")]
+                {
+                    if let Some(ref statsd) = self.statsd {
+                        let _ = statsd.incr(STATSD_KEYS[level.as_usize() - 1]);
+                    }
+                }
+                Ok(())
+            }
+            Err(e) => {
+                self.counters.write_error();
+                Err(e)
+            }
+        }
+    }
+
+    fn write_level(
+        &self,
+        level: Level,
+        route: Option<usize>,
+        payload: &mut Vec<u8>,
+    ) -> io::Result<usize> {
+        // A panic mid-write leaves at worst a partial record, so carry on
+        // past a poisoned lock
+        if let Some(route) = route {
+            let mut io = self.writers.routes[route]
+                .1
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            self.chain(route, payload);
+            return self.write_payload(&mut **io, payload);
+        }
+        let routes = self.writers.routes.len();
+        if let Some((split_level, ref io)) = self.writers.split {
+            if level.is_at_least(split_level) {
+                let mut io = io.lock().unwrap_or_else(PoisonError::into_inner);
+                self.chain(routes, payload);
+                return self.write_payload(&mut **io, payload);
+            }
+        }
+        let mut io = self.writers.io.lock().unwrap_or_else(PoisonError::into_inner);
+        self.chain(routes + 1, payload);
+        self.write_payload(&mut *io, payload)
+    }
+
+    /// Link a record into its output stream's hash chain, if enabled
+    #[cfg(feature = "audit")]
+    fn chain(&self, stream: usize, payload: &mut Vec<u8>) {
+        if let Some(ref chain) = self.chain {
+            chain.append(stream, payload);
+        }
+    }
+
+    #[cfg(not(feature = "audit"))]
+    fn chain(&self, _stream: usize, _payload: &mut Vec<u8>) {}
+
+    /// Serialize a record into `bufs.out`
+    fn assemble(
+        &self,
+        bufs: &mut RecordBufs,
+        rinfo: &Record,
+        logger_values: &OwnedKVList,
+    ) -> io::Result<()> {
+        match self.encoding {
+            Encoding::Json => (),
+            #[cfg(feature = "cbor")]
+            Encoding::Cbor => {
+                // ciborium only serializes through `into_writer`
+                let record = self.serialize_nested(
+                    serde_json::value::Serializer,
+                    rinfo,
+                    logger_values,
+                )?;
+                return ciborium::ser::into_writer(&record, &mut bufs.out)
+                    .map_err(|e| io::Error::other(e.to_string()));
+            }
+            Encoding::LogEntry => {
+                let record = self.serialize_nested(
+                    serde_json::value::Serializer,
+                    rinfo,
+                    logger_values,
+                )?;
+                if let Value::Object(ref record) = record {
+                    log_entry::encode(record, &mut bufs.out);
+                }
+                return Ok(());
+            }
+            #[cfg(feature = "msgpack")]
+            Encoding::MessagePack => {
+                let mut serializer = rmp_serde::Serializer::new(&mut bufs.out);
+                return self.serialize_nested(&mut serializer, rinfo, logger_values);
+            }
+        }
+        if !self.pretty {
+            return self.assemble_compact(bufs, rinfo, logger_values);
+        }
+
+        // XXX: UGLY HACK HERE
+        // First write out the structure without the Fields nested
+        {
+            let mut serializer = serde_json::Serializer::pretty(&mut bufs.body);
+            self.log_placeholder_impl(&mut serializer, &rinfo)?;
+        }
+
+        // XXX: UGLY HACK PART 2: Now write out just the Fields entry we replace with
+        {
+            let mut serializer = serde_json::Serializer::pretty(&mut bufs.fields);
+            self.log_fields_impl(&mut serializer, &rinfo, &logger_values)?;
+        }
+
+        // And now we replace the placeholder with the contents
+        let placeholder = b"\"00PLACEHOLDER00\"";
+        let mut rest = &bufs.body[..];
+        while let Some(pos) = rest.windows(placeholder.len()).position(|w| w == placeholder) {
+            bufs.out.extend_from_slice(&rest[..pos]);
+            bufs.out.extend_from_slice(&bufs.fields);
+            rest = &rest[pos + placeholder.len()..];
+        }
+        bufs.out.extend_from_slice(rest);
+        // The Fields map is never ended, so close it here
+        bufs.out.push(b'}');
+        Ok(())
+    }
+
+    /// Serialize a compact record into `bufs.out`, splicing in the
+    /// pre-serialized builder constants
+    fn assemble_compact(
+        &self,
+        bufs: &mut RecordBufs,
+        rinfo: &Record,
+        logger_values: &OwnedKVList,
+    ) -> io::Result<()> {
+        bufs.out.push(b'{');
+        if self.custom_values > 0 {
+            {
+                let mut serializer = compact_serializer(&mut bufs.body);
+                let mut serializer = SerdeSerializer::start(&mut serializer, None)?;
+                for kv in &self.values[..self.custom_values] {
+                    kv.serialize(rinfo, &mut serializer)?;
+                }
+                serializer
+                    .end()
+                    .map_err(io::Error::other)?;
+            }
+            // Splice in the entries without the enclosing braces
+            if bufs.body.len() > 2 {
+                bufs.out.extend_from_slice(&bufs.body[1..bufs.body.len() - 1]);
+                bufs.out.push(b',');
+            }
+        }
+        bufs.out.extend_from_slice(&self.statics);
+        let tag = rinfo.tag();
+        let msg_type = if tag.is_empty() {
+            self.msg_type.as_deref()
+        } else {
+            Some(tag)
+        };
+        if let Some(msg_type) = msg_type {
+            bufs.out.extend_from_slice(b"\"Type\":");
+            serde_json::to_writer(&mut bufs.out, msg_type)?;
+            bufs.out.push(b',');
+        }
+        if let Some(fallback) = self.logger_fallback {
+            bufs.out.extend_from_slice(b"\"Logger\":");
+            serde_json::to_writer(&mut bufs.out, fallback.logger_name(rinfo))?;
+            bufs.out.push(b',');
+        }
+        let nsec = self.clock.now_nanos();
+        io::Write::write_fmt(
+            &mut bufs.out,
+            format_args!(
+                "\"Timestamp\":{},\"Severity\":{},",
+                nsec,
+                level_to_severity(rinfo.level())
+            ),
+        )?;
+        bufs.out.extend_from_slice(&self.pid);
+        if let Some(ref seq) = self.seq {
+            io::Write::write_fmt(
+                &mut bufs.out,
+                format_args!("\"Seq\":{},", seq.fetch_add(1, Ordering::Relaxed)),
+            )?;
+        }
+        if let Some(started) = self.started {
+            io::Write::write_fmt(
+                &mut bufs.out,
+                format_args!("\"Uptime\":{:.3},", secs(started.elapsed())),
+            )?;
+        }
+        bufs.out.extend_from_slice(b"\"Fields\":");
+        {
+            let mut serializer = compact_serializer(&mut bufs.out);
+            self.log_fields_impl(&mut serializer, rinfo, logger_values)?;
+        }
+        // The Fields map is never ended, so close it and the record here
+        bufs.out.extend_from_slice(b"}}");
+        Ok(())
+    }
+
+    /// Serialize a record with `Fields` nested directly, for binary
+    /// encodings
+    fn serialize_nested<S: serde::Serializer>(
+        &self,
+        serializer: S,
+        rinfo: &Record,
+        logger_values: &OwnedKVList,
+    ) -> io::Result<S::Ok>
+    where
+        S::Error: Send + Sync + 'static,
+    {
+        let mut serializer = SerdeSerializer::start(serializer, None)?;
+        for kv in &self.values {
+            kv.serialize(rinfo, &mut serializer)?;
+        }
+        let rules = self.field_rules.read().unwrap_or_else(PoisonError::into_inner);
+        let fields = Fields {
+            rinfo,
+            logger_values,
+            rules: &rules,
+            extras: &self.extras,
+        };
+        serializer
+            .ser_map
+            .serialize_entry("Fields", &fields)
+            .map_err(io::Error::other)?;
+        serializer
+            .end()
+            .map_err(io::Error::other)
+    }
+
     fn log_placeholder_impl<F>(
         &self,
-        serializer: &mut serde_json::ser::Serializer<&mut io::Cursor<Vec<u8>>, F>,
+        serializer: &mut serde_json::ser::Serializer<&mut Vec<u8>, F>,
         rinfo: &Record,
     ) -> io::Result<()>
     where
@@ -199,27 +1019,125 @@ where
         Ok(())
     }
 
-    fn log_fields_impl<F>(
+    fn log_fields_impl<S>(
         &self,
-        serializer: &mut serde_json::ser::Serializer<&mut io::Cursor<Vec<u8>>, F>,
+        serializer: S,
         rinfo: &Record,
         logger_values: &OwnedKVList,
     ) -> io::Result<()>
     where
-        F: serde_json::ser::Formatter,
+        S: serde::Serializer,
     {
-        let mut serializer = SerdeSerializer::start(&mut *serializer, None)?;
+        let mut serializer = SerdeSerializer::start(serializer, None)?;
+        serialize_fields(
+            &mut serializer,
+            rinfo,
+            logger_values,
+            &self.field_rules.read().unwrap_or_else(PoisonError::into_inner),
+            &self.extras,
+        )?;
+        Ok(())
+    }
+}
 
-        let msg = kv!("msg" => format!("{}", rinfo.msg()));
-        msg.serialize(rinfo, &mut serializer)?;
+impl MozLogJson<io::Stdout> {
+    /// Build custom `Json` `Drain` writing Warning and more severe records
+    /// to stderr, and everything else to stdout
+    pub fn split_stdout_stderr() -> MozLogJsonBuilder<io::Stdout> {
+        MozLogJsonBuilder::new(io::stdout()).set_split(Level::Warning, io::stderr())
+    }
+}
 
-        logger_values.serialize(rinfo, &mut serializer)?;
-        rinfo.kv().serialize(rinfo, &mut serializer)?;
+impl<W> MozLogJson<W>
+where
+    W: io::Write + Send + 'static,
+{
+    /// Handle for flushing this drain once it's been moved into a
+    /// `Logger`, also registering it with `flush_all`
+    pub fn flush_handle(&self) -> FlushHandle {
+        FlushHandle::new(self.writers.clone())
+    }
 
+    /// Start a background thread writing a `Type` "heartbeat" record with
+    /// this drain's counters (`records`, `bytes`, `dropped`,
+    /// `write_errors`) every `interval`, so a silently failing pipeline
+    /// shows up as missing heartbeats. Stops once the drain is dropped.
+    ///
+    /// Heartbeats go to the main writer, bypassing filters, in the drain's
+    /// encoding (compact, for JSON).
+    pub fn start_heartbeat(&self, interval: Duration) -> io::Result<()> {
+        let writers = Arc::downgrade(&self.writers);
+        let counters = self.counters.clone();
+        let mut header = b"{".to_vec();
+        header.extend_from_slice(&self.heartbeat_statics);
+        let pid = self.pid.clone();
+        let clock = self.clock.clone();
+        let encoding = self.encoding;
+        let newlines = self.newlines;
+        let single_write = self.single_write;
+        thread::Builder::new()
+            .name("mozlog-heartbeat".to_owned())
+            .spawn(move || loop {
+                thread::sleep(interval);
+                let writers = match writers.upgrade() {
+                    Some(writers) => writers,
+                    None => return,
+                };
+                let stats = counters.snapshot();
+                let mut record = header.clone();
+                let _ = io::Write::write_fmt(
+                    &mut record,
+                    format_args!(
+                        "\"Timestamp\":{},\"Severity\":{},",
+                        clock.now_nanos(),
+                        level_to_severity(Level::Info)
+                    ),
+                );
+                record.extend_from_slice(&pid);
+                let _ = io::Write::write_fmt(
+                    &mut record,
+                    format_args!(
+                        "\"Fields\":{{\"msg\":\"heartbeat\",\"records\":{},\"bytes\":{},\
+                         \"dropped\":{},\"write_errors\":{}}}}}",
+                        stats.records, stats.bytes, stats.dropped, stats.write_errors
+                    ),
+                );
+                if encoding != Encoding::Json {
+                    let mut encoded = vec![];
+                    let encoded = serde_json::from_slice::<Value>(&record)
+                        .map_err(io::Error::from)
+                        .and_then(|record| encode_value(encoding, &record, &mut encoded))
+                        .map(|_| encoded);
+                    record = match encoded {
+                        Ok(encoded) => encoded,
+                        Err(_) => {
+                            counters.write_error();
+                            continue;
+                        }
+                    };
+                }
+                let mut io = writers.io.lock().unwrap_or_else(PoisonError::into_inner);
+                match write_framed(encoding, newlines, single_write, &mut *io, &mut record) {
+                    Ok(bytes) => counters.written(Level::Info, bytes),
+                    Err(_) => counters.write_error(),
+                }
+            })?;
         Ok(())
     }
 }
 
+impl<W> Drop for MozLogJson<W>
+where
+    W: io::Write,
+{
+    fn drop(&mut self) {
+        if let Some(repeated) = self.repeats.as_ref().and_then(RepeatSuppressor::pending) {
+            self.report_repeated(repeated);
+        }
+        let _ = self.writers.flush();
+    }
+}
+
 impl<W> slog::Drain for MozLogJson<W>
 where
     W: io::Write,
@@ -227,40 +1145,15 @@ where
     type Ok = ();
     type Err = io::Error;
     fn log(&self, rinfo: &Record, logger_values: &OwnedKVList) -> io::Result<()> {
-        // XXX: UGLY HACK HERE
-        // First write out the structure without the Fields nested
-        let mut buf = io::Cursor::new(Vec::new());
-        if self.pretty {
-            let mut serializer = serde_json::Serializer::pretty(&mut buf);
-            self.log_placeholder_impl(&mut serializer, &rinfo)?;
-        } else {
-            let mut serializer = serde_json::Serializer::new(&mut buf);
-            self.log_placeholder_impl(&mut serializer, &rinfo)?;
-        };
-        let payload = String::from_utf8(buf.into_inner()).unwrap();
-
-        // XXX: UGLY HACK PART 2: Now write out just the Fields entry we replace with
-        let mut buf = io::Cursor::new(Vec::new());
-        if self.pretty {
-            let mut serializer = serde_json::Serializer::pretty(&mut buf);
-            self.log_fields_impl(&mut serializer, &rinfo, &logger_values)?;
-        } else {
-            let mut serializer = serde_json::Serializer::new(&mut buf);
-            self.log_fields_impl(&mut serializer, &rinfo, &logger_values)?;
-        };
-        let fields = String::from_utf8(buf.into_inner()).unwrap();
-
-        // And now we replace the placeholder with the contents
-        let mut payload = payload.replace("\"00PLACEHOLDER00\"", fields.as_str());
-        // For some reason the replace loses an end }
-        payload.push_str("}");
+        self.with_record(rinfo, logger_values, |payload| {
+            let route = self.route(rinfo, logger_values);
+            self.write_record(rinfo.level(), route, payload)
+        })
+    }
 
-        let mut io = self.io.borrow_mut();
-        io.write_all(payload.as_bytes())?;
-        if self.newlines {
-            io.write_all(b"\n")?;
-        }
-        Ok(())
+    fn is_enabled(&self, level: Level) -> bool {
+        // Records below the minimum level may still be kept for echoing
+        self.echo.is_some() || self.level.level_enabled(level)
     }
 }
 
@@ -270,29 +1163,115 @@ where
 /// Json `Drain` builder
 ///
 /// Create with `Json::new`.
+///
+/// The minimum level and module directives default to the `MOZLOG_LEVEL`
+/// environment variable, e.g. `info,hyper=warn`, if set, and pretty
+/// printing to `MOZLOG_PRETTY` (`true`/`false`, `1`/`0` or `yes`/`no`).
+/// Fields of a `MOZLOG_FIELDS` JSON object, e.g.
+/// `{"env":"stage","region":"us-west-2"}`, are added as static fields.
 pub struct MozLogJsonBuilder<W: io::Write> {
     newlines: bool,
+    single_write: bool,
     values: Vec<OwnedKVList>,
     io: W,
+    split: Option<(Level, Box<dyn io::Write + Send>)>,
+    route_key: String,
+    routes: Vec<(String, Box<dyn io::Write + Send>)>,
     pretty: bool,
     logger_name: Option<String>,
     msg_type: Option<String>,
+    logger_fallback: Option<LoggerFallback>,
     hostname: Option<String>,
+    pid: Option<u32>,
+    env_version: Option<String>,
+    coarse_clock: bool,
+    clock: Option<Arc<dyn Clock>>,
+    uptime: bool,
+    start_time: bool,
+    seq: bool,
+    #[cfg(feature = "session-id")]
+    session_id: bool,
+    encoding: Encoding,
+    level: Level,
+    directives: Vec<Directive>,
+    predicate: Option<Predicate>,
+    sampler: Option<Sampler>,
+    repeats: Option<RepeatSuppressor>,
+    rate_limiter: Option<RateLimiter>,
+    field_rules: FieldRules,
+    extras: FieldExtras,
+    echo: Option<EchoBuffer>,
+    adaptive: Option<AdaptiveSampler>,
+    #[cfg(feature = "audit")]
+    chain_key: Option<Vec<u8>>,
+    #[cfg(feature = "statsd")]
+    statsd: Option<StatsdClient>,
 }
 
 impl<W> MozLogJsonBuilder<W>
 where
     W: io::Write,
 {
-    fn new(io: W) -> Self {
+    pub(crate) fn new(io: W) -> Self {
+        let mut builder = MozLogJsonBuilder::new_without_env(io);
+        // Let ops tune verbosity without code changes; a malformed value is
+        // ignored rather than failing logger setup
+        if let Ok(spec) = env::var("MOZLOG_LEVEL") {
+            if let Ok((level, directives)) = filter::parse_directives(&spec) {
+                builder.level = level.unwrap_or(builder.level);
+                builder.directives = directives;
+            }
+        }
+        if let Ok(Some(pretty)) = flag("MOZLOG_PRETTY") {
+            builder.pretty = pretty;
+        }
+        if let Ok(Some(fields)) = fields() {
+            builder.values.push(slog::OwnedKV(fields).into());
+        }
+        builder
+    }
+
+    /// Builder ignoring `MOZLOG_LEVEL`, `MOZLOG_PRETTY` and `MOZLOG_FIELDS`
+    ///
+    /// Keeps tests independent of the environment they run in.
+    pub(crate) fn new_without_env(io: W) -> Self {
         MozLogJsonBuilder {
             newlines: true,
+            single_write: false,
             values: vec![],
             io: io,
+            split: None,
+            route_key: "Type".to_owned(),
+            routes: vec![],
             pretty: false,
             logger_name: None,
             msg_type: None,
+            logger_fallback: None,
             hostname: None,
+            pid: process_id(),
+            env_version: None,
+            coarse_clock: false,
+            clock: None,
+            uptime: false,
+            start_time: false,
+            seq: false,
+            #[cfg(feature = "session-id")]
+            session_id: false,
+            encoding: Encoding::Json,
+            level: Level::Trace,
+            directives: vec![],
+            predicate: None,
+            sampler: None,
+            repeats: None,
+            rate_limiter: None,
+            field_rules: FieldRules::default(),
+            extras: FieldExtras::default(),
+            echo: None,
+            adaptive: None,
+            #[cfg(feature = "audit")]
+            chain_key: None,
+            #[cfg(feature = "statsd")]
+            statsd: None,
         }
     }
 
@@ -300,36 +1279,134 @@ where
     ///
     /// This consumes the builder.
     pub fn build(mut self) -> MozLogJson<W> {
+        // Chained records are verified line by line
+        #[cfg(feature = "audit")]
+        let chain = match self.chain_key {
+            Some(key) if self.encoding == Encoding::Json && !self.pretty => {
+                // A stream per route, then the split and main writers
+                Some(HashChain::new(key, self.routes.len() + 2))
+            }
+            _ => None,
+        };
+        let custom_values = self.values.len();
+        let mut statics = vec![];
         let mut values: Vec<OwnedKVList> = vec![];
-        if let Some(ref logger_name) = self.logger_name {
-            values.push(o!("Logger" => logger_name.to_owned()).into());
+        for &(key, value) in &[
+            ("Logger", &self.logger_name),
+            ("Hostname", &self.hostname),
+            ("EnvVersion", &self.env_version),
+        ] {
+            if let Some(ref value) = *value {
+                // Strings always serialize
+                serde_json::to_writer(&mut statics, key).unwrap();
+                statics.push(b':');
+                serde_json::to_writer(&mut statics, value).unwrap();
+                statics.push(b',');
+                values.push(o!(key => value.to_owned()).into());
+            }
         }
-        if let Some(ref msg_type) = self.msg_type {
-            values.push(o!("Type" => msg_type.to_owned()).into());
+        // Type may come from the record's tag, so isn't static
+        values.push(slog::OwnedKV(MsgType(self.msg_type.clone())).into());
+        let logger_fallback = match self.logger_name {
+            Some(_) => None,
+            None => self.logger_fallback,
+        };
+        if let Some(fallback) = logger_fallback {
+            values.push(slog::OwnedKV(fallback).into());
+        }
+        #[cfg(feature = "session-id")]
+        {
+            if self.session_id {
+                let session_id = Uuid::new_v4().to_string();
+                statics.extend_from_slice(format!("\"SessionId\":\"{}\",", session_id).as_bytes());
+                values.push(o!("SessionId" => session_id).into());
+            }
         }
-        if let Some(ref hostname) = self.hostname {
-            values.push(o!("Hostname" => hostname.to_owned()).into());
+        let clock: Arc<dyn Clock> = match self.clock {
+            Some(clock) => clock,
+            None if self.coarse_clock => Arc::new(CoarseClock),
+            None => Arc::new(SystemClock),
+        };
+        if self.start_time {
+            let start = clock.now_nanos();
+            statics.extend_from_slice(format!("\"StartTime\":{},", start).as_bytes());
+            values.push(o!("StartTime" => start).into());
         }
+        let mut heartbeat_statics = statics.clone();
+        heartbeat_statics.extend_from_slice(b"\"Type\":\"heartbeat\",");
+        let timestamps = clock.clone();
         values.push(
             o!(
-            "Timestamp" => FnValue(|_ : &Record| {
-                let now = chrono::Utc::now();
-                let nsec: i64 = (now.timestamp() as i64) * 1_000_000_000;
-                nsec + (now.timestamp_subsec_nanos() as i64)
-            }),
+            "Timestamp" => FnValue(move |_ : &Record| timestamps.now_nanos()),
             "Severity" => FnValue(|record : &Record| {
                 level_to_severity(record.level())
             }),
-            "Pid" => process::id(),
             ).into(),
         );
+        let pid = match self.pid {
+            Some(pid) => {
+                values.push(o!("Pid" => pid).into());
+                format!("\"Pid\":{},", pid).into_bytes()
+            }
+            None => vec![],
+        };
+        let seq = if self.seq {
+            let seq = Arc::new(AtomicU64::new(0));
+            let next = seq.clone();
+            values.push(o!("Seq" => FnValue(move |_: &Record| {
+                next.fetch_add(1, Ordering::Relaxed)
+            })).into());
+            Some(seq)
+        } else {
+            None
+        };
+        let started = if self.uptime {
+            let started = Instant::now();
+            values.push(o!("Uptime" => FnValue(move |_: &Record| secs(started.elapsed()))).into());
+            Some(started)
+        } else {
+            None
+        };
         self.values.extend(values);
 
         MozLogJson {
             values: self.values,
+            custom_values,
+            statics,
+            pid,
+            heartbeat_statics,
+            msg_type: self.msg_type,
+            logger_fallback,
+            clock,
+            started,
+            seq,
             newlines: self.newlines,
-            io: RefCell::new(self.io),
+            single_write: self.single_write,
+            writers: Arc::new(Writers {
+                io: Mutex::new(self.io),
+                split: self.split.map(|(level, io)| (level, Mutex::new(io))),
+                routes: self.routes
+                    .into_iter()
+                    .map(|(value, io)| (value, Mutex::new(io)))
+                    .collect(),
+            }),
+            counters: Arc::new(Counters::default()),
+            level: LevelHandle::new(self.level, self.directives),
+            predicate: self.predicate,
+            sampler: Arc::new(RwLock::new(self.sampler)),
+            repeats: self.repeats,
+            rate_limiter: self.rate_limiter,
+            field_rules: Arc::new(RwLock::new(self.field_rules)),
+            extras: self.extras,
+            route_key: self.route_key,
+            echo: self.echo,
+            adaptive: self.adaptive,
+            #[cfg(feature = "audit")]
+            chain,
+            #[cfg(feature = "statsd")]
+            statsd: self.statsd.map(AssertUnwindSafe),
             pretty: self.pretty,
+            encoding: self.encoding,
         }
     }
 
@@ -339,12 +1416,316 @@ where
         self
     }
 
+    /// Set writing each record and its newline as one buffer, rather than
+    /// with a vectored write, e.g. so records written to a pipe stay atomic
+    /// (up to `PIPE_BUF`)
+    pub fn set_single_write(mut self, enabled: bool) -> Self {
+        self.single_write = enabled;
+        self
+    }
+
+    /// Set the minimum level of records written (default `Trace`, so
+    /// everything the logger passes on)
+    pub fn set_level(mut self, level: Level) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Set per-module minimum levels from `RUST_LOG` style directives,
+    /// e.g. `info,hyper=warn,my_crate::db=debug`, matched against
+    /// `Record::module`. A bare level sets the minimum level of other
+    /// modules.
+    pub fn set_directives(mut self, directives: &str) -> io::Result<Self> {
+        let (level, directives) = filter::parse_directives(directives)?;
+        if let Some(level) = level {
+            self.level = level;
+        }
+        self.directives = directives;
+        Ok(self)
+    }
+
+    /// Set keeping the last `capacity` records below the minimum level in
+    /// memory, and writing them, marked with `echo_on_error`, ahead of the
+    /// next Error or Critical record for context
+    pub fn set_echo_on_error(mut self, capacity: usize) -> Self {
+        self.echo = Some(EchoBuffer::new(capacity));
+        self
+    }
+
+    /// Set a predicate over each record's key-values deciding whether it's
+    /// written, evaluated before serialization, e.g. to drop health checks
+    /// where `path` is `/__lbheartbeat__`
+    pub fn set_filter<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&RecordFields) -> bool + Send + Sync + UnwindSafe + RefUnwindSafe + 'static,
+    {
+        self.predicate = Some(Box::new(predicate));
+        self
+    }
+
+    /// Set writing only these `Fields` keys (besides `msg`), replacing any
+    /// denylist
+    pub fn set_field_allowlist(mut self, keys: Vec<String>) -> Self {
+        self.field_rules.filter = Some(FieldFilter::Allow(keys.into_iter().collect()));
+        self
+    }
+
+    /// Set dropping these `Fields` keys, e.g. `cookie` and `authorization`,
+    /// replacing any allowlist
+    pub fn set_field_denylist(mut self, keys: Vec<String>) -> Self {
+        self.field_rules.filter = Some(FieldFilter::Deny(keys.into_iter().collect()));
+        self
+    }
+
+    /// Set whether values of sensitive keys (by default `password`,
+    /// `token`, `authorization`, `secret` and `api_key`, case-insensitively)
+    /// are replaced with `[REDACTED]`; on by default
+    pub fn set_mask_secrets(mut self, enabled: bool) -> Self {
+        self.field_rules.mask_secrets = enabled;
+        self
+    }
+
+    /// Add a key to the sensitive keys whose values are masked
+    pub fn add_secret_key(mut self, key: &str) -> Self {
+        self.field_rules.secret_keys.insert(key.to_lowercase());
+        self
+    }
+
+    /// Replace the default sensitive keys whose values are masked
+    pub fn set_secret_keys(mut self, keys: Vec<String>) -> Self {
+        self.field_rules.secret_keys = keys.iter().map(|key| key.to_lowercase()).collect();
+        self
+    }
+
+    /// Truncate `msg` and string `Fields` values longer than `len` bytes,
+    /// marking them with an ellipsis and their original length
+    pub fn set_max_value_len(mut self, len: usize) -> Self {
+        self.field_rules.max_value_len = Some(len);
+        self
+    }
+
+    /// Set adding the source location of each record's log statement to
+    /// its Fields, as `file`, `line` and `module`
+    pub fn set_location_fields(mut self, enabled: bool) -> Self {
+        self.extras.location = enabled;
+        self
+    }
+
+    /// Set adding the emitting thread's name (if it has one) and ID to
+    /// each record's Fields, as `thread_name` and `thread_id`
+    pub fn set_thread_fields(mut self, enabled: bool) -> Self {
+        self.extras.thread = enabled;
+        self
+    }
+
+    /// Set adding a backtrace, captured when it's written, to Error and
+    /// Critical records' Fields as `stack_trace` (as GCP Error Reporting
+    /// expects)
+    pub fn set_stack_traces(mut self, enabled: bool) -> Self {
+        self.extras.stack_trace = enabled;
+        self
+    }
+
+    /// Keep at most `max` logger and record key-values per record, counting
+    /// the rest in a `fields_overflow` field
+    pub fn set_max_fields(mut self, max: usize) -> Self {
+        self.field_rules.max_fields = Some(max);
+        self
+    }
+
+    /// Add a pattern replaced with `[REDACTED]` wherever it matches in `msg`
+    /// and string `Fields` values, e.g. credit card numbers or bearer
+    /// tokens
+    #[cfg(feature = "redact")]
+    pub fn add_redaction(mut self, pattern: Regex) -> Self {
+        self.field_rules.redactions.push(pattern);
+        self
+    }
+
+    /// Mask the given kinds of personal data (see `Pii::ALL`) wherever they
+    /// appear in `msg` and string `Fields` values
+    #[cfg(feature = "redact")]
+    pub fn set_pii_masking(mut self, kinds: &[Pii]) -> Self {
+        for kind in kinds {
+            self.field_rules.redactions.push(kind.pattern());
+        }
+        self
+    }
+
+    /// Replace the values of `keys` (e.g. `email`, `ip`) with the hex SHA-256
+    /// digest of `salt` followed by the value, keeping records joinable on
+    /// them without logging the raw values
+    #[cfg(feature = "pseudonymize")]
+    pub fn set_pseudonymized_fields(mut self, keys: Vec<String>, salt: &[u8]) -> Self {
+        self.field_rules.pseudonymized = keys.into_iter().collect();
+        self.field_rules.pseudonym_salt = salt.to_vec();
+        self
+    }
+
+    /// Set appending a `Chain` field to each record: the hex HMAC-SHA256,
+    /// keyed by `key`, of the previous record's `Chain` and the record
+    /// itself, so each output stream forms a hash chain that
+    /// `verify_chain` can check for tampering.
+    ///
+    /// Only applies to compact `Encoding::Json` output.
+    #[cfg(feature = "audit")]
+    pub fn set_hash_chain(mut self, key: &[u8]) -> Self {
+        self.chain_key = Some(key.to_vec());
+        self
+    }
+
+    /// Set counting written records on `client`, as `mozlog.records.<level>`
+    /// (e.g. `mozlog.records.error`) under the client's prefix
+    #[cfg(feature = "statsd")]
+    pub fn set_statsd(mut self, client: StatsdClient) -> Self {
+        self.statsd = Some(client);
+        self
+    }
+
+    /// Set keeping only a `rate` fraction (0 to 1) of the entities
+    /// identified by the `key` field, e.g. `user_id`: the value is hashed so
+    /// either all or none of an entity's records are kept. Records without
+    /// the field are always kept.
+    pub fn set_sampling(mut self, key: String, rate: f64) -> Self {
+        self.sampler = Some(Sampler::new(key, rate));
+        self
+    }
+
+    /// Set sampling records at a rate adjusted each second to keep output
+    /// within `budget`, adding the effective rate to each record's Fields
+    /// as `sample_rate` so downstream analysis can re-weight counts
+    pub fn set_adaptive_sampling(mut self, budget: SampleBudget) -> Self {
+        self.adaptive = Some(AdaptiveSampler::new(budget));
+        self
+    }
+
+    /// Set collapsing repeats of a record, syslog style: records with the
+    /// same level, message and values of `keys` as the last one, within
+    /// `window` of its first occurrence, are suppressed and then reported
+    /// as one "last message repeated N times" record
+    pub fn set_repeat_suppression(mut self, window: Duration, keys: Vec<String>) -> Self {
+        self.repeats = Some(RepeatSuppressor::new(window, keys));
+        self
+    }
+
+    /// Set limiting all records to `per_sec`, in bursts of up to `burst`
+    ///
+    /// Dropped records are summarized in a Warning record every report
+    /// interval.
+    pub fn set_rate_limit(mut self, per_sec: u32, burst: u32) -> Self {
+        self.rate_limiter().set_global(per_sec, burst);
+        self
+    }
+
+    /// Set limiting records at `level` to `per_sec`, in bursts of up to
+    /// `burst`, in addition to any overall limit
+    pub fn set_level_rate_limit(mut self, level: Level, per_sec: u32, burst: u32) -> Self {
+        self.rate_limiter().set_level(level, per_sec, burst);
+        self
+    }
+
+    /// Set how often records dropped by rate limiting are summarized
+    /// (default every 10 seconds)
+    pub fn set_rate_limit_report_interval(mut self, interval: Duration) -> Self {
+        self.rate_limiter().set_report_interval(interval);
+        self
+    }
+
+    fn rate_limiter(&mut self) -> &mut RateLimiter {
+        self.rate_limiter
+            .get_or_insert_with(|| RateLimiter::new(Duration::from_secs(10)))
+    }
+
+    /// Set the encoding of records (default `Json`)
+    pub fn set_encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
     /// Set whether or not pretty formatted logging should be used
     pub fn set_pretty(mut self, enabled: bool) -> Self {
         self.pretty = enabled;
         self
     }
 
+    /// Set using a coarse clock for `Timestamp`, trading sub-millisecond
+    /// accuracy for a cheaper read per record
+    pub fn set_coarse_clock(mut self, enabled: bool) -> Self {
+        self.coarse_clock = enabled;
+        self
+    }
+
+    /// Set the clock of `Timestamp` and `StartTime`, instead of the system
+    /// clock (overriding `set_coarse_clock`), e.g. the host's clock on
+    /// `wasm32-unknown-unknown`, which has none, or a `FixedClock` or
+    /// `ManualClock` for deterministic output in tests and replays
+    pub fn set_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Some(Arc::new(clock));
+        self
+    }
+
+    /// Set adding `SessionId`, a random UUID generated when the drain is
+    /// built, to each record, grouping a process lifetime's records even
+    /// when its hostname and pid are reused
+    #[cfg(feature = "session-id")]
+    pub fn set_session_id(mut self, enabled: bool) -> Self {
+        self.session_id = enabled;
+        self
+    }
+
+    /// Set adding `Seq`, a number incremented for each record, so gaps
+    /// reveal dropped records and records sharing a timestamp can be
+    /// ordered
+    pub fn set_seq(mut self, enabled: bool) -> Self {
+        self.seq = enabled;
+        self
+    }
+
+    /// Set adding `Uptime`, the seconds since the drain was built, to each
+    /// record, e.g. to correlate errors with restarts and warm-up
+    ///
+    /// Uptime is measured with `Instant`, unavailable on
+    /// `wasm32-unknown-unknown`.
+    pub fn set_uptime(mut self, enabled: bool) -> Self {
+        self.uptime = enabled;
+        self
+    }
+
+    /// Set adding `StartTime`, when the drain was built in nanoseconds
+    /// since the epoch, to each record
+    pub fn set_start_time(mut self, enabled: bool) -> Self {
+        self.start_time = enabled;
+        self
+    }
+
+    /// Set a second writer receiving records at `level` or more severe,
+    /// e.g. stderr for Warning and above
+    pub fn set_split<E>(mut self, level: Level, io: E) -> Self
+    where
+        E: io::Write + Send + 'static,
+    {
+        self.split = Some((level, Box::new(io)));
+        self
+    }
+
+    /// Set a writer for records whose route key (by default `Type`), among
+    /// the record's or its logger's key-values, has `value`, e.g.
+    /// `request.summary` records to an access log file, ahead of any split
+    pub fn set_route<E>(mut self, value: String, io: E) -> Self
+    where
+        E: io::Write + Send + 'static,
+    {
+        self.routes.retain(|(route, _)| *route != value);
+        self.routes.push((value, Box::new(io)));
+        self
+    }
+
+    /// Set the key whose value selects a route (default `Type`)
+    pub fn set_route_key(mut self, key: String) -> Self {
+        self.route_key = key;
+        self
+    }
+
     /// Add custom values to be printed with this formatter
     pub fn add_key_value<T>(mut self, value: slog::OwnedKV<T>) -> Self
     where
@@ -354,11 +1735,45 @@ where
         self
     }
 
+    /// Add `version`, and `commit` and `build_time` if known, as static
+    /// fields
+    pub fn add_build_info(mut self, info: BuildInfo) -> Self {
+        self.values.push(o!("version" => info.version).into());
+        for &(key, value) in &[("commit", info.commit), ("build_time", info.build_time)] {
+            if let Some(value) = value {
+                self.values.push(o!(key => value).into());
+            }
+        }
+        self
+    }
+
+    /// Add the ECS task (ARN, cluster, family, revision, availability zone,
+    /// from the task metadata endpoint) and Lambda function (name, version,
+    /// log stream, from its environment) we run in, if any, as static
+    /// fields. Fetched once, here.
+    #[cfg(feature = "aws-metadata")]
+    pub fn add_aws_metadata(mut self) -> Self {
+        for (key, value) in aws_metadata::fields() {
+            self.values.push(o!(key => value).into());
+        }
+        self
+    }
+
     pub fn logger_name(mut self, logger_name: String) -> Self {
         self.logger_name = Some(logger_name);
         self
     }
 
+    /// Set naming the `Logger` of records after their module path or
+    /// crate when `logger_name` isn't set, e.g. to tell apart the crates
+    /// of a workspace
+    pub fn set_logger_fallback(mut self, fallback: LoggerFallback) -> Self {
+        self.logger_fallback = Some(fallback);
+        self
+    }
+
+    /// Set the `Type` of records without a tag; tagged records, e.g.
+    /// `info!(log, #"request.summary", ..)`, use their tag
     pub fn msg_type(mut self, msg_type: String) -> Self {
         self.msg_type = Some(msg_type);
         self
@@ -368,6 +1783,325 @@ where
         self.hostname = Some(hostname);
         self
     }
+
+    /// Set the `Pid`, e.g. to a batch orchestrator's, or omit it with
+    /// `None`, e.g. in containers where it's always 1
+    ///
+    /// Defaults to the process id (omitted on WebAssembly). Give a
+    /// `BackgroundWriter` the same `set_pid` for its dropped records
+    /// reports.
+    pub fn set_pid(mut self, pid: Option<u32>) -> Self {
+        self.pid = pid;
+        self
+    }
+
+    /// Set the `EnvVersion` of the MozLog format, e.g. "2.0"
+    pub fn env_version(mut self, env_version: String) -> Self {
+        self.env_version = Some(env_version);
+        self
+    }
+}
+// }}}
+
+// {{{ Tests
+#[cfg(test)]
+mod tests {
+    use std::{io, thread, sync::{Arc, Mutex}, time::Duration};
+
+    use serde_json::{self, Value};
+    use slog::{Drain, Level, Logger};
+
+    use super::MozLogJsonBuilder;
+
+    /// Writer whose output can be read once it's been moved into a drain
+    #[derive(Clone, Default)]
+    struct Capture(Arc<Mutex<Vec<u8>>>);
+
+    impl Capture {
+        /// The JSON records written, compact or pretty printed
+        fn records(&self) -> Vec<Value> {
+            let bytes = self.0.lock().unwrap();
+            serde_json::Deserializer::from_slice(&bytes)
+                .into_iter()
+                .collect::<Result<_, _>>()
+                .unwrap()
+        }
+
+        /// `msg` of each record written
+        fn msgs(&self) -> Vec<Value> {
+            self.records()
+                .into_iter()
+                .map(|mut record| record["Fields"]["msg"].take())
+                .collect()
+        }
+    }
+
+    impl io::Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// `record` without its `Timestamp`, which differs between drains
+    fn untimed(mut record: Value) -> Value {
+        record.as_object_mut().unwrap().remove("Timestamp");
+        record
+    }
+
+    /// `Fields` of a record logged through the drain `configure` sets up
+    fn fields<F>(configure: F) -> Value
+    where
+        F: FnOnce(MozLogJsonBuilder<Capture>) -> MozLogJsonBuilder<Capture>,
+    {
+        let out = Capture::default();
+        let drain = configure(MozLogJsonBuilder::new_without_env(out.clone())).build();
+        let logger = Logger::root(drain.fuse(), o!("app" => "test"));
+        info!(logger, "hello"; "user" => "jdoe", "cookie" => "c");
+        out.records().remove(0)["Fields"].take()
+    }
+
+    #[test]
+    fn splits_by_level() {
+        let out = Capture::default();
+        let errors = Capture::default();
+        let drain = MozLogJsonBuilder::new_without_env(out.clone())
+            .set_split(Level::Error, errors.clone())
+            .build();
+        let logger = Logger::root(drain.fuse(), o!());
+        info!(logger, "info");
+        error!(logger, "error");
+        crit!(logger, "crit");
+        assert_eq!(out.msgs(), ["info"]);
+        assert_eq!(errors.msgs(), ["error", "crit"]);
+    }
+
+    #[test]
+    fn splices_constants_like_pretty() {
+        let log = |pretty| {
+            let out = Capture::default();
+            let drain = MozLogJsonBuilder::new_without_env(out.clone())
+                .logger_name("app".to_owned())
+                .msg_type("web".to_owned())
+                .hostname("host".to_owned())
+                .add_key_value(o!("version" => "1.0"))
+                .set_pretty(pretty)
+                .build();
+            let logger = Logger::root(drain.fuse(), o!("region" => "us"));
+            info!(logger, "hello"; "user" => "jdoe");
+            untimed(out.records().remove(0))
+        };
+        let compact = log(false);
+        assert_eq!(compact["Logger"], "app");
+        assert_eq!(compact["Type"], "web");
+        assert_eq!(compact["Hostname"], "host");
+        assert_eq!(compact["version"], "1.0");
+        assert_eq!(compact["Fields"]["region"], "us");
+        assert_eq!(compact, log(true));
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn frames_msgpack_records() {
+        use super::Encoding;
+
+        let out = Capture::default();
+        let drain = MozLogJsonBuilder::new_without_env(out.clone())
+            .set_encoding(Encoding::MessagePack)
+            .build();
+        let logger = Logger::root(drain.fuse(), o!("app" => "test"));
+        info!(logger, "one"; "n" => 1);
+        warn!(logger, "two");
+        let bytes = out.0.lock().unwrap().clone();
+        let mut rest = &bytes[..];
+        let mut records: Vec<Value> = vec![];
+        while !rest.is_empty() {
+            // Each record follows its big-endian u32 length
+            let len = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+            records.push(rmp_serde::from_slice(&rest[4..4 + len]).unwrap());
+            rest = &rest[4 + len..];
+        }
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["Fields"]["msg"], "one");
+        assert_eq!(records[0]["Fields"]["n"], 1);
+        assert_eq!(records[0]["Fields"]["app"], "test");
+        assert_eq!(records[1]["Severity"], 4);
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn writes_cbor_sequence() {
+        use super::Encoding;
+
+        let out = Capture::default();
+        let drain = MozLogJsonBuilder::new_without_env(out.clone())
+            .set_encoding(Encoding::Cbor)
+            .build();
+        let logger = Logger::root(drain.fuse(), o!("app" => "test"));
+        info!(logger, "one"; "n" => 1);
+        warn!(logger, "two");
+        let bytes = out.0.lock().unwrap().clone();
+        let mut rest = &bytes[..];
+        let first: Value = ciborium::de::from_reader(&mut rest).unwrap();
+        let second: Value = ciborium::de::from_reader(&mut rest).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(first["Fields"]["msg"], "one");
+        assert_eq!(first["Fields"]["n"], 1);
+        assert_eq!(first["Fields"]["app"], "test");
+        assert_eq!(second["Severity"], 4);
+    }
+
+    #[test]
+    fn changes_level_at_runtime() {
+        let out = Capture::default();
+        let drain = MozLogJsonBuilder::new_without_env(out.clone())
+            .set_level(Level::Warning)
+            .build();
+        let handle = drain.level_handle();
+        let logger = Logger::root(drain.fuse(), o!());
+        info!(logger, "dropped");
+        handle.set_level(Level::Info);
+        info!(logger, "kept");
+        debug!(logger, "too verbose");
+        assert_eq!(out.msgs(), ["kept"]);
+    }
+
+    #[test]
+    fn filters_by_predicate() {
+        let out = Capture::default();
+        let drain = MozLogJsonBuilder::new_without_env(out.clone())
+            .set_filter(|fields| fields.get("path").as_deref() != Some("/__lbheartbeat__"))
+            .build();
+        let logger = Logger::root(drain.fuse(), o!());
+        info!(logger, "health"; "path" => "/__lbheartbeat__");
+        info!(logger, "request"; "path" => "/api");
+        info!(logger, "startup");
+        assert_eq!(out.msgs(), ["request", "startup"]);
+    }
+
+    #[test]
+    fn allows_or_denies_fields() {
+        let allowed = fields(|builder| builder.set_field_allowlist(vec!["user".to_owned()]));
+        assert_eq!(allowed["msg"], "hello");
+        assert_eq!(allowed["user"], "jdoe");
+        assert!(allowed.get("cookie").is_none());
+        assert!(allowed.get("app").is_none());
+
+        let denied = fields(|builder| builder.set_field_denylist(vec!["cookie".to_owned()]));
+        assert_eq!(denied["user"], "jdoe");
+        assert_eq!(denied["app"], "test");
+        assert!(denied.get("cookie").is_none());
+    }
+
+    #[test]
+    fn routes_by_type() {
+        let out = Capture::default();
+        let access = Capture::default();
+        let drain = MozLogJsonBuilder::new_without_env(out.clone())
+            .set_route("request.summary".to_owned(), access.clone())
+            .build();
+        let logger = Logger::root(drain.fuse(), o!());
+        info!(logger, "GET /"; "Type" => "request.summary");
+        info!(logger, "other");
+        assert_eq!(access.msgs(), ["GET /"]);
+        assert_eq!(out.msgs(), ["other"]);
+    }
+
+    #[test]
+    fn routes_by_key() {
+        let out = Capture::default();
+        let audit = Capture::default();
+        let drain = MozLogJsonBuilder::new_without_env(out.clone())
+            .set_route_key("channel".to_owned())
+            .set_route("audit".to_owned(), audit.clone())
+            .build();
+        let logger = Logger::root(drain.fuse(), o!("channel" => "audit"));
+        info!(logger, "login");
+        info!(logger, "ping"; "channel" => "health");
+        assert_eq!(audit.msgs(), ["login"]);
+        assert_eq!(out.msgs(), ["ping"]);
+    }
+
+    #[test]
+    fn caps_field_count() {
+        let fields = fields(|builder| builder.set_max_fields(2));
+        assert_eq!(fields["fields_overflow"], 1);
+        // msg and the overflow count aren't capped
+        assert_eq!(fields.as_object().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn stamps_records_with_clock() {
+        use clock::FixedClock;
+
+        let out = Capture::default();
+        let drain = MozLogJsonBuilder::new_without_env(out.clone())
+            .set_clock(FixedClock(1_500_000_000_000_000_000))
+            .build();
+        let logger = Logger::root(drain.fuse(), o!());
+        info!(logger, "one");
+        info!(logger, "two");
+        for record in out.records() {
+            assert_eq!(record["Timestamp"], 1_500_000_000_000_000_000i64);
+        }
+    }
+
+    #[test]
+    fn overrides_or_omits_pid() {
+        let pid = |pid| {
+            let out = Capture::default();
+            let drain = MozLogJsonBuilder::new_without_env(out.clone()).set_pid(pid).build();
+            info!(Logger::root(drain.fuse(), o!()), "hello");
+            out.records().remove(0).get("Pid").cloned()
+        };
+        assert_eq!(pid(Some(42)), Some(Value::from(42)));
+        assert_eq!(pid(None), None);
+    }
+
+    /// Bytes written by a heartbeat started on the drain `configure` sets up
+    fn heartbeat<F>(configure: F) -> Vec<u8>
+    where
+        F: FnOnce(MozLogJsonBuilder<Capture>) -> MozLogJsonBuilder<Capture>,
+    {
+        let out = Capture::default();
+        let drain = configure(MozLogJsonBuilder::new_without_env(out.clone())).build();
+        drain.start_heartbeat(Duration::from_millis(1)).unwrap();
+        while out.0.lock().unwrap().is_empty() {
+            thread::sleep(Duration::from_millis(1));
+        }
+        drop(drain);
+        let bytes = out.0.lock().unwrap().clone();
+        bytes
+    }
+
+    #[test]
+    fn heartbeats_carry_statics() {
+        let bytes = heartbeat(|builder| builder.logger_name("app".to_owned()).set_start_time(true));
+        let record: Value = serde_json::Deserializer::from_slice(&bytes)
+            .into_iter()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(record["Type"], "heartbeat");
+        assert_eq!(record["Logger"], "app");
+        assert!(record["StartTime"].is_i64());
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn frames_msgpack_heartbeats() {
+        use super::Encoding;
+
+        let bytes = heartbeat(|builder| builder.set_encoding(Encoding::MessagePack));
+        let len = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+        let record: Value = rmp_serde::from_slice(&bytes[4..4 + len]).unwrap();
+        assert_eq!(record["Type"], "heartbeat");
+        assert_eq!(record["Fields"]["msg"], "heartbeat");
+    }
 }
 // }}}
 // vim: foldmethod=marker foldmarker={{{,}}}
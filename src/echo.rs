@@ -0,0 +1,37 @@
+use std::{mem, collections::VecDeque, sync::{Mutex, PoisonError}};
+
+use slog::Level;
+
+/// A serialized record: its level, route and payload
+pub(crate) type Buffered = (Level, Option<usize>, Vec<u8>);
+
+/// Recent records below the minimum level, kept to be echoed with the
+/// next error
+pub(crate) struct EchoBuffer {
+    capacity: usize,
+    records: Mutex<VecDeque<Buffered>>,
+}
+
+impl EchoBuffer {
+    pub(crate) fn new(capacity: usize) -> Self {
+        EchoBuffer {
+            capacity: capacity.max(1),
+            records: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Keep a record, forgetting the oldest when full
+    pub(crate) fn push(&self, record: Buffered) {
+        let mut records = self.records.lock().unwrap_or_else(PoisonError::into_inner);
+        if records.len() >= self.capacity {
+            records.pop_front();
+        }
+        records.push_back(record);
+    }
+
+    /// Take the kept records, oldest first
+    pub(crate) fn take(&self) -> VecDeque<Buffered> {
+        let mut records = self.records.lock().unwrap_or_else(PoisonError::into_inner);
+        mem::take(&mut *records)
+    }
+}
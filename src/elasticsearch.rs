@@ -0,0 +1,258 @@
+use std::{io, thread, sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc, format::{Item, StrftimeItems}};
+use serde_json::{self, Value};
+use ureq;
+
+use batch::{BatchConfig, BatchSink, BatchWriter, SendError};
+use http::ureq_error;
+
+/// Callback receiving records Elasticsearch could not index, along with
+/// the reason
+pub type DeadLetter = Arc<dyn Fn(&[u8], &str) + Send + Sync>;
+
+struct ElasticsearchSink {
+    agent: ureq::Agent,
+    url: String,
+    index: String,
+    headers: Vec<(String, String)>,
+    retries: (u32, Duration),
+    dead_letter: Option<DeadLetter>,
+    /// Indices of the batch's records still to be indexed, when a request
+    /// failed after earlier ones indexed the rest
+    unsent: Option<Vec<usize>>,
+}
+
+impl ElasticsearchSink {
+    /// Index name for a record, formatting the pattern with the record's
+    /// `Timestamp` (or the current time)
+    fn index_name(&self, record: &[u8]) -> String {
+        let timestamp = serde_json::from_slice::<Value>(record)
+            .ok()
+            .and_then(|parsed| parsed.get("Timestamp").and_then(Value::as_i64))
+            .and_then(|ns| {
+                DateTime::<Utc>::from_timestamp(
+                    ns.div_euclid(1_000_000_000),
+                    ns.rem_euclid(1_000_000_000) as u32,
+                )
+            })
+            .unwrap_or_else(Utc::now);
+        timestamp.format(&self.index).to_string()
+    }
+
+    /// `_bulk` request body indexing the `pending` records of `batch`
+    fn body(&self, batch: &[Vec<u8>], pending: &[usize]) -> Vec<u8> {
+        let mut body = Vec::new();
+        for &i in pending {
+            let record = &batch[i];
+            let action = json!({ "create": { "_index": self.index_name(record) } });
+            body.extend_from_slice(action.to_string().as_bytes());
+            body.push(b'\n');
+            body.extend_from_slice(record);
+            body.push(b'\n');
+        }
+        body
+    }
+
+    fn dead_letter(&self, record: &[u8], reason: &str) {
+        if let Some(ref dead_letter) = self.dead_letter {
+            dead_letter(record, reason);
+        }
+    }
+}
+
+impl BatchSink for ElasticsearchSink {
+    fn send(&mut self, batch: &[Vec<u8>]) -> Result<(), SendError> {
+        let (max_retries, mut backoff) = self.retries;
+        let mut pending: Vec<usize> = self.unsent
+            .take()
+            .unwrap_or_else(|| (0..batch.len()).collect());
+        let mut attempt = 0;
+        loop {
+            let mut request = self.agent
+                .post(&format!("{}/_bulk", self.url))
+                .set("Content-Type", "application/x-ndjson");
+            for (name, value) in &self.headers {
+                request = request.set(name, value);
+            }
+            let resp = match request.send_bytes(&self.body(batch, &pending)) {
+                Ok(resp) => resp,
+                Err(e) => {
+                    // Records indexed by earlier requests aren't sent again
+                    self.unsent = Some(pending);
+                    return Err(ureq_error(e));
+                }
+            };
+            let resp: Value = resp.into_string()
+                .ok()
+                .and_then(|body| serde_json::from_str(&body).ok())
+                .unwrap_or(Value::Null);
+            if !resp.get("errors").and_then(Value::as_bool).unwrap_or(false) {
+                return Ok(());
+            }
+
+            // Retry items that were throttled, dead letter the rest
+            let items = resp.get("items").and_then(Value::as_array).cloned().unwrap_or_default();
+            let mut throttled = vec![];
+            for (&i, item) in pending.iter().zip(items.iter()) {
+                let result = item.get("create").unwrap_or(&Value::Null);
+                let status = result.get("status").and_then(Value::as_u64).unwrap_or(0);
+                if status == 429 {
+                    throttled.push(i);
+                } else if status >= 300 {
+                    let reason = result.get("error").map(Value::to_string).unwrap_or_default();
+                    self.dead_letter(&batch[i], &reason);
+                }
+            }
+            if throttled.is_empty() {
+                return Ok(());
+            }
+            if attempt >= max_retries {
+                for i in throttled {
+                    self.dead_letter(&batch[i], "throttled (429)");
+                }
+                return Ok(());
+            }
+            pending = throttled;
+            thread::sleep(backoff);
+            backoff *= 2;
+            attempt += 1;
+        }
+    }
+
+    fn failed(&mut self, batch: &[Vec<u8>], err: &io::Error) {
+        let reason = err.to_string();
+        let unsent = self.unsent.take().unwrap_or_else(|| (0..batch.len()).collect());
+        for i in unsent {
+            self.dead_letter(&batch[i], &reason);
+        }
+    }
+}
+
+/// Elasticsearch `_bulk` API `io::Write` sink
+///
+/// Records are batched on a background thread (see `BatchConfig`) and
+/// indexed into an index named by a `strftime` pattern applied to each
+/// record's `Timestamp`, e.g. `mozlog-%Y.%m.%d`. Throttled (429) requests
+/// and items are retried, without resending records already indexed;
+/// records that still can't be indexed are passed to an optional dead
+/// letter callback. Requires compact output with
+/// newlines enabled.
+pub struct ElasticsearchWriter {
+    inner: BatchWriter,
+}
+
+impl ElasticsearchWriter {
+    /// Build an `ElasticsearchWriter` for the cluster at `url`
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(url: String) -> ElasticsearchWriterBuilder {
+        ElasticsearchWriterBuilder {
+            url: url.trim_end_matches('/').to_owned(),
+            index: "mozlog-%Y.%m.%d".to_owned(),
+            headers: vec![],
+            dead_letter: None,
+            timeout: Duration::from_secs(10),
+            batching: BatchConfig::default(),
+        }
+    }
+
+    /// Number of records dropped on a full queue or failed delivery
+    pub fn dropped(&self) -> usize {
+        self.inner.dropped()
+    }
+}
+
+impl io::Write for ElasticsearchWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// `ElasticsearchWriter` builder
+///
+/// Create with `ElasticsearchWriter::new`.
+pub struct ElasticsearchWriterBuilder {
+    url: String,
+    index: String,
+    headers: Vec<(String, String)>,
+    dead_letter: Option<DeadLetter>,
+    timeout: Duration,
+    batching: BatchConfig,
+}
+
+impl ElasticsearchWriterBuilder {
+    /// Build `ElasticsearchWriter`, starting its sender thread
+    ///
+    /// Fails if the index pattern isn't a valid `strftime` format.
+    pub fn build(self) -> io::Result<ElasticsearchWriter> {
+        if StrftimeItems::new(&self.index).any(|item| item == Item::Error) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid Elasticsearch index pattern: {}", self.index),
+            ));
+        }
+        let sink = ElasticsearchSink {
+            agent: ureq::AgentBuilder::new().timeout(self.timeout).build(),
+            url: self.url,
+            index: self.index,
+            headers: self.headers,
+            retries: self.batching.retries(),
+            dead_letter: self.dead_letter,
+            unsent: None,
+        };
+        Ok(ElasticsearchWriter {
+            inner: BatchWriter::spawn("mozlog-elasticsearch", sink, self.batching)?,
+        })
+    }
+
+    /// Set the index name pattern (`strftime` format, default
+    /// `mozlog-%Y.%m.%d`)
+    pub fn set_index(mut self, index: String) -> Self {
+        self.index = index;
+        self
+    }
+
+    /// Add a header sent with every request (e.g. `Authorization`)
+    pub fn add_header(mut self, name: String, value: String) -> Self {
+        self.headers.push((name, value));
+        self
+    }
+
+    /// Set a callback for records that could not be indexed
+    pub fn set_dead_letter<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&[u8], &str) + Send + Sync + 'static,
+    {
+        self.dead_letter = Some(Arc::new(f));
+        self
+    }
+
+    /// Set the timeout for each request
+    pub fn set_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set batching and backpressure behavior
+    pub fn set_batching(mut self, batching: BatchConfig) -> Self {
+        self.batching = batching;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ElasticsearchWriter;
+
+    #[test]
+    fn rejects_invalid_index_pattern() {
+        let writer = ElasticsearchWriter::new("http://localhost:9200".to_owned())
+            .set_index("mozlog-%Q".to_owned())
+            .build();
+        assert!(writer.is_err());
+    }
+}
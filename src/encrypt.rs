@@ -0,0 +1,102 @@
+use std::{io, iter};
+
+use age::{self, x25519, stream::StreamWriter};
+
+fn invalid<E: ToString>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, e.to_string())
+}
+
+/// Encrypting `io::Write` adapter
+///
+/// Encrypts the record stream to one or more age X25519 recipients
+/// (ChaCha20-Poly1305 payload) before writing it to the underlying writer.
+/// Plaintext is sealed in 64KiB chunks, so a crash loses at most the
+/// unsealed tail. The stream is finished when the writer is dropped, or
+/// explicitly with `finish`. Read it back with `decrypt_log` or the `age`
+/// CLI.
+pub struct EncryptedWriter<W: io::Write> {
+    stream: Option<StreamWriter<W>>,
+}
+
+impl<W: io::Write> EncryptedWriter<W> {
+    /// Build an `EncryptedWriter` over `io`
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(io: W) -> EncryptedWriterBuilder<W> {
+        EncryptedWriterBuilder {
+            io,
+            recipients: vec![],
+        }
+    }
+
+    /// Finish the encrypted stream, returning the underlying writer
+    pub fn finish(mut self) -> io::Result<W> {
+        self.stream.take().unwrap().finish()
+    }
+}
+
+impl<W: io::Write> io::Write for EncryptedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.as_mut().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.as_mut().unwrap().flush()
+    }
+}
+
+impl<W: io::Write> Drop for EncryptedWriter<W> {
+    fn drop(&mut self) {
+        if let Some(stream) = self.stream.take() {
+            let _ = stream.finish();
+        }
+    }
+}
+
+/// `EncryptedWriter` builder
+///
+/// Create with `EncryptedWriter::new`.
+pub struct EncryptedWriterBuilder<W> {
+    io: W,
+    recipients: Vec<x25519::Recipient>,
+}
+
+impl<W: io::Write> EncryptedWriterBuilder<W> {
+    /// Build `EncryptedWriter`, writing the age header
+    pub fn build(self) -> io::Result<EncryptedWriter<W>> {
+        let recipients = self
+            .recipients
+            .into_iter()
+            .map(|recipient| Box::new(recipient) as Box<dyn age::Recipient + Send>)
+            .collect();
+        let encryptor = age::Encryptor::with_recipients(recipients)
+            .ok_or_else(|| invalid("no recipients"))?;
+        Ok(EncryptedWriter {
+            stream: Some(encryptor.wrap_output(self.io).map_err(io::Error::other)?),
+        })
+    }
+
+    /// Add a recipient by its age public key (`age1...`)
+    pub fn add_recipient(mut self, public_key: &str) -> io::Result<Self> {
+        self.recipients.push(public_key.parse().map_err(invalid)?);
+        Ok(self)
+    }
+}
+
+/// Decrypt a stream written by `EncryptedWriter` with an age identity
+/// (`AGE-SECRET-KEY-1...`), returning the number of bytes written to
+/// `output`
+pub fn decrypt_log<R, W>(input: R, identity: &str, output: &mut W) -> io::Result<u64>
+where
+    R: io::Read,
+    W: io::Write,
+{
+    let identity: x25519::Identity = identity.parse().map_err(invalid)?;
+    let decryptor = match age::Decryptor::new(input).map_err(invalid)? {
+        age::Decryptor::Recipients(decryptor) => decryptor,
+        _ => return Err(invalid("stream is passphrase encrypted")),
+    };
+    let mut reader = decryptor
+        .decrypt(iter::once(&identity as &dyn age::Identity))
+        .map_err(invalid)?;
+    io::copy(&mut reader, output)
+}
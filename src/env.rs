@@ -0,0 +1,166 @@
+use std::{env, io, fs::OpenOptions, sync::{Mutex, PoisonError}};
+
+use serde_json::{self, Value};
+use slog::{self, Record, KV};
+
+use drain::{MozLogJson, MozLogJsonBuilder};
+use tcp::TcpWriter;
+#[cfg(unix)]
+use unix::UnixWriter;
+
+/// Keys of `MOZLOG_FIELDS` seen so far, leaked once each as slog keys are
+/// `'static`
+static KEYS: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+
+/// A boolean environment variable, if set: `true`/`false`, `1`/`0` or
+/// `yes`/`no`
+pub(crate) fn flag(name: &str) -> io::Result<Option<bool>> {
+    match env::var(name) {
+        Ok(value) => match value.trim().to_lowercase().as_str() {
+            "true" | "1" | "yes" => Ok(Some(true)),
+            "false" | "0" | "no" => Ok(Some(false)),
+            _ => Err(invalid(name, &value)),
+        },
+        Err(_) => Ok(None),
+    }
+}
+
+/// Static fields from the `MOZLOG_FIELDS` JSON object, if set, e.g.
+/// `{"env":"stage","region":"us-west-2"}`
+pub(crate) fn fields() -> io::Result<Option<EnvFields>> {
+    let value = match env::var("MOZLOG_FIELDS") {
+        Ok(value) => value,
+        Err(_) => return Ok(None),
+    };
+    match serde_json::from_str(&value) {
+        Ok(Value::Object(map)) => Ok(Some(EnvFields(
+            map.into_iter().map(|(key, value)| (intern(key), value)).collect(),
+        ))),
+        _ => Err(invalid("MOZLOG_FIELDS", &value)),
+    }
+}
+
+fn intern(key: String) -> &'static str {
+    let mut keys = KEYS.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(&key) = keys.iter().find(|&&known| known == key) {
+        return key;
+    }
+    let key: &'static str = Box::leak(key.into_boxed_str());
+    keys.push(key);
+    key
+}
+
+/// The fields of `MOZLOG_FIELDS`; arrays and objects are written as JSON
+/// text
+pub(crate) struct EnvFields(Vec<(&'static str, Value)>);
+
+impl KV for EnvFields {
+    fn serialize(&self, _rinfo: &Record, serializer: &mut dyn slog::Serializer) -> slog::Result {
+        for &(key, ref value) in &self.0 {
+            match *value {
+                Value::Null => serializer.emit_unit(key)?,
+                Value::Bool(value) => serializer.emit_bool(key, value)?,
+                Value::Number(ref number) => {
+                    if let Some(number) = number.as_u64() {
+                        serializer.emit_u64(key, number)?
+                    } else if let Some(number) = number.as_i64() {
+                        serializer.emit_i64(key, number)?
+                    } else {
+                        serializer.emit_f64(key, number.as_f64().unwrap_or(0.0))?
+                    }
+                }
+                Value::String(ref value) => serializer.emit_str(key, value)?,
+                Value::Array(_) | Value::Object(_) => {
+                    serializer.emit_arguments(key, &format_args!("{}", value))?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn invalid(name: &str, value: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("invalid {}: {:?}", name, value),
+    )
+}
+
+/// Open the writer named by `output`: `stdout`, `stderr`, `file:<path>`
+/// (appended to), `tcp://<host>:<port>` or (on Unix) `unix:<path>`, a
+/// stream socket
+pub(crate) fn open_output(output: &str) -> io::Result<Box<dyn io::Write + Send>> {
+    let output = output.trim();
+    match output {
+        "stdout" => return Ok(Box::new(io::stdout())),
+        "stderr" => return Ok(Box::new(io::stderr())),
+        _ => {}
+    }
+    if let Some(path) = output.strip_prefix("file:") {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        return Ok(Box::new(file));
+    }
+    if let Some(addr) = output.strip_prefix("tcp://") {
+        return Ok(Box::new(TcpWriter::new(addr.to_owned())));
+    }
+    #[cfg(unix)]
+    {
+        if let Some(path) = output.strip_prefix("unix:") {
+            return Ok(Box::new(UnixWriter::stream(path)));
+        }
+    }
+    Err(invalid("output", output))
+}
+
+/// `MozLogJson` drain configured entirely from the environment, see
+/// `MozLogJsonBuilder::from_env`
+///
+/// ```ignore
+/// // MOZLOG_OUTPUT=tcp://vector:9000 MOZLOG_LOGGER=my-app
+/// let logger = Logger::root(mozlog_from_env()?.fuse(), o!());
+/// ```
+pub fn mozlog_from_env() -> io::Result<MozLogJson<Box<dyn io::Write + Send>>> {
+    MozLogJsonBuilder::from_env().map(MozLogJsonBuilder::build)
+}
+
+impl MozLogJsonBuilder<Box<dyn io::Write + Send>> {
+    /// Builder configured entirely from the environment, so deployments
+    /// can set up logging without code changes:
+    ///
+    /// - `MOZLOG_OUTPUT`: `stdout` (the default), `stderr`, `file:<path>`,
+    ///   `tcp://<host>:<port>` or (on Unix) `unix:<path>`
+    /// - `MOZLOG_LEVEL`: minimum level and per-module directives, e.g.
+    ///   `info,hyper=warn` (as read by every builder)
+    /// - `MOZLOG_PRETTY`: `true` (or `1`, `yes`) for pretty printed records
+    ///   (as read by every builder)
+    /// - `MOZLOG_LOGGER`: the `Logger`
+    /// - `MOZLOG_TYPE`: the `Type` of untagged records
+    /// - `MOZLOG_HOSTNAME`: the `Hostname`, instead of the system's
+    /// - `MOZLOG_FIELDS`: a JSON object of static fields (as read by every
+    ///   builder)
+    ///
+    /// Unlike `MOZLOG_LEVEL`, `MOZLOG_PRETTY` and `MOZLOG_FIELDS` in other
+    /// builders, a malformed value is an error.
+    pub fn from_env() -> io::Result<Self> {
+        let output = env::var("MOZLOG_OUTPUT").unwrap_or_else(|_| "stdout".to_owned());
+        // Already added by `new`, which ignores a malformed value
+        fields()?;
+        let mut builder = MozLogJsonBuilder::new(open_output(&output)?);
+        if let Ok(spec) = env::var("MOZLOG_LEVEL") {
+            builder = builder.set_directives(&spec)?;
+        }
+        if let Some(pretty) = flag("MOZLOG_PRETTY")? {
+            builder = builder.set_pretty(pretty);
+        }
+        if let Ok(logger_name) = env::var("MOZLOG_LOGGER") {
+            builder = builder.logger_name(logger_name);
+        }
+        if let Ok(msg_type) = env::var("MOZLOG_TYPE") {
+            builder = builder.msg_type(msg_type);
+        }
+        if let Ok(hostname) = env::var("MOZLOG_HOSTNAME") {
+            builder = builder.hostname(hostname);
+        }
+        Ok(builder)
+    }
+}
@@ -0,0 +1,112 @@
+use std::{any, error::Error, fmt, iter};
+#[cfg(feature = "anyhow")]
+use std::backtrace::BacktraceStatus;
+
+#[cfg(feature = "anyhow")]
+use anyhow;
+#[cfg(feature = "eyre")]
+use eyre;
+use slog::{self, Key, Record, Value, KV};
+
+/// Keys for an error's causes: `Key`s are static, so only this many are
+/// logged
+const SOURCE_KEYS: [&str; 8] = [
+    "error.source[0]",
+    "error.source[1]",
+    "error.source[2]",
+    "error.source[3]",
+    "error.source[4]",
+    "error.source[5]",
+    "error.source[6]",
+    "error.source[7]",
+];
+
+/// Emit `err` as `error` and its `sources`, outermost first, as
+/// `error.source[0]` onwards
+fn emit_chain<'a, I>(
+    serializer: &mut dyn slog::Serializer,
+    err: &dyn fmt::Display,
+    sources: I,
+) -> slog::Result
+where
+    I: Iterator<Item = &'a (dyn Error + 'static)>,
+{
+    serializer.emit_arguments("error", &format_args!("{}", err))?;
+    for (key, source) in SOURCE_KEYS.iter().zip(sources) {
+        serializer.emit_arguments(key, &format_args!("{}", source))?;
+    }
+    Ok(())
+}
+
+/// A `std::error::Error` as Fields `error`, its causes (found by walking
+/// `source()`) as `error.source[0]` onwards, and its type's name as
+/// `error.type`
+///
+/// As a `Value` its key is ignored and the same fields are spliced in.
+///
+/// ```ignore
+/// error!(logger, "Request failed"; ErrValue(&e));
+/// ```
+pub struct ErrValue<E>(pub E);
+
+impl<E: Error> KV for ErrValue<E> {
+    fn serialize(&self, _rinfo: &Record, serializer: &mut dyn slog::Serializer) -> slog::Result {
+        emit_chain(serializer, &self.0, iter::successors(self.0.source(), |&e| e.source()))?;
+        serializer.emit_str("error.type", any::type_name::<E>())
+    }
+}
+
+impl<E: Error> Value for ErrValue<E> {
+    fn serialize(&self, rinfo: &Record, _key: Key, serializer: &mut dyn slog::Serializer) -> slog::Result {
+        KV::serialize(self, rinfo, serializer)
+    }
+}
+
+/// An `anyhow::Error` as Fields `error`, its causes as `error.source[0]`
+/// onwards, and if one was captured its backtrace as `stack_trace`
+///
+/// As a `Value` its key is ignored and the same fields are spliced in.
+#[cfg(feature = "anyhow")]
+pub struct AnyhowValue<'a>(pub &'a anyhow::Error);
+
+#[cfg(feature = "anyhow")]
+impl<'a> KV for AnyhowValue<'a> {
+    fn serialize(&self, _rinfo: &Record, serializer: &mut dyn slog::Serializer) -> slog::Result {
+        emit_chain(serializer, self.0, self.0.chain().skip(1))?;
+        let backtrace = self.0.backtrace();
+        if backtrace.status() == BacktraceStatus::Captured {
+            serializer.emit_arguments("stack_trace", &format_args!("{}", backtrace))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "anyhow")]
+impl<'a> Value for AnyhowValue<'a> {
+    fn serialize(&self, rinfo: &Record, _key: Key, serializer: &mut dyn slog::Serializer) -> slog::Result {
+        KV::serialize(self, rinfo, serializer)
+    }
+}
+
+/// An `eyre::Report` as Fields `error` and its causes as `error.source[0]`
+/// onwards
+///
+/// The backtrace, if any, belongs to the report's handler, so isn't
+/// included. As a `Value` its key is ignored and the same fields are
+/// spliced in.
+#[cfg(feature = "eyre")]
+pub struct EyreValue<'a>(pub &'a eyre::Report);
+
+#[cfg(feature = "eyre")]
+impl<'a> KV for EyreValue<'a> {
+    fn serialize(&self, _rinfo: &Record, serializer: &mut dyn slog::Serializer) -> slog::Result {
+        emit_chain(serializer, self.0, self.0.chain().skip(1))
+    }
+}
+
+#[cfg(feature = "eyre")]
+impl<'a> Value for EyreValue<'a> {
+    fn serialize(&self, rinfo: &Record, _key: Key, serializer: &mut dyn slog::Serializer) -> slog::Result {
+        KV::serialize(self, rinfo, serializer)
+    }
+}
@@ -0,0 +1,70 @@
+use std::{backtrace::Backtrace, thread};
+
+#[cfg(feature = "otel")]
+use opentelemetry::{Context, trace::TraceContextExt};
+use slog::{self, Level, Record, KV};
+
+use context;
+
+thread_local! {
+    /// `ThreadId` has no stable numeric accessor, so parse its `Debug` form
+    static THREAD_ID: u64 = format!("{:?}", thread::current().id())
+        .trim_start_matches("ThreadId(")
+        .trim_end_matches(')')
+        .parse()
+        .unwrap_or(0);
+}
+
+/// Fields the drain adds to every record's `Fields`, besides the current
+/// `request_id` and (with the `otel` feature) OpenTelemetry `trace_id` and
+/// `span_id`, if any
+#[derive(Default)]
+pub(crate) struct FieldExtras {
+    /// Add the emitting thread's `thread_name` (if named) and `thread_id`
+    pub(crate) thread: bool,
+    /// Add the record's source `file`, `line` and `module`
+    pub(crate) location: bool,
+    /// Add a `stack_trace` captured as Error and Critical records are
+    /// written
+    pub(crate) stack_trace: bool,
+}
+
+impl FieldExtras {
+    pub(crate) fn serialize<S: slog::Serializer>(
+        &self,
+        rinfo: &Record,
+        serializer: &mut S,
+    ) -> slog::Result {
+        context::with_request_id(|id| match id {
+            Some(id) => kv!("request_id" => id).serialize(rinfo, serializer),
+            None => Ok(()),
+        })?;
+        #[cfg(feature = "otel")]
+        {
+            let cx = Context::current();
+            let span = cx.span();
+            let span = span.span_context();
+            if span.is_valid() {
+                kv!(
+                    "trace_id" => format!("{}", span.trace_id()),
+                    "span_id" => format!("{}", span.span_id())
+                ).serialize(rinfo, serializer)?;
+            }
+        }
+        if self.location {
+            kv!("file" => rinfo.file(), "line" => rinfo.line(), "module" => rinfo.module())
+                .serialize(rinfo, serializer)?;
+        }
+        if self.thread {
+            if let Some(name) = thread::current().name() {
+                kv!("thread_name" => name).serialize(rinfo, serializer)?;
+            }
+            kv!("thread_id" => THREAD_ID.with(|id| *id)).serialize(rinfo, serializer)?;
+        }
+        if self.stack_trace && rinfo.level().is_at_least(Level::Error) {
+            kv!("stack_trace" => format!("{}", Backtrace::force_capture()))
+                .serialize(rinfo, serializer)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,93 @@
+use std::{io, time::{Duration, Instant}};
+
+use util::LineBuffer;
+
+/// `io::Write` adapter failing over from a primary writer to a fallback
+///
+/// Complete records are written to the primary writer; a record it fails
+/// to take is written to the fallback instead (e.g. stderr). After
+/// `max_failures` consecutive failures the primary is bypassed entirely,
+/// and only retried once per probe interval until it recovers.
+pub struct FallbackWriter<P: io::Write, F: io::Write> {
+    primary: P,
+    fallback: F,
+    lines: LineBuffer,
+    max_failures: u32,
+    failures: u32,
+    probe_interval: Duration,
+    next_probe: Option<Instant>,
+}
+
+impl<P: io::Write, F: io::Write> FallbackWriter<P, F> {
+    /// New `FallbackWriter` writing to `primary`, or `fallback` while it's
+    /// failing
+    pub fn new(primary: P, fallback: F) -> Self {
+        FallbackWriter {
+            primary,
+            fallback,
+            lines: LineBuffer::new(),
+            max_failures: 3,
+            failures: 0,
+            probe_interval: Duration::from_secs(10),
+            next_probe: None,
+        }
+    }
+
+    /// Set the number of consecutive failures before bypassing the primary
+    pub fn set_max_failures(mut self, failures: u32) -> Self {
+        self.max_failures = failures;
+        self
+    }
+
+    /// Set how often a bypassed primary is retried
+    pub fn set_probe_interval(mut self, interval: Duration) -> Self {
+        self.probe_interval = interval;
+        self
+    }
+
+    /// Whether records are currently bypassing the primary
+    pub fn is_failed_over(&self) -> bool {
+        self.next_probe.is_some()
+    }
+
+    fn write_record(&mut self, record: &[u8]) -> io::Result<()> {
+        let try_primary = match self.next_probe {
+            Some(at) => Instant::now() >= at,
+            None => true,
+        };
+        if try_primary {
+            match self.primary.write_all(record).and_then(|_| self.primary.flush()) {
+                Ok(()) => {
+                    self.failures = 0;
+                    self.next_probe = None;
+                    return Ok(());
+                }
+                Err(_) => {
+                    self.failures = self.failures.saturating_add(1);
+                    if self.failures >= self.max_failures {
+                        self.next_probe = Some(Instant::now() + self.probe_interval);
+                    }
+                }
+            }
+        }
+        self.fallback.write_all(record)
+    }
+}
+
+impl<P: io::Write, F: io::Write> io::Write for FallbackWriter<P, F> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut records = vec![];
+        self.lines.push(buf, |record| records.push(record));
+        for record in records {
+            self.write_record(&record)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.next_probe.is_none() && self.primary.flush().is_ok() {
+            return Ok(());
+        }
+        self.fallback.flush()
+    }
+}
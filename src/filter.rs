@@ -0,0 +1,245 @@
+use std::{cmp, io, collections::HashSet, panic::{RefUnwindSafe, UnwindSafe}, sync::{Arc, PoisonError, RwLock}, sync::atomic::{AtomicBool, AtomicUsize, Ordering}};
+
+use slog::{Level, OwnedKVList, Record};
+
+use sample::find_key;
+
+/// A record and its logger's key-values, for predicate filters
+pub struct RecordFields<'a, 'b: 'a> {
+    rinfo: &'a Record<'b>,
+    logger_values: &'a OwnedKVList,
+}
+
+impl<'a, 'b> RecordFields<'a, 'b> {
+    pub(crate) fn new(rinfo: &'a Record<'b>, logger_values: &'a OwnedKVList) -> Self {
+        RecordFields {
+            rinfo,
+            logger_values,
+        }
+    }
+
+    /// The record
+    pub fn record(&self) -> &Record<'b> {
+        self.rinfo
+    }
+
+    /// The value of `key`, formatted, from the record or failing that its
+    /// logger
+    pub fn get(&self, key: &str) -> Option<String> {
+        find_key(key, self.rinfo, self.logger_values)
+    }
+}
+
+/// Predicate deciding whether a record is written
+///
+/// Unwind safe, so the drain can still go in a `Logger`.
+pub(crate) type Predicate =
+    Box<dyn Fn(&RecordFields) -> bool + Send + Sync + UnwindSafe + RefUnwindSafe>;
+
+/// Which `Fields` keys are written
+pub(crate) enum FieldFilter {
+    Allow(HashSet<String>),
+    Deny(HashSet<String>),
+}
+
+impl FieldFilter {
+    pub(crate) fn allows(&self, key: &str) -> bool {
+        match *self {
+            FieldFilter::Allow(ref keys) => keys.contains(key),
+            FieldFilter::Deny(ref keys) => !keys.contains(key),
+        }
+    }
+}
+
+/// Module path and the minimum level of its records
+pub(crate) type Directive = (String, Level);
+
+pub(crate) fn parse_level(level: &str) -> Option<Level> {
+    match level.trim().to_lowercase().as_str() {
+        "trace" => Some(Level::Trace),
+        "debug" => Some(Level::Debug),
+        "info" => Some(Level::Info),
+        "warn" | "warning" => Some(Level::Warning),
+        "error" => Some(Level::Error),
+        "crit" | "critical" => Some(Level::Critical),
+        _ => None,
+    }
+}
+
+/// Parse `RUST_LOG` style directives, e.g. `info,hyper=warn,my_crate::db=debug`:
+/// an optional default level and per-module levels, a bare module meaning
+/// `trace`
+pub(crate) fn parse_directives(spec: &str) -> io::Result<(Option<Level>, Vec<Directive>)> {
+    let invalid = |part: &str| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid log directive: {:?}", part),
+        )
+    };
+    let mut default = None;
+    let mut directives = vec![];
+    for part in spec.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+        let mut split = part.splitn(2, '=');
+        let module = split.next().unwrap().trim();
+        match split.next() {
+            Some(level) => {
+                let level = parse_level(level).ok_or_else(|| invalid(part))?;
+                if module.is_empty() {
+                    return Err(invalid(part));
+                }
+                directives.push((module.to_owned(), level));
+            }
+            None => match parse_level(module) {
+                Some(level) => default = Some(level),
+                None => directives.push((module.to_owned(), Level::Trace)),
+            },
+        }
+    }
+    // Most specific first
+    directives.sort_by_key(|directive| cmp::Reverse(directive.0.len()));
+    Ok((default, directives))
+}
+
+struct Levels {
+    default: AtomicUsize,
+    /// Most verbose level of the default and any directive
+    max: AtomicUsize,
+    has_directives: AtomicBool,
+    directives: RwLock<Vec<Directive>>,
+}
+
+/// Handle changing a `MozLogJson` drain's minimum level, and per-module
+/// overrides, at runtime without rebuilding the logger tree
+///
+/// Create with `MozLogJson::level_handle`.
+#[derive(Clone)]
+pub struct LevelHandle {
+    levels: Arc<Levels>,
+}
+
+impl LevelHandle {
+    pub(crate) fn new(level: Level, directives: Vec<Directive>) -> Self {
+        let handle = LevelHandle {
+            levels: Arc::new(Levels {
+                default: AtomicUsize::new(level.as_usize()),
+                max: AtomicUsize::new(level.as_usize()),
+                has_directives: AtomicBool::new(false),
+                directives: RwLock::new(vec![]),
+            }),
+        };
+        handle.replace_directives(directives);
+        handle
+    }
+
+    /// Current minimum level of modules without a directive
+    pub fn level(&self) -> Level {
+        Level::from_usize(self.levels.default.load(Ordering::Relaxed)).unwrap_or(Level::Trace)
+    }
+
+    /// Set the minimum level of modules without a directive
+    pub fn set_level(&self, level: Level) {
+        self.levels.default.store(level.as_usize(), Ordering::Relaxed);
+        self.update_max();
+    }
+
+    /// Replace the module directives, e.g. `info,hyper=warn,my_crate::db=debug`
+    ///
+    /// A bare level sets the default level.
+    pub fn set_directives(&self, spec: &str) -> io::Result<()> {
+        let (default, directives) = parse_directives(spec)?;
+        if let Some(level) = default {
+            self.levels.default.store(level.as_usize(), Ordering::Relaxed);
+        }
+        self.replace_directives(directives);
+        Ok(())
+    }
+
+    fn replace_directives(&self, directives: Vec<Directive>) {
+        let has_directives = !directives.is_empty();
+        *self.levels
+            .directives
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = directives;
+        self.levels
+            .has_directives
+            .store(has_directives, Ordering::Relaxed);
+        self.update_max();
+    }
+
+    fn update_max(&self) {
+        let directives = self.levels
+            .directives
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        let max = directives
+            .iter()
+            .map(|&(_, level)| level.as_usize())
+            .fold(self.levels.default.load(Ordering::Relaxed), usize::max);
+        self.levels.max.store(max, Ordering::Relaxed);
+    }
+
+    /// Whether any module's records at `level` pass
+    pub(crate) fn level_enabled(&self, level: Level) -> bool {
+        level.as_usize() <= self.levels.max.load(Ordering::Relaxed)
+    }
+
+    /// Whether `record` passes
+    pub(crate) fn enabled(&self, record: &Record) -> bool {
+        let level = record.level().as_usize();
+        if self.levels.has_directives.load(Ordering::Relaxed) {
+            let module = record.module();
+            let directives = self.levels
+                .directives
+                .read()
+                .unwrap_or_else(PoisonError::into_inner);
+            let matching = directives.iter().find(|&(path, _)| {
+                module.starts_with(path.as_str())
+                    && (module.len() == path.len() || module[path.len()..].starts_with("::"))
+            });
+            if let Some(&(_, min)) = matching {
+                return level <= min.as_usize();
+            }
+        }
+        level <= self.levels.default.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use slog::Level;
+
+    use super::{parse_directives, LevelHandle};
+
+    #[test]
+    fn parses_directives() {
+        let (default, directives) =
+            parse_directives("info, hyper=warn,my_crate::db=debug,noisy").unwrap();
+        assert_eq!(default, Some(Level::Info));
+        // Most specific first
+        assert_eq!(
+            directives,
+            [
+                ("my_crate::db".to_owned(), Level::Debug),
+                ("hyper".to_owned(), Level::Warning),
+                ("noisy".to_owned(), Level::Trace),
+            ]
+        );
+        assert!(parse_directives("hyper=loud").is_err());
+        assert!(parse_directives("=warn").is_err());
+    }
+
+    #[test]
+    fn most_specific_directive_wins() {
+        let (default, directives) =
+            parse_directives("warn,slog_mozlog_json=info,slog_mozlog_json::filter=debug").unwrap();
+        let handle = LevelHandle::new(default.unwrap(), directives);
+        // Records logged here come from `slog_mozlog_json::filter::tests`
+        assert!(handle.enabled(&record!(Level::Debug, "", &format_args!("debug"), b!())));
+        assert!(!handle.enabled(&record!(Level::Trace, "", &format_args!("trace"), b!())));
+        assert!(handle.level_enabled(Level::Debug));
+        assert!(!handle.level_enabled(Level::Trace));
+
+        handle.set_directives("error").unwrap();
+        assert!(!handle.enabled(&record!(Level::Warning, "", &format_args!("warn"), b!())));
+    }
+}
@@ -0,0 +1,232 @@
+use std::{io, io::Write, net::{TcpStream, ToSocketAddrs}, time::Duration};
+
+use chrono;
+use rmpv::{self, Value as MsgValue};
+use serde_json::{self, Value};
+
+use batch::{BatchConfig, BatchSink, BatchWriter, SendError};
+
+fn other<E: ToString>(e: E) -> io::Error {
+    io::Error::other(e.to_string())
+}
+
+/// Convert a JSON value to its MessagePack equivalent
+fn to_msgpack(val: &Value) -> MsgValue {
+    match *val {
+        Value::Null => MsgValue::Nil,
+        Value::Bool(b) => MsgValue::from(b),
+        Value::Number(ref n) => {
+            if let Some(i) = n.as_i64() {
+                MsgValue::from(i)
+            } else if let Some(u) = n.as_u64() {
+                MsgValue::from(u)
+            } else {
+                MsgValue::from(n.as_f64().unwrap_or(0.0))
+            }
+        }
+        Value::String(ref s) => MsgValue::from(s.as_str()),
+        Value::Array(ref vals) => MsgValue::Array(vals.iter().map(to_msgpack).collect()),
+        Value::Object(ref map) => MsgValue::Map(
+            map.iter()
+                .map(|(k, v)| (MsgValue::from(k.as_str()), to_msgpack(v)))
+                .collect(),
+        ),
+    }
+}
+
+/// Fluentd `EventTime` extension for a nanosecond timestamp
+fn event_time(ns: i64) -> MsgValue {
+    let secs = ns.div_euclid(1_000_000_000) as u32;
+    let nsecs = ns.rem_euclid(1_000_000_000) as u32;
+    let mut data = Vec::with_capacity(8);
+    data.extend_from_slice(&secs.to_be_bytes());
+    data.extend_from_slice(&nsecs.to_be_bytes());
+    MsgValue::Ext(0, data)
+}
+
+struct FluentdSink {
+    addr: String,
+    tag: String,
+    timeout: Duration,
+    ack: bool,
+    stream: Option<TcpStream>,
+    chunks: u64,
+}
+
+impl FluentdSink {
+    fn connect(&self) -> io::Result<TcpStream> {
+        let mut last_err = None;
+        for addr in self.addr.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, self.timeout) {
+                Ok(stream) => {
+                    stream.set_read_timeout(Some(self.timeout))?;
+                    stream.set_write_timeout(Some(self.timeout))?;
+                    return Ok(stream);
+                }
+                Err(e) => last_err = Some(e),
+            }
+        }
+        Err(last_err.unwrap_or_else(|| other("no addresses to connect to")))
+    }
+
+    /// Forward mode message: `[tag, [[time, record], ...], option]`
+    fn message(&mut self, batch: &[Vec<u8>]) -> (MsgValue, String) {
+        let entries = batch
+            .iter()
+            .map(|record| {
+                let parsed: Value = serde_json::from_slice(record).unwrap_or_else(|_| {
+                    json!({ "message": String::from_utf8_lossy(record) })
+                });
+                let ns = parsed.get("Timestamp").and_then(Value::as_i64).unwrap_or_else(|| {
+                    let now = chrono::Utc::now();
+                    now.timestamp() * 1_000_000_000 + i64::from(now.timestamp_subsec_nanos())
+                });
+                MsgValue::Array(vec![event_time(ns), to_msgpack(&parsed)])
+            })
+            .collect();
+        self.chunks += 1;
+        let now = chrono::Utc::now();
+        let chunk = format!(
+            "{:x}-{:x}-{:x}",
+            now.timestamp_nanos_opt().unwrap_or(0),
+            ::std::process::id(),
+            self.chunks
+        );
+        let mut option = vec![(MsgValue::from("size"), MsgValue::from(batch.len() as u64))];
+        if self.ack {
+            option.push((MsgValue::from("chunk"), MsgValue::from(chunk.as_str())));
+        }
+        let message = MsgValue::Array(vec![
+            MsgValue::from(self.tag.as_str()),
+            MsgValue::Array(entries),
+            MsgValue::Map(option),
+        ]);
+        (message, chunk)
+    }
+
+    fn forward(&mut self, message: &MsgValue, chunk: &str) -> io::Result<()> {
+        if self.stream.is_none() {
+            self.stream = Some(self.connect()?);
+        }
+        let stream = self.stream.as_mut().unwrap();
+        let mut buf = Vec::new();
+        rmpv::encode::write_value(&mut buf, message).map_err(other)?;
+        stream.write_all(&buf)?;
+        stream.flush()?;
+        if self.ack {
+            let resp = rmpv::decode::read_value(stream).map_err(other)?;
+            let ack = resp.as_map().and_then(|map| {
+                map.iter()
+                    .find(|&(k, _)| k.as_str() == Some("ack"))
+                    .and_then(|(_, v)| v.as_str())
+            });
+            if ack != Some(chunk) {
+                return Err(other("fluentd did not acknowledge chunk"));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl BatchSink for FluentdSink {
+    fn send(&mut self, batch: &[Vec<u8>]) -> Result<(), SendError> {
+        let (message, chunk) = self.message(batch);
+        self.forward(&message, &chunk).map_err(|e| {
+            // Reconnect for the retry; the stream may be mid-message
+            self.stream = None;
+            SendError::Transient(e)
+        })
+    }
+}
+
+/// Fluentd forward protocol `io::Write` sink
+///
+/// Records are batched on a background thread (see `BatchConfig`) and sent
+/// to a fluentd or fluent-bit `forward` input as MessagePack Forward mode
+/// messages, each record's `Timestamp` becoming its event time. With acks
+/// enabled, each batch is retried until the agent confirms it. Requires
+/// compact output with newlines enabled.
+pub struct FluentdWriter {
+    inner: BatchWriter,
+}
+
+impl FluentdWriter {
+    /// Build a `FluentdWriter` for the agent at `addr` (`host:port`)
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(addr: String) -> FluentdWriterBuilder {
+        FluentdWriterBuilder {
+            addr,
+            tag: "mozlog".to_owned(),
+            ack: false,
+            timeout: Duration::from_secs(10),
+            batching: BatchConfig::default(),
+        }
+    }
+
+    /// Number of records dropped on a full queue or failed delivery
+    pub fn dropped(&self) -> usize {
+        self.inner.dropped()
+    }
+}
+
+impl io::Write for FluentdWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// `FluentdWriter` builder
+///
+/// Create with `FluentdWriter::new`.
+pub struct FluentdWriterBuilder {
+    addr: String,
+    tag: String,
+    ack: bool,
+    timeout: Duration,
+    batching: BatchConfig,
+}
+
+impl FluentdWriterBuilder {
+    /// Build `FluentdWriter`, starting its sender thread
+    pub fn build(self) -> io::Result<FluentdWriter> {
+        let sink = FluentdSink {
+            addr: self.addr,
+            tag: self.tag,
+            timeout: self.timeout,
+            ack: self.ack,
+            stream: None,
+            chunks: 0,
+        };
+        Ok(FluentdWriter {
+            inner: BatchWriter::spawn("mozlog-fluentd", sink, self.batching)?,
+        })
+    }
+
+    /// Set the fluentd tag (default `mozlog`)
+    pub fn set_tag(mut self, tag: String) -> Self {
+        self.tag = tag;
+        self
+    }
+
+    /// Set whether to request and wait for an ack of each batch
+    pub fn set_ack(mut self, enabled: bool) -> Self {
+        self.ack = enabled;
+        self
+    }
+
+    /// Set the connect, write and ack timeout
+    pub fn set_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set batching and backpressure behavior
+    pub fn set_batching(mut self, batching: BatchConfig) -> Self {
+        self.batching = batching;
+        self
+    }
+}
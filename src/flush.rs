@@ -0,0 +1,57 @@
+use std::{io, sync::{Arc, Mutex, PoisonError, Weak}};
+
+/// Something a `FlushHandle` can flush
+pub(crate) trait Flush: Send + Sync {
+    fn flush(&self) -> io::Result<()>;
+}
+
+/// Drains with outstanding `FlushHandle`s, for `flush_all`
+static REGISTRY: Mutex<Vec<Weak<dyn Flush>>> = Mutex::new(Vec::new());
+
+/// Handle flushing a `MozLogJson` drain's writers from elsewhere, e.g.
+/// just before process exit
+///
+/// Create with `MozLogJson::flush_handle`. Doesn't keep the drain alive;
+/// flushing a dropped drain does nothing (it flushed on drop).
+#[derive(Clone)]
+pub struct FlushHandle {
+    target: Weak<dyn Flush>,
+}
+
+impl FlushHandle {
+    pub(crate) fn new(target: Arc<dyn Flush>) -> Self {
+        let target = Arc::downgrade(&target);
+        let mut registry = REGISTRY.lock().unwrap_or_else(PoisonError::into_inner);
+        registry.retain(|drain| drain.upgrade().is_some());
+        registry.push(target.clone());
+        FlushHandle { target }
+    }
+
+    /// Flush the drain's writers
+    pub fn flush(&self) -> io::Result<()> {
+        match self.target.upgrade() {
+            Some(target) => target.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Flush every live drain a `FlushHandle` was created for
+///
+/// Returns the first error, after attempting all of them.
+pub fn flush_all() -> io::Result<()> {
+    let drains: Vec<_> = REGISTRY
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+        .filter_map(Weak::upgrade)
+        .collect();
+    let mut res = Ok(());
+    for drain in drains {
+        let flushed = drain.flush();
+        if res.is_ok() {
+            res = flushed;
+        }
+    }
+    res
+}
@@ -0,0 +1,170 @@
+use std::{env, fs, io, path::PathBuf, sync::Mutex, time::{Duration, Instant}};
+
+use chrono;
+use jsonwebtoken::{self, Algorithm, EncodingKey, Header};
+use serde_json::{self, Value};
+use ureq;
+
+const SCOPE: &str = "https://www.googleapis.com/auth/cloud-platform";
+const METADATA: &str = "http://metadata.google.internal/computeMetadata/v1";
+
+fn other<E: ToString>(e: E) -> io::Error {
+    io::Error::other(e.to_string())
+}
+
+/// Where access tokens come from
+enum Source {
+    ServiceAccount {
+        email: String,
+        key_id: Option<String>,
+        key: EncodingKey,
+        token_uri: String,
+    },
+    AuthorizedUser {
+        client_id: String,
+        client_secret: String,
+        refresh_token: String,
+    },
+    Metadata,
+}
+
+/// Fetch a metadata server value (e.g. `project/project-id`)
+pub(crate) fn metadata(agent: &ureq::Agent, path: &str) -> io::Result<String> {
+    agent
+        .get(&format!("{}/{}", METADATA, path))
+        .set("Metadata-Flavor", "Google")
+        .call()
+        .map_err(other)?
+        .into_string()
+}
+
+/// OAuth access tokens from Application Default Credentials
+///
+/// Uses the credentials file named by `GOOGLE_APPLICATION_CREDENTIALS`
+/// (a service account key), or gcloud's application default credentials,
+/// or the metadata server's default service account. Tokens are cached
+/// until shortly before they expire.
+pub(crate) struct AccessToken {
+    agent: ureq::Agent,
+    source: Source,
+    #[cfg_attr(not(feature = "cloud-logging"), allow(dead_code))]
+    project_id: Option<String>,
+    cached: Mutex<Option<(String, Instant)>>,
+}
+
+impl AccessToken {
+    pub(crate) fn application_default() -> io::Result<Self> {
+        let agent = ureq::AgentBuilder::new().timeout(Duration::from_secs(5)).build();
+        let path = env::var_os("GOOGLE_APPLICATION_CREDENTIALS")
+            .map(PathBuf::from)
+            .or_else(|| {
+                env::var_os("HOME").map(|home| {
+                    PathBuf::from(home).join(".config/gcloud/application_default_credentials.json")
+                })
+            })
+            .filter(|path| path.exists());
+        let (source, project_id) = match path {
+            Some(path) => {
+                let creds: Value = serde_json::from_slice(&fs::read(path)?)?;
+                let field = |name: &str| {
+                    creds.get(name)
+                        .and_then(Value::as_str)
+                        .map(str::to_owned)
+                        .ok_or_else(|| other(format!("credentials file missing {}", name)))
+                };
+                let source = match creds.get("type").and_then(Value::as_str) {
+                    Some("service_account") => Source::ServiceAccount {
+                        email: field("client_email")?,
+                        key_id: field("private_key_id").ok(),
+                        key: EncodingKey::from_rsa_pem(field("private_key")?.as_bytes())
+                            .map_err(other)?,
+                        token_uri: field("token_uri")
+                            .unwrap_or_else(|_| "https://oauth2.googleapis.com/token".to_owned()),
+                    },
+                    Some("authorized_user") => Source::AuthorizedUser {
+                        client_id: field("client_id")?,
+                        client_secret: field("client_secret")?,
+                        refresh_token: field("refresh_token")?,
+                    },
+                    _ => return Err(other("unsupported credentials type")),
+                };
+                (source, field("project_id").ok().or_else(|| field("quota_project_id").ok()))
+            }
+            None => (Source::Metadata, None),
+        };
+        Ok(AccessToken {
+            agent,
+            source,
+            project_id,
+            cached: Mutex::new(None),
+        })
+    }
+
+    /// Project of the credentials, if known
+    #[cfg_attr(not(feature = "cloud-logging"), allow(dead_code))]
+    pub(crate) fn project_id(&self) -> Option<String> {
+        match self.source {
+            Source::Metadata => metadata(&self.agent, "project/project-id").ok(),
+            _ => self.project_id.clone(),
+        }
+    }
+
+    pub(crate) fn get(&self) -> io::Result<String> {
+        let mut cached = self.cached.lock().unwrap();
+        if let Some((ref token, expires)) = *cached {
+            if Instant::now() < expires {
+                return Ok(token.clone());
+            }
+        }
+        let resp: Value = serde_json::from_str(&self.fetch()?)?;
+        let token = resp.get("access_token")
+            .and_then(Value::as_str)
+            .ok_or_else(|| other("token response has no access_token"))?
+            .to_owned();
+        let expires_in = resp.get("expires_in").and_then(Value::as_u64).unwrap_or(0);
+        let expires = Instant::now() + Duration::from_secs(expires_in.saturating_sub(60));
+        *cached = Some((token.clone(), expires));
+        Ok(token)
+    }
+
+    fn fetch(&self) -> io::Result<String> {
+        let resp = match self.source {
+            Source::ServiceAccount {
+                ref email,
+                ref key_id,
+                ref key,
+                ref token_uri,
+            } => {
+                let now = chrono::Utc::now().timestamp();
+                let claims = json!({
+                    "iss": email,
+                    "scope": SCOPE,
+                    "aud": token_uri,
+                    "iat": now,
+                    "exp": now + 3600,
+                });
+                let mut header = Header::new(Algorithm::RS256);
+                header.kid = key_id.clone();
+                let assertion = jsonwebtoken::encode(&header, &claims, key).map_err(other)?;
+                self.agent.post(token_uri).send_form(&[
+                    ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+                    ("assertion", &assertion),
+                ])
+            }
+            Source::AuthorizedUser {
+                ref client_id,
+                ref client_secret,
+                ref refresh_token,
+            } => self.agent.post("https://oauth2.googleapis.com/token").send_form(&[
+                ("grant_type", "refresh_token"),
+                ("client_id", client_id),
+                ("client_secret", client_secret),
+                ("refresh_token", refresh_token),
+            ]),
+            Source::Metadata => {
+                return metadata(&self.agent, "instance/service-accounts/default/token");
+            }
+        };
+        resp.map_err(other)?.into_string()
+    }
+}
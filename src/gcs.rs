@@ -0,0 +1,71 @@
+use std::{fs, io, path::Path, time::Duration};
+
+use ureq;
+
+use gcp::AccessToken;
+use rotate::RotateHook;
+
+/// `RotateHook` uploading completed segments to Google Cloud Storage
+///
+/// Authenticates with Application Default Credentials. Each segment is
+/// stored as `<prefix><file name>` and, by default, removed locally once
+/// uploaded.
+pub struct GcsUpload {
+    agent: ureq::Agent,
+    bucket: String,
+    prefix: String,
+    token: AccessToken,
+    delete: bool,
+}
+
+impl GcsUpload {
+    /// New `GcsUpload` to `bucket`
+    ///
+    /// Fails if the Application Default Credentials file can't be read.
+    pub fn new(bucket: String) -> io::Result<Self> {
+        Ok(GcsUpload {
+            agent: ureq::AgentBuilder::new().timeout(Duration::from_secs(300)).build(),
+            bucket,
+            prefix: String::new(),
+            token: AccessToken::application_default()?,
+            delete: true,
+        })
+    }
+
+    /// Set the object name prefix (e.g. `logs/myservice/`)
+    pub fn set_prefix(mut self, prefix: String) -> Self {
+        self.prefix = prefix;
+        self
+    }
+
+    /// Set whether segments are deleted locally after upload
+    pub fn set_delete_after_upload(mut self, enabled: bool) -> Self {
+        self.delete = enabled;
+        self
+    }
+}
+
+impl RotateHook for GcsUpload {
+    fn rotated(&self, path: &Path) -> io::Result<()> {
+        let name = path.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let body = fs::read(path)?;
+        let token = self.token.get()?;
+        self.agent
+            .post(&format!(
+                "https://storage.googleapis.com/upload/storage/v1/b/{}/o",
+                self.bucket
+            ))
+            .query("uploadType", "media")
+            .query("name", &format!("{}{}", self.prefix, name))
+            .set("Authorization", &format!("Bearer {}", token))
+            .set("Content-Type", "application/x-ndjson")
+            .send_bytes(&body)
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        if self.delete {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,151 @@
+use std::{io, time::Duration};
+
+use ureq;
+
+use batch::{BatchConfig, BatchSink, BatchWriter, SendError};
+
+/// Body format for `HttpWriter` batches
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HttpFormat {
+    /// Newline delimited JSON (`application/x-ndjson`)
+    Ndjson,
+    /// A JSON array of records (`application/json`)
+    JsonArray,
+}
+
+/// Classify a `ureq` failure: transport errors, throttling and server
+/// errors are worth retrying
+pub(crate) fn ureq_error(err: ureq::Error) -> SendError {
+    match err {
+        ureq::Error::Status(code, _) if code == 429 || code >= 500 => SendError::Transient(
+            io::Error::other(format!("HTTP status {}", code)),
+        ),
+        ureq::Error::Status(code, _) => SendError::Permanent(io::Error::other(
+            format!("HTTP status {}", code),
+        )),
+        ureq::Error::Transport(e) => {
+            SendError::Transient(io::Error::other(e.to_string()))
+        }
+    }
+}
+
+struct HttpSink {
+    agent: ureq::Agent,
+    url: String,
+    format: HttpFormat,
+    headers: Vec<(String, String)>,
+}
+
+impl BatchSink for HttpSink {
+    fn send(&mut self, batch: &[Vec<u8>]) -> Result<(), SendError> {
+        let (content_type, body) = match self.format {
+            HttpFormat::Ndjson => {
+                let mut body = Vec::new();
+                for record in batch {
+                    body.extend_from_slice(record);
+                    body.push(b'\n');
+                }
+                ("application/x-ndjson", body)
+            }
+            HttpFormat::JsonArray => {
+                let mut body = vec![b'['];
+                body.extend_from_slice(&batch.join(&b","[..]));
+                body.push(b']');
+                ("application/json", body)
+            }
+        };
+        let mut request = self.agent.post(&self.url).set("Content-Type", content_type);
+        for (name, value) in &self.headers {
+            request = request.set(name, value);
+        }
+        request.send_bytes(&body).map(|_| ()).map_err(ureq_error)
+    }
+}
+
+/// HTTP(S) batching `io::Write` sink
+///
+/// Records are queued to a background thread which POSTs them in batches
+/// to a configured endpoint, see `BatchConfig`. Requires compact output
+/// with newlines enabled, as records are framed by newline.
+pub struct HttpWriter {
+    inner: BatchWriter,
+}
+
+impl HttpWriter {
+    /// Build an `HttpWriter` POSTing to `url`
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(url: String) -> HttpWriterBuilder {
+        HttpWriterBuilder {
+            url,
+            format: HttpFormat::Ndjson,
+            headers: vec![],
+            timeout: Duration::from_secs(10),
+            batching: BatchConfig::default(),
+        }
+    }
+
+    /// Number of records dropped on a full queue or failed delivery
+    pub fn dropped(&self) -> usize {
+        self.inner.dropped()
+    }
+}
+
+impl io::Write for HttpWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// `HttpWriter` builder
+///
+/// Create with `HttpWriter::new`.
+pub struct HttpWriterBuilder {
+    url: String,
+    format: HttpFormat,
+    headers: Vec<(String, String)>,
+    timeout: Duration,
+    batching: BatchConfig,
+}
+
+impl HttpWriterBuilder {
+    /// Build `HttpWriter`, starting its sender thread
+    pub fn build(self) -> io::Result<HttpWriter> {
+        let sink = HttpSink {
+            agent: ureq::AgentBuilder::new().timeout(self.timeout).build(),
+            url: self.url,
+            format: self.format,
+            headers: self.headers,
+        };
+        Ok(HttpWriter {
+            inner: BatchWriter::spawn("mozlog-http", sink, self.batching)?,
+        })
+    }
+
+    /// Set the request body format
+    pub fn set_format(mut self, format: HttpFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Add a header sent with every request (e.g. `Authorization`)
+    pub fn add_header(mut self, name: String, value: String) -> Self {
+        self.headers.push((name, value));
+        self
+    }
+
+    /// Set the timeout for each request
+    pub fn set_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set batching and backpressure behavior
+    pub fn set_batching(mut self, batching: BatchConfig) -> Self {
+        self.batching = batching;
+        self
+    }
+}
@@ -0,0 +1,110 @@
+use std::io;
+
+#[cfg(feature = "init")]
+use log::{self, SetLoggerError};
+use slog::{Drain, Logger};
+use slog_scope::{self, GlobalLoggerGuard};
+#[cfg(feature = "init")]
+use slog_stdlog;
+
+use build_info::BuildInfo;
+#[cfg(feature = "init")]
+use drain::MozLogJson;
+use drain::MozLogJsonBuilder;
+
+fn install<W>(builder: MozLogJsonBuilder<W>) -> GlobalLoggerGuard
+where
+    W: io::Write + Send + 'static,
+{
+    slog_scope::set_global_logger(Logger::root(builder.build().fuse(), o!()))
+}
+
+/// Build `builder`'s drain, stamped with `info` (usually `build_info!()`),
+/// and install it as the global `slog-scope` logger until the returned
+/// guard is dropped
+///
+/// The drain is safe to share between threads as is, so needs no `Mutex`.
+/// For offloading writes from logging threads, give the builder a
+/// `BackgroundWriter`.
+pub fn set_global<W>(builder: MozLogJsonBuilder<W>, info: BuildInfo) -> GlobalLoggerGuard
+where
+    W: io::Write + Send + 'static,
+{
+    install(builder.add_build_info(info))
+}
+
+/// Log to stdout as `logger_name` through the global `slog-scope` logger
+/// and the `log` crate
+///
+/// Records get `Logger` `logger_name` and `Type` `<logger_name>:log`. Keep
+/// the returned guard alive for as long as logging should go to the drain.
+#[cfg(feature = "init")]
+pub fn init_mozlog(logger_name: &str) -> Result<GlobalLoggerGuard, SetLoggerError> {
+    init_mozlog_with(
+        MozLogJson::new(io::stdout())
+            .logger_name(logger_name.to_owned())
+            .msg_type(format!("{}:log", logger_name)),
+    )
+}
+
+/// Build `builder`'s drain and install it as the global `slog-scope` logger
+/// and the `log` crate backend
+///
+/// `log` records of all levels are forwarded; the drain's level filter
+/// applies to them like any others.
+#[cfg(feature = "init")]
+pub fn init_mozlog_with<W>(
+    builder: MozLogJsonBuilder<W>,
+) -> Result<GlobalLoggerGuard, SetLoggerError>
+where
+    W: io::Write + Send + 'static,
+{
+    let guard = install(builder);
+    slog_stdlog::init_with_level(log::Level::Trace)?;
+    Ok(guard)
+}
+
+#[cfg(test)]
+mod tests {
+    use slog_scope;
+
+    use build_info::BuildInfo;
+    use drain::MozLogJsonBuilder;
+    use test_drain::TestWriter;
+    use super::set_global;
+
+    // One test, as the global loggers are shared by all of them
+    #[test]
+    fn installs_global_logger() {
+        let writer = TestWriter::new();
+        let records = writer.records_handle();
+        {
+            let info = BuildInfo {
+                version: "1.2.3",
+                commit: Some("abc123"),
+                build_time: None,
+            };
+            let _guard = set_global(MozLogJsonBuilder::new_without_env(writer), info);
+            info!(slog_scope::logger(), "scoped");
+        }
+        let scoped = records.take();
+        assert_eq!(scoped.len(), 1);
+        assert_eq!(scoped[0]["Fields"]["msg"], "scoped");
+        assert_eq!(scoped[0]["version"], "1.2.3");
+        assert_eq!(scoped[0]["commit"], "abc123");
+
+        #[cfg(feature = "init")]
+        {
+            let writer = TestWriter::new();
+            let records = writer.records_handle();
+            let _guard = super::init_mozlog_with(
+                MozLogJsonBuilder::new_without_env(writer).logger_name("app".to_owned()),
+            ).unwrap();
+            log::warn!("forwarded");
+            let forwarded = records.take();
+            assert_eq!(forwarded.len(), 1);
+            assert_eq!(forwarded[0]["Logger"], "app");
+            assert_eq!(forwarded[0]["Fields"]["msg"], "forwarded");
+        }
+    }
+}
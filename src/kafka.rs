@@ -0,0 +1,183 @@
+use std::{io, thread, sync::Arc, sync::atomic::{AtomicUsize, Ordering}, time::Duration};
+
+use rdkafka::{ClientContext, config::ClientConfig, error::{KafkaError, RDKafkaErrorCode},
+              producer::{BaseRecord, DeliveryResult, Producer, ProducerContext, ThreadedProducer}};
+use serde_json::{self, Value};
+
+use util::LineBuffer;
+
+fn other<E: ToString>(e: E) -> io::Error {
+    io::Error::other(e.to_string())
+}
+
+/// Counts records the producer gave up delivering
+struct CountingContext {
+    dropped: Arc<AtomicUsize>,
+}
+
+impl ClientContext for CountingContext {}
+
+impl ProducerContext for CountingContext {
+    type DeliveryOpaque = ();
+
+    fn delivery(&self, result: &DeliveryResult, _: ()) {
+        if result.is_err() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Value of `key` at the top level of the record or within its `Fields`
+fn record_key(record: &[u8], key: &str) -> Option<String> {
+    let parsed: Value = serde_json::from_slice(record).ok()?;
+    let val = parsed
+        .get(key)
+        .or_else(|| parsed.get("Fields").and_then(|fields| fields.get(key)))?;
+    match *val {
+        Value::String(ref s) => Some(s.clone()),
+        Value::Null => None,
+        ref val => Some(val.to_string()),
+    }
+}
+
+/// Kafka producer `io::Write` sink
+///
+/// Each newline terminated record is produced, without its newline, to a
+/// topic. Records are held in the producer's bounded queue while brokers
+/// are unavailable; when it fills up, records are dropped (or logging
+/// blocks, see `set_block_on_full`).
+pub struct KafkaWriter {
+    producer: ThreadedProducer<CountingContext>,
+    topic: String,
+    key_field: Option<String>,
+    block_on_full: bool,
+    lines: LineBuffer,
+    dropped: Arc<AtomicUsize>,
+}
+
+impl KafkaWriter {
+    /// Build a `KafkaWriter` producing to `topic` via `brokers` (a comma
+    /// separated `host:port` list)
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(brokers: String, topic: String) -> KafkaWriterBuilder {
+        let mut config = ClientConfig::new();
+        config.set("bootstrap.servers", &brokers);
+        KafkaWriterBuilder {
+            config,
+            topic,
+            key_field: None,
+            queue_limit: 100_000,
+            block_on_full: false,
+        }
+    }
+
+    /// Number of records dropped on a full queue or failed delivery
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    fn produce(&self, record: &[u8]) -> io::Result<()> {
+        let key = self.key_field.as_ref().and_then(|field| record_key(record, field));
+        loop {
+            let mut base = BaseRecord::<str, [u8]>::to(&self.topic).payload(record);
+            if let Some(ref key) = key {
+                base = base.key(key.as_str());
+            }
+            match self.producer.send(base) {
+                Ok(()) => return Ok(()),
+                Err((KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull), _)) => {
+                    if !self.block_on_full {
+                        self.dropped.fetch_add(1, Ordering::Relaxed);
+                        return Ok(());
+                    }
+                    thread::sleep(Duration::from_millis(10));
+                }
+                Err((e, _)) => return Err(other(e)),
+            }
+        }
+    }
+}
+
+impl io::Write for KafkaWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut records = vec![];
+        self.lines.push(buf, |mut record| {
+            record.pop();
+            records.push(record);
+        });
+        for record in records {
+            self.produce(&record)?;
+        }
+        Ok(buf.len())
+    }
+
+    /// Wait for queued records to be delivered
+    fn flush(&mut self) -> io::Result<()> {
+        self.producer.flush(Duration::from_secs(30)).map_err(other)
+    }
+}
+
+impl Drop for KafkaWriter {
+    fn drop(&mut self) {
+        let _ = self.producer.flush(Duration::from_secs(5));
+    }
+}
+
+/// `KafkaWriter` builder
+///
+/// Create with `KafkaWriter::new`.
+pub struct KafkaWriterBuilder {
+    config: ClientConfig,
+    topic: String,
+    key_field: Option<String>,
+    queue_limit: usize,
+    block_on_full: bool,
+}
+
+impl KafkaWriterBuilder {
+    /// Build `KafkaWriter`, connecting the producer
+    pub fn build(mut self) -> io::Result<KafkaWriter> {
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let producer = self.config
+            .set("queue.buffering.max.messages", self.queue_limit.to_string())
+            .create_with_context(CountingContext {
+                dropped: dropped.clone(),
+            })
+            .map_err(other)?;
+        Ok(KafkaWriter {
+            producer,
+            topic: self.topic,
+            key_field: self.key_field,
+            block_on_full: self.block_on_full,
+            lines: LineBuffer::new(),
+            dropped,
+        })
+    }
+
+    /// Key each message by the value of `field` (top-level or in `Fields`,
+    /// e.g. `rid`), so related records land on the same partition
+    pub fn set_key_field(mut self, field: String) -> Self {
+        self.key_field = Some(field);
+        self
+    }
+
+    /// Set the maximum number of records queued in the producer
+    pub fn set_queue_limit(mut self, records: usize) -> Self {
+        self.queue_limit = records;
+        self
+    }
+
+    /// Set whether logging blocks when the queue is full, rather than
+    /// dropping the record
+    pub fn set_block_on_full(mut self, enabled: bool) -> Self {
+        self.block_on_full = enabled;
+        self
+    }
+
+    /// Set an arbitrary librdkafka producer property (e.g.
+    /// `security.protocol`)
+    pub fn set_property(mut self, key: &str, value: &str) -> Self {
+        self.config.set(key, value);
+        self
+    }
+}
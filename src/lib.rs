@@ -1,10 +1,315 @@
+#[cfg(feature = "actix")]
+extern crate actix_web;
+#[cfg(feature = "encrypt")]
+extern crate age;
+#[cfg(feature = "anyhow")]
+extern crate anyhow;
+#[cfg(feature = "azure")]
+extern crate base64;
+#[cfg(feature = "statsd")]
+extern crate cadence;
+#[cfg(feature = "chrono")]
 extern crate chrono;
+#[cfg(feature = "cbor")]
+extern crate ciborium;
+#[cfg(feature = "ring")]
+extern crate crossbeam_queue;
+#[cfg(feature = "eyre")]
+extern crate eyre;
+#[cfg(any(feature = "gzip", feature = "splunk"))]
+extern crate flate2;
+#[cfg(any(feature = "audit", feature = "azure", feature = "cloudwatch", feature = "s3"))]
+extern crate hmac;
+#[cfg(feature = "tower")]
+extern crate http1;
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+extern crate io_uring;
+#[cfg(any(feature = "cloud-logging", feature = "gcs"))]
+extern crate jsonwebtoken;
+#[cfg(unix)]
+extern crate libc;
+#[cfg(feature = "init")]
+extern crate log;
+#[cfg(all(feature = "oslog", target_os = "macos"))]
+extern crate oslog;
+#[cfg(feature = "mmap")]
+extern crate memmap2;
+#[cfg(feature = "otel")]
+extern crate opentelemetry;
+#[cfg(feature = "prometheus")]
+extern crate prometheus;
+#[cfg(feature = "kafka")]
+extern crate rdkafka;
+#[cfg(feature = "redis")]
+extern crate redis;
+#[cfg(feature = "redact")]
+extern crate regex;
+#[cfg(feature = "msgpack")]
+extern crate rmp_serde;
+#[cfg(feature = "fluentd")]
+extern crate rmpv;
+#[cfg(feature = "mqtt")]
+extern crate rumqttc;
+#[cfg(feature = "sqlite")]
+extern crate rusqlite;
+#[cfg(feature = "tls")]
+extern crate rustls;
+#[cfg(feature = "tls")]
+extern crate rustls_pemfile;
+#[cfg(feature = "sentry")]
+extern crate sentry;
 extern crate serde;
+#[macro_use]
 extern crate serde_json;
+#[cfg(any(
+    feature = "audit",
+    feature = "azure",
+    feature = "cloudwatch",
+    feature = "pseudonymize",
+    feature = "s3"
+))]
+extern crate sha2;
 #[macro_use]
 extern crate slog;
+#[cfg(feature = "async")]
+extern crate slog_async;
+#[cfg(feature = "scope")]
+extern crate slog_scope;
+#[cfg(feature = "init")]
+extern crate slog_stdlog;
+#[cfg(feature = "sonic")]
+extern crate sonic_rs;
+#[cfg(feature = "tokio")]
+extern crate tokio;
+#[cfg(feature = "tower")]
+extern crate tower_layer;
+#[cfg(feature = "tower")]
+extern crate tower_service;
+#[cfg(feature = "http")]
+extern crate ureq;
+#[cfg(any(feature = "session-id", feature = "splunk"))]
+extern crate uuid;
+#[cfg(feature = "warp")]
+extern crate warp;
+#[cfg(feature = "tls")]
+extern crate webpki_roots;
+#[cfg(feature = "zstd")]
+extern crate zstd;
 
+#[cfg(feature = "actix")]
+mod actix;
+mod adaptive;
+#[cfg(all(feature = "android", target_os = "android"))]
+mod android;
+#[cfg(feature = "async")]
+mod async_drain;
+#[cfg(feature = "tokio")]
+mod async_write;
+mod background;
+#[cfg(any(feature = "cloudwatch", feature = "s3"))]
+mod aws;
+#[cfg(feature = "aws-metadata")]
+mod aws_metadata;
+#[cfg(feature = "azure")]
+mod azure;
+#[cfg(any(feature = "http", feature = "fluentd", feature = "sqlite"))]
+mod batch;
+mod buffered;
+mod build_info;
+#[cfg(feature = "audit")]
+mod chain;
+mod clock;
+#[cfg(feature = "cloud-logging")]
+mod cloud_logging;
+#[cfg(feature = "cloudwatch")]
+mod cloudwatch;
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+mod compress;
+#[cfg(feature = "config")]
+mod config;
+mod context;
 mod drain;
+mod echo;
+#[cfg(feature = "elasticsearch")]
+mod elasticsearch;
+#[cfg(feature = "encrypt")]
+mod encrypt;
+mod env;
+mod error_value;
+mod extras;
+mod fallback;
+mod filter;
+#[cfg(feature = "fluentd")]
+mod fluentd;
+mod flush;
+#[cfg(any(feature = "cloud-logging", feature = "gcs"))]
+mod gcp;
+#[cfg(feature = "gcs")]
+mod gcs;
+#[cfg(feature = "http")]
+mod http;
+#[cfg(feature = "scope")]
+mod init;
+#[cfg(feature = "kafka")]
+mod kafka;
+mod log_entry;
+#[cfg(feature = "loki")]
+mod loki;
+#[cfg(feature = "prometheus")]
+mod metrics;
+#[cfg(feature = "mmap")]
+mod mmap_ring;
+#[cfg(feature = "mqtt")]
+mod mqtt;
+#[cfg(all(feature = "oslog", target_os = "macos"))]
+mod os_log;
+mod panic;
+#[cfg(any(unix, windows))]
+mod pipe;
+mod rate_limit;
+mod reconnect;
+mod repeat;
+#[cfg(feature = "redis")]
+mod redis_stream;
+#[cfg(feature = "config")]
+mod reload;
+#[cfg(feature = "ring")]
+mod ring;
+mod rotate;
+#[cfg(feature = "s3")]
+mod s3;
+mod sample;
+mod scrub;
+#[cfg(feature = "sentry")]
+mod sentry_drain;
+mod shard;
+#[cfg(unix)]
+mod signal;
+#[cfg(feature = "splunk")]
+mod splunk;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod stats;
+mod summary;
+mod tcp;
+mod tee;
+mod test_drain;
+#[cfg(feature = "tls")]
+mod tls;
+#[cfg(feature = "tower")]
+mod tower;
+mod udp;
+#[cfg(unix)]
+mod unix;
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+mod uring;
 mod util;
+#[cfg(feature = "warp")]
+mod warp_log;
 
-pub use drain::MozLogJson;
+#[cfg(feature = "actix")]
+pub use actix::{ActixSummary, ActixSummaryMiddleware, RequestUid, SummaryFuture};
+pub use adaptive::SampleBudget;
+#[cfg(all(feature = "android", target_os = "android"))]
+pub use android::AndroidLogWriter;
+#[cfg(feature = "async")]
+pub use async_drain::{mozlog_async, AsyncDropReports};
+#[cfg(feature = "tokio")]
+pub use async_write::AsyncWriter;
+pub use background::{BackgroundWriter, BackgroundWriterBuilder, OverflowPolicy, QueueHandle};
+#[cfg(feature = "azure")]
+pub use azure::{LogAnalyticsWriter, LogAnalyticsWriterBuilder};
+#[cfg(any(feature = "http", feature = "fluentd", feature = "sqlite"))]
+pub use batch::BatchConfig;
+pub use buffered::{BufferedWriter, BufferedWriterBuilder};
+pub use build_info::BuildInfo;
+#[cfg(feature = "audit")]
+pub use chain::verify_chain;
+pub use clock::{Clock, FixedClock, ManualClock, SystemClock};
+#[cfg(feature = "cloud-logging")]
+pub use cloud_logging::{CloudLoggingWriter, CloudLoggingWriterBuilder};
+#[cfg(feature = "cloudwatch")]
+pub use cloudwatch::{CloudWatchWriter, CloudWatchWriterBuilder};
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+pub use compress::CompressedWriter;
+#[cfg(feature = "config")]
+pub use config::{AdaptiveSamplingConfig, LevelRateLimitConfig, MozLogConfig, RateLimitConfig,
+                 RepeatConfig, SamplingConfig, SplitConfig};
+#[cfg(all(feature = "config", feature = "pseudonymize"))]
+pub use config::PseudonymizeConfig;
+#[cfg(feature = "tokio")]
+pub use context::scope_request_id;
+pub use context::{enter_request_id, RequestIdGuard};
+pub use drain::{Encoding, LoggerFallback, MozLogJson};
+#[cfg(feature = "elasticsearch")]
+pub use elasticsearch::{DeadLetter, ElasticsearchWriter, ElasticsearchWriterBuilder};
+#[cfg(feature = "encrypt")]
+pub use encrypt::{decrypt_log, EncryptedWriter, EncryptedWriterBuilder};
+pub use env::mozlog_from_env;
+#[cfg(feature = "anyhow")]
+pub use error_value::AnyhowValue;
+pub use error_value::ErrValue;
+#[cfg(feature = "eyre")]
+pub use error_value::EyreValue;
+pub use fallback::FallbackWriter;
+pub use filter::{LevelHandle, RecordFields};
+#[cfg(feature = "fluentd")]
+pub use fluentd::{FluentdWriter, FluentdWriterBuilder};
+pub use flush::{flush_all, FlushHandle};
+#[cfg(feature = "gcs")]
+pub use gcs::GcsUpload;
+#[cfg(feature = "http")]
+pub use http::{HttpFormat, HttpWriter, HttpWriterBuilder};
+#[cfg(feature = "init")]
+pub use init::{init_mozlog, init_mozlog_with};
+#[cfg(feature = "scope")]
+pub use init::set_global;
+#[cfg(feature = "kafka")]
+pub use kafka::{KafkaWriter, KafkaWriterBuilder};
+#[cfg(feature = "loki")]
+pub use loki::{LokiWriter, LokiWriterBuilder};
+#[cfg(feature = "prometheus")]
+pub use metrics::MozLogCollector;
+#[cfg(feature = "mmap")]
+pub use mmap_ring::MmapRingWriter;
+#[cfg(feature = "mqtt")]
+pub use mqtt::{MqttQos, MqttWriter, MqttWriterBuilder};
+#[cfg(all(feature = "oslog", target_os = "macos"))]
+pub use os_log::OsLogWriter;
+pub use panic::install_panic_hook;
+#[cfg(any(unix, windows))]
+pub use pipe::PipeWriter;
+#[cfg(feature = "redis")]
+pub use redis_stream::RedisStreamWriter;
+#[cfg(feature = "config")]
+pub use reload::ReloadHandle;
+#[cfg(feature = "ring")]
+pub use ring::RingDrain;
+pub use rotate::{RotateHook, RotatingFileWriter, SyncPolicy};
+#[cfg(feature = "s3")]
+pub use s3::S3Upload;
+#[cfg(feature = "redact")]
+pub use scrub::Pii;
+#[cfg(feature = "sentry")]
+pub use sentry_drain::SentryDrain;
+pub use shard::ShardedDrain;
+#[cfg(feature = "splunk")]
+pub use splunk::{SplunkWriter, SplunkWriterBuilder};
+#[cfg(feature = "sqlite")]
+pub use sqlite::{SqliteWriter, SqliteWriterBuilder};
+pub use stats::{DrainStats, StatsHandle};
+pub use summary::RequestSummary;
+pub use tcp::TcpWriter;
+pub use tee::Tee;
+pub use test_drain::{RecordsHandle, TestDrain, TestWriter};
+#[cfg(feature = "tls")]
+pub use tls::TlsConfig;
+#[cfg(feature = "tower")]
+pub use tower::{TowerSummary, TowerSummaryFuture, TowerSummaryLayer};
+pub use udp::{TruncationPolicy, UdpWriter};
+#[cfg(unix)]
+pub use unix::UnixWriter;
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+pub use uring::UringFileWriter;
+#[cfg(feature = "warp")]
+pub use warp_log::warp_summary;
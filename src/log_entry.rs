@@ -0,0 +1,83 @@
+use serde_json::{Map, Value};
+
+// Protobuf wire types
+const VARINT: u8 = 0;
+const FIXED64: u8 = 1;
+const LEN: u8 = 2;
+
+pub(crate) fn write_varint(buf: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        buf.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    buf.push(n as u8);
+}
+
+fn key(buf: &mut Vec<u8>, field: u32, wire_type: u8) {
+    write_varint(buf, u64::from(field << 3 | u32::from(wire_type)));
+}
+
+fn varint_field(buf: &mut Vec<u8>, field: u32, n: u64) {
+    key(buf, field, VARINT);
+    write_varint(buf, n);
+}
+
+fn bytes_field(buf: &mut Vec<u8>, field: u32, bytes: &[u8]) {
+    key(buf, field, LEN);
+    write_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+fn message_field<F: FnOnce(&mut Vec<u8>)>(buf: &mut Vec<u8>, field: u32, f: F) {
+    let mut message = vec![];
+    f(&mut message);
+    bytes_field(buf, field, &message);
+}
+
+/// `google.protobuf.Value`
+fn value(buf: &mut Vec<u8>, value: &Value) {
+    match *value {
+        Value::Null => varint_field(buf, 1, 0),
+        Value::Number(ref n) => {
+            key(buf, 2, FIXED64);
+            buf.extend_from_slice(&n.as_f64().unwrap_or(0.0).to_bits().to_le_bytes());
+        }
+        Value::String(ref s) => bytes_field(buf, 3, s.as_bytes()),
+        Value::Bool(b) => varint_field(buf, 4, b as u64),
+        Value::Object(ref object) => message_field(buf, 5, |s| structure(s, object)),
+        Value::Array(ref array) => message_field(buf, 6, |list| {
+            for item in array {
+                message_field(list, 1, |v| self::value(v, item));
+            }
+        }),
+    }
+}
+
+/// `google.protobuf.Struct`
+fn structure(buf: &mut Vec<u8>, object: &Map<String, Value>) {
+    for (name, field) in object {
+        message_field(buf, 1, |entry| {
+            bytes_field(entry, 1, name.as_bytes());
+            message_field(entry, 2, |v| value(v, field));
+        });
+    }
+}
+
+/// Encode a record as a `google.logging.v2.LogEntry`: the record as
+/// `json_payload`, with its `Timestamp` and `Severity` mapped to the entry's
+/// own fields
+pub(crate) fn encode(record: &Map<String, Value>, buf: &mut Vec<u8>) {
+    message_field(buf, 6, |payload| structure(payload, record));
+    if let Some(ns) = record.get("Timestamp").and_then(Value::as_i64) {
+        message_field(buf, 9, |timestamp| {
+            varint_field(timestamp, 1, ns.div_euclid(1_000_000_000) as u64);
+            varint_field(timestamp, 2, ns.rem_euclid(1_000_000_000) as u64);
+        });
+    }
+    // Syslog's EMERGENCY (0) to DEBUG (7) onto LogSeverity's 800 to 100
+    if let Some(severity) = record.get("Severity").and_then(Value::as_u64) {
+        if severity <= 7 {
+            varint_field(buf, 10, (8 - severity) * 100);
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use std::{io, collections::BTreeMap, time::Duration};
+
+use chrono;
+use serde_json::{self, Value};
+use ureq;
+
+use batch::{BatchConfig, BatchSink, BatchWriter, SendError};
+use http::ureq_error;
+
+struct LokiSink {
+    agent: ureq::Agent,
+    url: String,
+    labels: Vec<String>,
+    static_labels: BTreeMap<String, String>,
+    headers: Vec<(String, String)>,
+}
+
+impl LokiSink {
+    /// Loki push API request body for `batch`
+    fn body(&self, batch: &[Vec<u8>]) -> Value {
+        let mut streams: BTreeMap<BTreeMap<String, String>, Vec<(i64, String)>> = BTreeMap::new();
+        for record in batch {
+            let line = String::from_utf8_lossy(record).into_owned();
+            let parsed: Option<Value> = serde_json::from_slice(record).ok();
+            let mut labels = self.static_labels.clone();
+            let mut timestamp = None;
+            if let Some(ref parsed) = parsed {
+                for key in &self.labels {
+                    match parsed.get(key) {
+                        Some(Value::String(val)) => {
+                            labels.insert(key.clone(), val.clone());
+                        }
+                        Some(val) if !val.is_null() => {
+                            labels.insert(key.clone(), val.to_string());
+                        }
+                        _ => (),
+                    }
+                }
+                timestamp = parsed.get("Timestamp").and_then(Value::as_i64);
+            }
+            let timestamp = timestamp.unwrap_or_else(|| {
+                let now = chrono::Utc::now();
+                now.timestamp() * 1_000_000_000 + i64::from(now.timestamp_subsec_nanos())
+            });
+            streams.entry(labels).or_default().push((timestamp, line));
+        }
+
+        let streams: Vec<Value> = streams
+            .into_iter()
+            .map(|(labels, mut values)| {
+                // Loki rejects out of order entries within a stream
+                values.sort_by_key(|&(ts, _)| ts);
+                json!({
+                    "stream": labels,
+                    "values": values
+                        .into_iter()
+                        .map(|(ts, line)| json!([ts.to_string(), line]))
+                        .collect::<Vec<_>>(),
+                })
+            })
+            .collect();
+        json!({ "streams": streams })
+    }
+}
+
+impl BatchSink for LokiSink {
+    fn send(&mut self, batch: &[Vec<u8>]) -> Result<(), SendError> {
+        let body = serde_json::to_vec(&self.body(batch))
+            .map_err(|e| SendError::Permanent(io::Error::other(e)))?;
+        let mut request = self.agent
+            .post(&self.url)
+            .set("Content-Type", "application/json");
+        for (name, value) in &self.headers {
+            request = request.set(name, value);
+        }
+        request.send_bytes(&body).map(|_| ()).map_err(ureq_error)
+    }
+}
+
+/// Grafana Loki push API `io::Write` sink
+///
+/// Records are batched on a background thread (see `BatchConfig`) and
+/// pushed to Loki with the record JSON as the log line. Streams are labeled
+/// by the values of chosen top-level keys, by default `Logger`, `Type` and
+/// `Severity`. Requires compact output with newlines enabled.
+pub struct LokiWriter {
+    inner: BatchWriter,
+}
+
+impl LokiWriter {
+    /// Build a `LokiWriter` pushing to `url`, e.g.
+    /// `http://loki:3100/loki/api/v1/push`
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(url: String) -> LokiWriterBuilder {
+        LokiWriterBuilder {
+            url,
+            labels: vec!["Logger".to_owned(), "Type".to_owned(), "Severity".to_owned()],
+            static_labels: BTreeMap::new(),
+            headers: vec![],
+            timeout: Duration::from_secs(10),
+            batching: BatchConfig::default(),
+        }
+    }
+
+    /// Number of records dropped on a full queue or failed delivery
+    pub fn dropped(&self) -> usize {
+        self.inner.dropped()
+    }
+}
+
+impl io::Write for LokiWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// `LokiWriter` builder
+///
+/// Create with `LokiWriter::new`.
+pub struct LokiWriterBuilder {
+    url: String,
+    labels: Vec<String>,
+    static_labels: BTreeMap<String, String>,
+    headers: Vec<(String, String)>,
+    timeout: Duration,
+    batching: BatchConfig,
+}
+
+impl LokiWriterBuilder {
+    /// Build `LokiWriter`, starting its sender thread
+    pub fn build(self) -> io::Result<LokiWriter> {
+        let sink = LokiSink {
+            agent: ureq::AgentBuilder::new().timeout(self.timeout).build(),
+            url: self.url,
+            labels: self.labels,
+            static_labels: self.static_labels,
+            headers: self.headers,
+        };
+        Ok(LokiWriter {
+            inner: BatchWriter::spawn("mozlog-loki", sink, self.batching)?,
+        })
+    }
+
+    /// Set the top-level record keys whose values become stream labels
+    pub fn set_labels(mut self, keys: Vec<String>) -> Self {
+        self.labels = keys;
+        self
+    }
+
+    /// Add a label applied to every stream (e.g. `job`)
+    pub fn add_static_label(mut self, name: String, value: String) -> Self {
+        self.static_labels.insert(name, value);
+        self
+    }
+
+    /// Add a header sent with every request (e.g. `X-Scope-OrgID`)
+    pub fn add_header(mut self, name: String, value: String) -> Self {
+        self.headers.push((name, value));
+        self
+    }
+
+    /// Set the timeout for each request
+    pub fn set_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set batching and backpressure behavior
+    pub fn set_batching(mut self, batching: BatchConfig) -> Self {
+        self.batching = batching;
+        self
+    }
+}
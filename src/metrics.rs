@@ -0,0 +1,119 @@
+use prometheus::{IntCounter, IntCounterVec, IntGauge, Opts, core::{Collector, Desc},
+                 proto::MetricFamily};
+use slog::Level;
+
+use background::QueueHandle;
+use stats::StatsHandle;
+
+const LEVELS: [Level; 6] = [
+    Level::Critical,
+    Level::Error,
+    Level::Warning,
+    Level::Info,
+    Level::Debug,
+    Level::Trace,
+];
+
+struct Metrics {
+    records: IntCounterVec,
+    bytes: IntCounter,
+    write_errors: IntCounter,
+    dropped: IntCounter,
+    queue_depth: IntGauge,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        // The names and labels are constant, and valid
+        let counter = |name: &str, help: &str| IntCounter::with_opts(Opts::new(name, help)).unwrap();
+        Metrics {
+            records: IntCounterVec::new(
+                Opts::new("mozlog_records_total", "Log records written"),
+                &["level"],
+            ).unwrap(),
+            bytes: counter("mozlog_bytes_total", "Log bytes written"),
+            write_errors: counter(
+                "mozlog_write_errors_total",
+                "Log records that failed to serialize or write",
+            ),
+            dropped: counter(
+                "mozlog_dropped_total",
+                "Log records lost to write errors, full queues and filters",
+            ),
+            queue_depth: IntGauge::new(
+                "mozlog_queue_depth",
+                "Log records waiting in the background writer's queue",
+            ).unwrap(),
+        }
+    }
+}
+
+/// Prometheus `Collector` exporting a `MozLogJson` drain's counters
+///
+/// Exports `mozlog_records_total` (labeled by `level`),
+/// `mozlog_bytes_total`, `mozlog_write_errors_total`,
+/// `mozlog_dropped_total` and, once `set_queue` is called,
+/// `mozlog_queue_depth`. Values are read at scrape time, so logging pays
+/// nothing extra.
+///
+/// ```ignore
+/// prometheus::register(Box::new(drain.collector()))?;
+/// ```
+pub struct MozLogCollector {
+    stats: StatsHandle,
+    queue: Option<QueueHandle>,
+    descs: Metrics,
+}
+
+impl MozLogCollector {
+    pub(crate) fn new(stats: StatsHandle) -> Self {
+        MozLogCollector {
+            stats,
+            queue: None,
+            descs: Metrics::new(),
+        }
+    }
+
+    /// Also export the queue depth of the drain's `BackgroundWriter`
+    pub fn set_queue(mut self, queue: QueueHandle) -> Self {
+        self.queue = Some(queue);
+        self
+    }
+}
+
+impl Collector for MozLogCollector {
+    fn desc(&self) -> Vec<&Desc> {
+        let mut descs = self.descs.records.desc();
+        descs.extend(self.descs.bytes.desc());
+        descs.extend(self.descs.write_errors.desc());
+        descs.extend(self.descs.dropped.desc());
+        if self.queue.is_some() {
+            descs.extend(self.descs.queue_depth.desc());
+        }
+        descs
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        // Fresh metrics per scrape, so concurrent scrapes can't interleave
+        let metrics = Metrics::new();
+        let stats = self.stats.stats();
+        for &level in &LEVELS {
+            metrics
+                .records
+                .with_label_values(&[&level.as_str().to_lowercase()])
+                .inc_by(stats.records_at(level));
+        }
+        metrics.bytes.inc_by(stats.bytes);
+        metrics.write_errors.inc_by(stats.write_errors);
+        metrics.dropped.inc_by(stats.dropped);
+        let mut families = metrics.records.collect();
+        families.extend(metrics.bytes.collect());
+        families.extend(metrics.write_errors.collect());
+        families.extend(metrics.dropped.collect());
+        if let Some(ref queue) = self.queue {
+            metrics.queue_depth.set(queue.depth() as i64);
+            families.extend(metrics.queue_depth.collect());
+        }
+        families
+    }
+}
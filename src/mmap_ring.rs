@@ -0,0 +1,131 @@
+use std::{fs, io, mem, path::Path};
+
+use memmap2::MmapMut;
+
+const MAGIC: &[u8; 8] = b"MOZLOGR1";
+/// Magic, data capacity, then total bytes ever written
+const HEADER_LEN: usize = 24;
+
+fn read_u64(buf: &[u8]) -> u64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&buf[..8]);
+    u64::from_le_bytes(bytes)
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Crash-safe memory-mapped ring buffer `io::Write` sink
+///
+/// Newline terminated records are copied into a fixed size ring in a
+/// memory-mapped file, overwriting the oldest. The mapping is shared, so
+/// the most recent records survive the process crashing (though not the
+/// machine) without any flushing; read them back with `MmapRingWriter::dump`.
+/// The write position is only advanced once a record is fully copied, so a
+/// crash mid-record never exposes a torn record. Reopening an existing ring
+/// of the same capacity continues where it left off.
+pub struct MmapRingWriter {
+    map: MmapMut,
+    capacity: u64,
+    position: u64,
+    partial: Vec<u8>,
+}
+
+impl MmapRingWriter {
+    /// New `MmapRingWriter` keeping the last `capacity` bytes of records
+    /// in the file at `path`
+    pub fn new<P: AsRef<Path>>(path: P, capacity: u64) -> io::Result<Self> {
+        if capacity == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "ring capacity must be non-zero",
+            ));
+        }
+        let file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            // Keep an existing ring, to resume it
+            .truncate(false)
+            .open(path)?;
+        file.set_len(HEADER_LEN as u64 + capacity)?;
+        let mut map = unsafe { MmapMut::map_mut(&file)? };
+        let position = if &map[..8] == MAGIC && read_u64(&map[8..]) == capacity {
+            read_u64(&map[16..])
+        } else {
+            map[..8].copy_from_slice(MAGIC);
+            map[8..16].copy_from_slice(&capacity.to_le_bytes());
+            map[16..24].copy_from_slice(&0u64.to_le_bytes());
+            0
+        };
+        Ok(MmapRingWriter {
+            map,
+            capacity,
+            position,
+            partial: vec![],
+        })
+    }
+
+    /// Read the records held in the ring file at `path`, oldest first
+    pub fn dump<P: AsRef<Path>>(path: P) -> io::Result<Vec<u8>> {
+        let file = fs::read(path)?;
+        if file.len() < HEADER_LEN || &file[..8] != MAGIC {
+            return Err(invalid("not a mozlog ring file"));
+        }
+        let capacity = read_u64(&file[8..]);
+        let position = read_u64(&file[16..]);
+        let data = &file[HEADER_LEN..];
+        if data.len() as u64 != capacity {
+            return Err(invalid("truncated mozlog ring file"));
+        }
+        if position <= capacity {
+            return Ok(data[..position as usize].to_vec());
+        }
+        let start = (position % capacity) as usize;
+        let mut records = data[start..].to_vec();
+        records.extend_from_slice(&data[..start]);
+        // Skip the oldest record, partially overwritten
+        match records.iter().position(|b| *b == b'\n') {
+            Some(end) => Ok(records.split_off(end + 1)),
+            None => Ok(vec![]),
+        }
+    }
+
+    fn append(&mut self, record: &[u8]) {
+        if record.len() as u64 > self.capacity {
+            return;
+        }
+        let data = &mut self.map[HEADER_LEN..];
+        let start = (self.position % self.capacity) as usize;
+        let first = record.len().min(data.len() - start);
+        data[start..start + first].copy_from_slice(&record[..first]);
+        data[..record.len() - first].copy_from_slice(&record[first..]);
+        self.position += record.len() as u64;
+        self.map[16..24].copy_from_slice(&self.position.to_le_bytes());
+    }
+}
+
+impl io::Write for MmapRingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut rest = buf;
+        while let Some(pos) = rest.iter().position(|b| *b == b'\n') {
+            // Complete records are copied straight from `buf`
+            if self.partial.is_empty() {
+                self.append(&rest[..pos + 1]);
+            } else {
+                self.partial.extend_from_slice(&rest[..pos + 1]);
+                let record = mem::take(&mut self.partial);
+                self.append(&record);
+            }
+            rest = &rest[pos + 1..];
+        }
+        self.partial.extend_from_slice(rest);
+        Ok(buf.len())
+    }
+
+    /// Schedule writing the ring back to disk
+    fn flush(&mut self) -> io::Result<()> {
+        self.map.flush_async()
+    }
+}
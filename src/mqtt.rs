@@ -0,0 +1,172 @@
+use std::{io, process, thread, time::Duration};
+
+use rumqttc::{self, Client, ClientError, MqttOptions};
+
+use util::LineBuffer;
+
+fn other<E: ToString>(e: E) -> io::Error {
+    io::Error::other(e.to_string())
+}
+
+/// MQTT delivery guarantee for `MqttWriter` records
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MqttQos {
+    /// Fire and forget (QoS 0)
+    AtMostOnce,
+    /// Acknowledged, possibly redelivered (QoS 1)
+    AtLeastOnce,
+    /// Acknowledged exactly once (QoS 2)
+    ExactlyOnce,
+}
+
+impl From<MqttQos> for rumqttc::QoS {
+    fn from(qos: MqttQos) -> Self {
+        match qos {
+            MqttQos::AtMostOnce => rumqttc::QoS::AtMostOnce,
+            MqttQos::AtLeastOnce => rumqttc::QoS::AtLeastOnce,
+            MqttQos::ExactlyOnce => rumqttc::QoS::ExactlyOnce,
+        }
+    }
+}
+
+/// MQTT publisher `io::Write` sink
+///
+/// Each newline terminated record is published, without its newline, to a
+/// topic. A background thread drives the connection, reconnecting as
+/// needed; records wait in a bounded queue meanwhile and are dropped once
+/// it fills up.
+pub struct MqttWriter {
+    client: Client,
+    topic: String,
+    qos: rumqttc::QoS,
+    lines: LineBuffer,
+    dropped: usize,
+}
+
+impl MqttWriter {
+    /// Build an `MqttWriter` publishing to `topic` on the broker at
+    /// `host`:`port`
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(host: String, port: u16, topic: String) -> MqttWriterBuilder {
+        MqttWriterBuilder {
+            host,
+            port,
+            topic,
+            client_id: format!("mozlog-{}", process::id()),
+            qos: MqttQos::AtLeastOnce,
+            credentials: None,
+            keep_alive: Duration::from_secs(30),
+            queue_limit: 10_000,
+        }
+    }
+
+    /// Number of records dropped on a full queue
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+}
+
+impl io::Write for MqttWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut records = vec![];
+        self.lines.push(buf, |mut record| {
+            record.pop();
+            records.push(record);
+        });
+        for record in records {
+            match self.client.try_publish(self.topic.as_str(), self.qos, false, record) {
+                Ok(()) => (),
+                Err(ClientError::TryRequest(_)) => {
+                    self.dropped += 1;
+                }
+                Err(e) => return Err(other(e)),
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for MqttWriter {
+    /// Queue a disconnect behind any pending records
+    fn drop(&mut self) {
+        let _ = self.client.try_disconnect();
+    }
+}
+
+/// `MqttWriter` builder
+///
+/// Create with `MqttWriter::new`.
+pub struct MqttWriterBuilder {
+    host: String,
+    port: u16,
+    topic: String,
+    client_id: String,
+    qos: MqttQos,
+    credentials: Option<(String, String)>,
+    keep_alive: Duration,
+    queue_limit: usize,
+}
+
+impl MqttWriterBuilder {
+    /// Build `MqttWriter`, starting its connection thread
+    pub fn build(self) -> io::Result<MqttWriter> {
+        let mut options = MqttOptions::new(self.client_id, self.host, self.port);
+        options.set_keep_alive(self.keep_alive);
+        if let Some((username, password)) = self.credentials {
+            options.set_credentials(username, password);
+        }
+        let (client, mut connection) = Client::new(options, self.queue_limit);
+        thread::Builder::new()
+            .name("mozlog-mqtt".to_owned())
+            .spawn(move || {
+                // Polling after an error reconnects; the iterator ends once
+                // the writer has disconnected
+                for notification in connection.iter() {
+                    if notification.is_err() {
+                        thread::sleep(Duration::from_secs(1));
+                    }
+                }
+            })?;
+        Ok(MqttWriter {
+            client,
+            topic: self.topic,
+            qos: self.qos.into(),
+            lines: LineBuffer::new(),
+            dropped: 0,
+        })
+    }
+
+    /// Set the MQTT client identifier (default `mozlog-<pid>`)
+    pub fn set_client_id(mut self, client_id: String) -> Self {
+        self.client_id = client_id;
+        self
+    }
+
+    /// Set the delivery guarantee (default `AtLeastOnce`)
+    pub fn set_qos(mut self, qos: MqttQos) -> Self {
+        self.qos = qos;
+        self
+    }
+
+    /// Authenticate with `username` and `password`
+    pub fn set_credentials(mut self, username: String, password: String) -> Self {
+        self.credentials = Some((username, password));
+        self
+    }
+
+    /// Set the keep alive interval
+    pub fn set_keep_alive(mut self, keep_alive: Duration) -> Self {
+        self.keep_alive = keep_alive;
+        self
+    }
+
+    /// Set the maximum number of records queued while disconnected
+    pub fn set_queue_limit(mut self, records: usize) -> Self {
+        self.queue_limit = records;
+        self
+    }
+}
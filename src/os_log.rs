@@ -0,0 +1,57 @@
+use std::io;
+
+use oslog::{Level, OsLog};
+use serde_json::{self, Value};
+
+use util::LineBuffer;
+
+/// Unified logging level for a MozLog (syslog) `Severity`
+fn os_log_level(severity: Option<u64>) -> Level {
+    match severity {
+        Some(0) | Some(1) | Some(2) => Level::Fault,
+        Some(3) => Level::Error,
+        Some(6) => Level::Info,
+        Some(7) => Level::Debug,
+        _ => Level::Default,
+    }
+}
+
+/// Apple unified logging (`os_log`) `io::Write` sink
+///
+/// Each newline terminated record is logged, without its newline, as the
+/// message of an `os_log` entry under a subsystem and category, at the
+/// level mapped from its `Severity` (warnings use the default level), so
+/// it shows up in Console.app and `log stream`. macOS only.
+pub struct OsLogWriter {
+    log: OsLog,
+    lines: LineBuffer,
+}
+
+impl OsLogWriter {
+    /// New `OsLogWriter` for `subsystem` (e.g. `org.mozilla.vpn`) and
+    /// `category`
+    pub fn new(subsystem: &str, category: &str) -> Self {
+        OsLogWriter {
+            log: OsLog::new(subsystem, category),
+            lines: LineBuffer::new(),
+        }
+    }
+}
+
+impl io::Write for OsLogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let log = &self.log;
+        self.lines.push(buf, |mut record| {
+            record.pop();
+            let severity = serde_json::from_slice::<Value>(&record)
+                .ok()
+                .and_then(|parsed| parsed.get("Severity").and_then(Value::as_u64));
+            log.with_level(os_log_level(severity), &String::from_utf8_lossy(&record));
+        });
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
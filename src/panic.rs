@@ -0,0 +1,39 @@
+use std::{any::Any, backtrace::Backtrace, panic, thread};
+
+use slog::Logger;
+
+use flush::flush_all;
+
+/// Install a panic hook logging panics to `logger` as Critical `panic`
+/// records, with Fields `location`, `thread` and `stack_trace`, then
+/// flushing every drain with a `FlushHandle` (see `flush_all`) before
+/// running the previously installed hook.
+///
+/// A panic while a drain's writer is locked (i.e. in the writer itself)
+/// can't be logged through that drain.
+pub fn install_panic_hook(logger: Logger) {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let location = info
+            .location()
+            .map(|location| format!("{}:{}:{}", location.file(), location.line(), location.column()));
+        let backtrace = Backtrace::force_capture();
+        crit!(logger, #"panic", "{}", payload_msg(info.payload());
+            "location" => location,
+            "thread" => thread::current().name(),
+            "stack_trace" => %backtrace
+        );
+        let _ = flush_all();
+        previous(info);
+    }));
+}
+
+fn payload_msg(payload: &(dyn Any + Send)) -> &str {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg
+    } else {
+        "Box<dyn Any>"
+    }
+}
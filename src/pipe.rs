@@ -0,0 +1,88 @@
+use std::{fs::{File, OpenOptions}, io, path::PathBuf, time::Duration};
+
+#[cfg(unix)]
+use libc;
+#[cfg(unix)]
+use std::os::unix::{fs::OpenOptionsExt, io::AsRawFd};
+
+use reconnect::{Connect, Reconnecting};
+
+struct PipeConnector {
+    path: PathBuf,
+}
+
+impl Connect for PipeConnector {
+    type Stream = File;
+
+    /// Open the FIFO without waiting for a reader (failing with `ENXIO`
+    /// when there is none), then switch it back to blocking writes
+    #[cfg(unix)]
+    fn connect(&self) -> io::Result<File> {
+        let file = OpenOptions::new()
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(&self.path)?;
+        let fd = file.as_raw_fd();
+        unsafe {
+            let flags = libc::fcntl(fd, libc::F_GETFL);
+            if flags < 0 || libc::fcntl(fd, libc::F_SETFL, flags & !libc::O_NONBLOCK) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(file)
+    }
+
+    /// Open the pipe as a client, failing while the server has no free
+    /// instance
+    #[cfg(windows)]
+    fn connect(&self) -> io::Result<File> {
+        OpenOptions::new().write(true).open(&self.path)
+    }
+}
+
+/// Named pipe `io::Write` sink
+///
+/// Writes newline terminated records to a Unix FIFO (e.g.
+/// `/run/mozlog.fifo`) or Windows named pipe (e.g. `\\.\pipe\mozlog`) for
+/// a sidecar to read. When the reader goes away (`EPIPE`) the pipe is
+/// reopened with exponential backoff; meanwhile complete records are
+/// buffered up to a byte limit, dropping the oldest first.
+pub struct PipeWriter {
+    inner: Reconnecting<PipeConnector>,
+}
+
+impl PipeWriter {
+    /// New `PipeWriter` for the pipe at `path`
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        PipeWriter {
+            inner: Reconnecting::new(PipeConnector { path: path.into() }),
+        }
+    }
+
+    /// Set the maximum number of bytes buffered while disconnected
+    pub fn set_buffer_limit(mut self, bytes: usize) -> Self {
+        self.inner.set_buffer_limit(bytes);
+        self
+    }
+
+    /// Set the initial and maximum delay between reopen attempts
+    pub fn set_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.inner.set_backoff(initial, max);
+        self
+    }
+
+    /// Number of records dropped because the buffer limit was reached
+    pub fn dropped(&self) -> u64 {
+        self.inner.dropped()
+    }
+}
+
+impl io::Write for PipeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
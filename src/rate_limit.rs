@@ -0,0 +1,148 @@
+use std::{sync::{Mutex, PoisonError}, time::{Duration, Instant}};
+
+use slog::Level;
+
+struct Bucket {
+    per_sec: f64,
+    burst: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl Bucket {
+    fn new(per_sec: u32, burst: u32) -> Self {
+        let burst = f64::from(burst.max(1));
+        Bucket {
+            per_sec: f64::from(per_sec),
+            burst,
+            tokens: burst,
+            last: Instant::now(),
+        }
+    }
+
+    /// Whether a token is available, after refilling
+    fn ready(&mut self, now: Instant) -> bool {
+        let elapsed = now.duration_since(self.last);
+        let elapsed = elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) / 1e9;
+        self.tokens = (self.tokens + elapsed * self.per_sec).min(self.burst);
+        self.last = now;
+        self.tokens >= 1.0
+    }
+}
+
+struct State {
+    global: Option<Bucket>,
+    levels: Vec<(Level, Bucket)>,
+    /// Records dropped since the last report
+    dropped: u64,
+    window_start: Instant,
+}
+
+/// Global and per-level token bucket rate limiting
+pub(crate) struct RateLimiter {
+    state: Mutex<State>,
+    report_interval: Duration,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(report_interval: Duration) -> Self {
+        RateLimiter {
+            state: Mutex::new(State {
+                global: None,
+                levels: vec![],
+                dropped: 0,
+                window_start: Instant::now(),
+            }),
+            report_interval,
+        }
+    }
+
+    /// Limit all records to `per_sec`, in bursts of up to `burst`
+    pub(crate) fn set_global(&mut self, per_sec: u32, burst: u32) {
+        self.state.get_mut().unwrap().global = Some(Bucket::new(per_sec, burst));
+    }
+
+    /// Limit records at `level` to `per_sec`, in bursts of up to `burst`
+    pub(crate) fn set_level(&mut self, level: Level, per_sec: u32, burst: u32) {
+        let levels = &mut self.state.get_mut().unwrap().levels;
+        levels.retain(|&(l, _)| l != level);
+        levels.push((level, Bucket::new(per_sec, burst)));
+    }
+
+    pub(crate) fn set_report_interval(&mut self, interval: Duration) {
+        self.report_interval = interval;
+    }
+
+    /// Whether a record at `level` may be written, and once the report
+    /// interval has passed, the number of records dropped over how long
+    pub(crate) fn admit(&self, level: Level) -> (bool, Option<(u64, Duration)>) {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let state = &mut *state;
+        let level_ready = state
+            .levels
+            .iter_mut()
+            .find(|&&mut (l, _)| l == level)
+            .is_none_or(|&mut (_, ref mut bucket)| bucket.ready(now));
+        let global_ready = state
+            .global
+            .as_mut()
+            .is_none_or(|bucket| bucket.ready(now));
+        let admitted = level_ready && global_ready;
+        if admitted {
+            // Only spend tokens once both buckets have one
+            for &mut (l, ref mut bucket) in &mut state.levels {
+                if l == level {
+                    bucket.tokens -= 1.0;
+                }
+            }
+            if let Some(ref mut bucket) = state.global {
+                bucket.tokens -= 1.0;
+            }
+        } else {
+            state.dropped += 1;
+        }
+
+        let mut report = None;
+        if now.duration_since(state.window_start) >= self.report_interval {
+            if state.dropped > 0 {
+                report = Some((state.dropped, now.duration_since(state.window_start)));
+            }
+            state.dropped = 0;
+            state.window_start = now;
+        }
+        (admitted, report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use slog::Level;
+
+    use super::RateLimiter;
+
+    #[test]
+    fn drops_past_burst_and_reports() {
+        // No refill, so only the burst gets through
+        let mut limiter = RateLimiter::new(Duration::from_secs(3600));
+        limiter.set_global(0, 2);
+        let admitted: Vec<_> = (0..3).map(|_| limiter.admit(Level::Info).0).collect();
+        assert_eq!(admitted, [true, true, false]);
+
+        limiter.set_report_interval(Duration::from_secs(0));
+        let (admitted, report) = limiter.admit(Level::Info);
+        assert!(!admitted);
+        assert_eq!(report.map(|(dropped, _)| dropped), Some(2));
+    }
+
+    #[test]
+    fn limits_levels_separately() {
+        let mut limiter = RateLimiter::new(Duration::from_secs(3600));
+        limiter.set_level(Level::Debug, 0, 1);
+        assert!(limiter.admit(Level::Debug).0);
+        assert!(!limiter.admit(Level::Debug).0);
+        assert!(limiter.admit(Level::Info).0);
+    }
+}
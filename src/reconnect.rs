@@ -0,0 +1,209 @@
+use std::{io, io::Write, time::Duration};
+
+use util::{Backoff, LineBuffer, RecordQueue};
+
+/// Opens a fresh connection for `Reconnecting`
+pub(crate) trait Connect {
+    type Stream: io::Write;
+
+    fn connect(&self) -> io::Result<Self::Stream>;
+}
+
+/// Whether a write failed because of the connection, so may succeed on a
+/// new one, rather than because of the record itself (e.g. `EMSGSIZE` for
+/// an oversized datagram)
+pub(crate) fn is_connection_error(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::BrokenPipe
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::NotConnected
+            | io::ErrorKind::NotFound
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::UnexpectedEof
+            | io::ErrorKind::WouldBlock
+            | io::ErrorKind::WriteZero
+    )
+}
+
+/// Newline framed `io::Write` over a connection that is re-established on
+/// failure
+///
+/// The connection is made lazily and retried with exponential backoff;
+/// while disconnected, complete records are buffered up to a byte limit,
+/// dropping the oldest first. A record failing for any other reason than
+/// the connection is dropped, so it can't hold up the ones behind it.
+pub(crate) struct Reconnecting<C: Connect> {
+    connector: C,
+    stream: Option<C::Stream>,
+    lines: LineBuffer,
+    queue: RecordQueue,
+    backoff: Backoff,
+}
+
+impl<C: Connect> Reconnecting<C> {
+    pub(crate) fn new(connector: C) -> Self {
+        Reconnecting {
+            connector,
+            stream: None,
+            lines: LineBuffer::new(),
+            queue: RecordQueue::new(1024 * 1024),
+            backoff: Backoff::new(Duration::from_millis(100), Duration::from_secs(30)),
+        }
+    }
+
+    pub(crate) fn connector_mut(&mut self) -> &mut C {
+        &mut self.connector
+    }
+
+    pub(crate) fn set_buffer_limit(&mut self, bytes: usize) {
+        self.queue.set_limit(bytes);
+    }
+
+    pub(crate) fn set_backoff(&mut self, initial: Duration, max: Duration) {
+        self.backoff = Backoff::new(initial, max);
+    }
+
+    pub(crate) fn dropped(&self) -> u64 {
+        self.queue.dropped()
+    }
+
+    /// Send as many buffered records as possible
+    fn send_queued(&mut self) {
+        while let Some(record) = self.queue.front().cloned() {
+            if self.stream.is_none() {
+                if !self.backoff.ready() {
+                    return;
+                }
+                match self.connector.connect() {
+                    Ok(stream) => {
+                        self.backoff.reset();
+                        self.stream = Some(stream);
+                    }
+                    Err(_) => {
+                        self.backoff.fail();
+                        return;
+                    }
+                }
+            }
+            match self.stream.as_mut().unwrap().write_all(&record) {
+                Ok(()) => {
+                    self.queue.pop_front();
+                }
+                Err(ref e) if is_connection_error(e) => {
+                    self.stream = None;
+                    self.backoff.fail();
+                    return;
+                }
+                Err(_) => self.queue.reject_front(),
+            }
+        }
+    }
+}
+
+impl<C: Connect> io::Write for Reconnecting<C> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        {
+            let queue = &mut self.queue;
+            self.lines.push(buf, |record| queue.push(record));
+        }
+        self.send_queued();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.send_queued();
+        if !self.queue.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "records buffered while disconnected",
+            ));
+        }
+        match self.stream {
+            Some(ref mut stream) => stream.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io, io::Write, cell::RefCell, rc::Rc, time::Duration};
+
+    use super::{Connect, Reconnecting};
+
+    type Sent = Rc<RefCell<Vec<Vec<u8>>>>;
+
+    /// Stream failing writes of records starting with `!` with the error
+    /// kind `fail`
+    struct Stream {
+        sent: Sent,
+        fail: io::ErrorKind,
+    }
+
+    impl io::Write for Stream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if buf.starts_with(b"!") {
+                return Err(io::Error::new(self.fail, "rejected"));
+            }
+            self.sent.borrow_mut().push(buf.to_vec());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    struct Connector {
+        sent: Sent,
+        connects: Rc<RefCell<usize>>,
+        fail: io::ErrorKind,
+    }
+
+    impl Connect for Connector {
+        type Stream = Stream;
+
+        fn connect(&self) -> io::Result<Stream> {
+            *self.connects.borrow_mut() += 1;
+            Ok(Stream {
+                sent: self.sent.clone(),
+                fail: self.fail,
+            })
+        }
+    }
+
+    fn reconnecting(fail: io::ErrorKind) -> (Reconnecting<Connector>, Sent, Rc<RefCell<usize>>) {
+        let sent = Rc::new(RefCell::new(vec![]));
+        let connects = Rc::new(RefCell::new(0));
+        let mut writer = Reconnecting::new(Connector {
+            sent: sent.clone(),
+            connects: connects.clone(),
+            fail,
+        });
+        writer.set_backoff(Duration::from_millis(0), Duration::from_millis(0));
+        (writer, sent, connects)
+    }
+
+    #[test]
+    fn drops_rejected_record() {
+        let (mut writer, sent, connects) = reconnecting(io::ErrorKind::Other);
+        writer.write_all(b"a\n!too big\nb\n").unwrap();
+        assert_eq!(*sent.borrow(), vec![b"a\n".to_vec(), b"b\n".to_vec()]);
+        assert_eq!(writer.dropped(), 1);
+        assert_eq!(*connects.borrow(), 1);
+        writer.flush().unwrap();
+    }
+
+    #[test]
+    fn retries_after_connection_error() {
+        let (mut writer, sent, connects) = reconnecting(io::ErrorKind::BrokenPipe);
+        writer.write_all(b"a\n!b\n").unwrap();
+        assert_eq!(*sent.borrow(), vec![b"a\n".to_vec()]);
+        assert_eq!(writer.dropped(), 0);
+        assert!(writer.flush().is_err());
+        assert!(*connects.borrow() > 1);
+    }
+}
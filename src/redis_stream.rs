@@ -0,0 +1,146 @@
+use std::{io, time::Duration};
+
+use redis;
+
+use reconnect::{Connect, Reconnecting};
+
+struct RedisConnector {
+    client: redis::Client,
+    stream: String,
+    field: String,
+    max_len: usize,
+    timeout: Duration,
+}
+
+impl Connect for RedisConnector {
+    type Stream = RedisStream;
+
+    fn connect(&self) -> io::Result<RedisStream> {
+        let conn = self.client
+            .get_connection_with_timeout(self.timeout)
+            .map_err(|e| connection_error(&e))?;
+        conn.set_read_timeout(Some(self.timeout)).map_err(|e| connection_error(&e))?;
+        conn.set_write_timeout(Some(self.timeout)).map_err(|e| connection_error(&e))?;
+        Ok(RedisStream {
+            conn,
+            stream: self.stream.clone(),
+            field: self.field.clone(),
+            max_len: self.max_len,
+        })
+    }
+}
+
+fn connection_error(e: &redis::RedisError) -> io::Error {
+    io::Error::new(io::ErrorKind::NotConnected, e.to_string())
+}
+
+/// Connection `XADD`ing each record written to it
+struct RedisStream {
+    conn: redis::Connection,
+    stream: String,
+    field: String,
+    max_len: usize,
+}
+
+impl io::Write for RedisStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let record = buf.strip_suffix(b"\n").unwrap_or(buf);
+        let res: redis::RedisResult<String> = redis::cmd("XADD")
+            .arg(&self.stream)
+            .arg("MAXLEN")
+            .arg("~")
+            .arg(self.max_len)
+            .arg("*")
+            .arg(&self.field)
+            .arg(record)
+            .query(&mut self.conn);
+        match res {
+            Ok(_) => Ok(buf.len()),
+            Err(ref e) if e.is_io_error() || e.is_connection_dropped() || e.is_timeout() => {
+                Err(connection_error(e))
+            }
+            // Rejected by the server, e.g. `WRONGTYPE` or `OOM`
+            Err(e) => Err(io::Error::new(io::ErrorKind::InvalidData, e.to_string())),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Redis Streams `io::Write` sink
+///
+/// Each newline terminated record is `XADD`ed, without its newline, to a
+/// stream capped at an approximate maximum length. The connection is
+/// re-established with exponential backoff after a failure; while
+/// disconnected, records are buffered up to a byte limit, dropping the
+/// oldest first. Records the server rejects are dropped.
+pub struct RedisStreamWriter {
+    inner: Reconnecting<RedisConnector>,
+}
+
+impl RedisStreamWriter {
+    /// New `RedisStreamWriter` adding to `stream` on the server at `url`
+    /// (e.g. `redis://127.0.0.1/`)
+    pub fn new(url: &str, stream: String) -> io::Result<Self> {
+        let client = redis::Client::open(url)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+        Ok(RedisStreamWriter {
+            inner: Reconnecting::new(RedisConnector {
+                client,
+                stream,
+                field: "record".to_owned(),
+                max_len: 100_000,
+                timeout: Duration::from_secs(5),
+            }),
+        })
+    }
+
+    /// Set the approximate maximum stream length (`XADD MAXLEN ~`)
+    pub fn set_max_len(mut self, max_len: usize) -> Self {
+        self.inner.connector_mut().max_len = max_len;
+        self
+    }
+
+    /// Set the name of the entry field holding the record (default
+    /// `record`)
+    pub fn set_field(mut self, field: String) -> Self {
+        self.inner.connector_mut().field = field;
+        self
+    }
+
+    /// Set the maximum number of bytes buffered while disconnected
+    pub fn set_buffer_limit(mut self, bytes: usize) -> Self {
+        self.inner.set_buffer_limit(bytes);
+        self
+    }
+
+    /// Set the initial and maximum delay between reconnection attempts
+    pub fn set_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.inner.set_backoff(initial, max);
+        self
+    }
+
+    /// Set the timeout for connecting and for each `XADD` (default 5s)
+    pub fn set_timeout(mut self, timeout: Duration) -> Self {
+        self.inner.connector_mut().timeout = timeout;
+        self
+    }
+
+    /// Number of records dropped because the buffer limit was reached or
+    /// the server rejected them
+    pub fn dropped(&self) -> u64 {
+        self.inner.dropped()
+    }
+}
+
+impl io::Write for RedisStreamWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
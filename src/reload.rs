@@ -0,0 +1,126 @@
+use std::{fs, io, thread, path::PathBuf, sync::{Arc, PoisonError, RwLock}, time::Duration};
+
+#[cfg(feature = "redact")]
+use regex::Regex;
+
+use config::MozLogConfig;
+#[cfg(feature = "redact")]
+use config::pii;
+#[cfg(feature = "redact")]
+use env::invalid;
+use filter::{parse_directives, LevelHandle};
+use sample::Sampler;
+use scrub::FieldRules;
+
+/// Handle applying changed settings to a `MozLogJson` drain at runtime,
+/// e.g. to raise verbosity from config management without a restart
+///
+/// Create with `MozLogJson::reload_handle`. Only the minimum level and
+/// directives, sampling and redaction can change; other settings need a
+/// new drain.
+#[derive(Clone)]
+pub struct ReloadHandle {
+    level: LevelHandle,
+    sampler: Arc<RwLock<Option<Sampler>>>,
+    field_rules: Arc<RwLock<FieldRules>>,
+}
+
+impl ReloadHandle {
+    pub(crate) fn new(
+        level: LevelHandle,
+        sampler: Arc<RwLock<Option<Sampler>>>,
+        field_rules: Arc<RwLock<FieldRules>>,
+    ) -> Self {
+        ReloadHandle {
+            level,
+            sampler,
+            field_rules,
+        }
+    }
+
+    /// Apply `cfg`'s `level`, `sampling`, `mask_secrets`, `secret_keys`,
+    /// `max_value_len`, and (if non-empty) `redactions` and `pii_masking`
+    ///
+    /// Settings absent from `cfg` are left as they are, and other settings
+    /// are ignored. Nothing is applied if any setting is invalid.
+    pub fn reload(&self, cfg: &MozLogConfig) -> io::Result<()> {
+        if let Some(ref spec) = cfg.level {
+            parse_directives(spec)?;
+        }
+        #[cfg(feature = "redact")]
+        let redactions = if cfg.redactions.is_empty() && cfg.pii_masking.is_empty() {
+            None
+        } else {
+            let mut redactions = cfg.redactions
+                .iter()
+                .map(|pattern| Regex::new(pattern).map_err(|_| invalid("redactions", pattern)))
+                .collect::<io::Result<Vec<_>>>()?;
+            for kind in &cfg.pii_masking {
+                redactions.push(pii(kind)?.pattern());
+            }
+            Some(redactions)
+        };
+
+        if let Some(ref spec) = cfg.level {
+            self.level.set_directives(spec)?;
+        }
+        if let Some(ref sampling) = cfg.sampling {
+            *self.sampler.write().unwrap_or_else(PoisonError::into_inner) =
+                Some(Sampler::new(sampling.key.clone(), sampling.rate));
+        }
+        let mut rules = self.field_rules.write().unwrap_or_else(PoisonError::into_inner);
+        if let Some(mask_secrets) = cfg.mask_secrets {
+            rules.mask_secrets = mask_secrets;
+        }
+        if let Some(ref keys) = cfg.secret_keys {
+            rules.secret_keys = keys.iter().map(|key| key.to_lowercase()).collect();
+        }
+        if let Some(len) = cfg.max_value_len {
+            rules.max_value_len = Some(len);
+        }
+        #[cfg(feature = "redact")]
+        {
+            if let Some(redactions) = redactions {
+                rules.redactions = redactions;
+            }
+        }
+        Ok(())
+    }
+
+    /// Start a background thread checking `path` every `interval`, and
+    /// whenever it's modified, `reload`ing the config `parse` reads from
+    /// its contents (e.g. with `toml::from_str`)
+    ///
+    /// A config that can't be read, parsed or applied is skipped, keeping
+    /// the current settings until the file changes again. Stops once the
+    /// drain and its other `ReloadHandle`s are dropped.
+    pub fn watch<F>(&self, path: PathBuf, interval: Duration, parse: F) -> io::Result<()>
+    where
+        F: Fn(&str) -> io::Result<MozLogConfig> + Send + 'static,
+    {
+        let level = self.level.clone();
+        let sampler = Arc::downgrade(&self.sampler);
+        let field_rules = Arc::downgrade(&self.field_rules);
+        let mut modified = fs::metadata(&path).and_then(|meta| meta.modified()).ok();
+        thread::Builder::new()
+            .name("mozlog-reload".to_owned())
+            .spawn(move || loop {
+                thread::sleep(interval);
+                let handle = match (sampler.upgrade(), field_rules.upgrade()) {
+                    (Some(sampler), Some(field_rules)) => {
+                        ReloadHandle::new(level.clone(), sampler, field_rules)
+                    }
+                    _ => return,
+                };
+                let current = fs::metadata(&path).and_then(|meta| meta.modified()).ok();
+                if current.is_none() || current == modified {
+                    continue;
+                }
+                modified = current;
+                let _ = fs::read_to_string(&path)
+                    .and_then(|contents| parse(&contents))
+                    .and_then(|cfg| handle.reload(&cfg));
+            })?;
+        Ok(())
+    }
+}
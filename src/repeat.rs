@@ -0,0 +1,99 @@
+use std::{collections::hash_map::DefaultHasher, hash::{Hash, Hasher}, sync::{Mutex, PoisonError},
+          time::{Duration, Instant}};
+
+use slog::{Level, OwnedKVList, Record};
+
+use sample::find_key;
+
+/// A collapsed run of repeats of one record
+pub(crate) struct Repeated {
+    pub(crate) level: Level,
+    pub(crate) msg: String,
+    pub(crate) count: u64,
+}
+
+struct State {
+    /// Identity of the last record written
+    last: Option<u64>,
+    level: Level,
+    msg: String,
+    since: Instant,
+    /// Repeats of it suppressed since
+    count: u64,
+}
+
+impl State {
+    fn take(&mut self) -> Option<Repeated> {
+        if self.count == 0 {
+            return None;
+        }
+        let count = self.count;
+        self.count = 0;
+        Some(Repeated {
+            level: self.level,
+            msg: self.msg.clone(),
+            count,
+        })
+    }
+}
+
+/// Collapses repeats of a record, syslog style
+pub(crate) struct RepeatSuppressor {
+    window: Duration,
+    keys: Vec<String>,
+    state: Mutex<State>,
+}
+
+impl RepeatSuppressor {
+    pub(crate) fn new(window: Duration, keys: Vec<String>) -> Self {
+        RepeatSuppressor {
+            window,
+            keys,
+            state: Mutex::new(State {
+                last: None,
+                level: Level::Info,
+                msg: String::new(),
+                since: Instant::now(),
+                count: 0,
+            }),
+        }
+    }
+
+    /// Whether `rinfo` repeats the last record within the window, so is
+    /// suppressed; otherwise the run of repeats it ends, if any
+    pub(crate) fn check(
+        &self,
+        rinfo: &Record,
+        logger_values: &OwnedKVList,
+    ) -> (bool, Option<Repeated>) {
+        let msg = format!("{}", rinfo.msg());
+        let mut hasher = DefaultHasher::new();
+        rinfo.level().as_usize().hash(&mut hasher);
+        msg.hash(&mut hasher);
+        for key in &self.keys {
+            find_key(key, rinfo, logger_values).hash(&mut hasher);
+        }
+        let id = hasher.finish();
+
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        if state.last == Some(id) && now.duration_since(state.since) < self.window {
+            state.count += 1;
+            return (true, None);
+        }
+        let repeated = state.take();
+        state.last = Some(id);
+        state.level = rinfo.level();
+        state.msg = msg;
+        state.since = now;
+        (false, repeated)
+    }
+
+    /// The run of repeats not yet reported, e.g. on drop
+    pub(crate) fn pending(&self) -> Option<Repeated> {
+        self.state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+    }
+}
@@ -0,0 +1,101 @@
+use std::{io, thread, sync::Arc, sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+          time::Duration};
+
+use crossbeam_queue::ArrayQueue;
+use slog::{self, Level, OwnedKVList, Record};
+
+use drain::MozLogJson;
+
+struct Shared<W: io::Write> {
+    drain: MozLogJson<W>,
+    queue: ArrayQueue<(Level, Option<usize>, Vec<u8>)>,
+    closed: AtomicBool,
+}
+
+fn run<W: io::Write>(shared: &Shared<W>) {
+    loop {
+        let closed = shared.closed.load(Ordering::Acquire);
+        let mut wrote = false;
+        while let Some((level, route, mut record)) = shared.queue.pop() {
+            let _ = shared.drain.write_record(level, route, &mut record);
+            wrote = true;
+        }
+        if wrote {
+            let _ = shared.drain.flush();
+        }
+        if closed {
+            return;
+        }
+        thread::park_timeout(Duration::from_millis(100));
+    }
+}
+
+/// `Drain` handing records to a dedicated writer thread through a
+/// lock-free ring
+///
+/// Logging threads serialize records with the wrapped `MozLogJson` and
+/// push them into a bounded multi-producer ring, without taking any lock;
+/// a single consumer thread performs all writes. Records are dropped when
+/// the ring is full. Dropping the drain writes out what's left.
+pub struct RingDrain<W: io::Write + Send + 'static> {
+    shared: Arc<Shared<W>>,
+    consumer: Option<thread::JoinHandle<()>>,
+    dropped: AtomicUsize,
+}
+
+impl<W: io::Write + Send + 'static> RingDrain<W> {
+    /// New `RingDrain` writing through `drain`, with room for `capacity`
+    /// records (at least 1)
+    pub fn new(drain: MozLogJson<W>, capacity: usize) -> io::Result<Self> {
+        let shared = Arc::new(Shared {
+            drain,
+            queue: ArrayQueue::new(capacity.max(1)),
+            closed: AtomicBool::new(false),
+        });
+        let consumer = {
+            let shared = shared.clone();
+            thread::Builder::new()
+                .name("mozlog-ring".to_owned())
+                .spawn(move || run(&shared))?
+        };
+        Ok(RingDrain {
+            shared,
+            consumer: Some(consumer),
+            dropped: AtomicUsize::new(0),
+        })
+    }
+
+    /// Number of records dropped on a full ring
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl<W: io::Write + Send + 'static> slog::Drain for RingDrain<W> {
+    type Ok = ();
+    type Err = io::Error;
+
+    fn log(&self, rinfo: &Record, logger_values: &OwnedKVList) -> io::Result<()> {
+        self.shared.drain.with_record(rinfo, logger_values, |payload| {
+            let route = self.shared.drain.route(rinfo, logger_values);
+            let record = (rinfo.level(), route, payload.clone());
+            if self.shared.queue.push(record).is_err() {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                self.shared.drain.counters().dropped();
+            } else if let Some(ref consumer) = self.consumer {
+                consumer.thread().unpark();
+            }
+            Ok(())
+        })
+    }
+}
+
+impl<W: io::Write + Send + 'static> Drop for RingDrain<W> {
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::Release);
+        if let Some(consumer) = self.consumer.take() {
+            consumer.thread().unpark();
+            let _ = consumer.join();
+        }
+    }
+}
@@ -0,0 +1,450 @@
+use std::{fs, io, io::Write, thread, collections::HashSet, path::{Path, PathBuf},
+          sync::{Arc, Mutex, PoisonError}};
+
+use slog::Level;
+
+use clock::file_stamp;
+use context;
+
+/// Called with the path of each completed log segment
+///
+/// Hooks run on a background thread so they may block (e.g. to upload the
+/// segment).
+pub trait RotateHook: Send + Sync {
+    fn rotated(&self, path: &Path) -> io::Result<()>;
+}
+
+impl<F> RotateHook for F
+where
+    F: Fn(&Path) -> io::Result<()> + Send + Sync,
+{
+    fn rotated(&self, path: &Path) -> io::Result<()> {
+        self(path)
+    }
+}
+
+/// When `RotatingFileWriter` `fsync`s the file, for logs that must
+/// survive power loss
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyncPolicy {
+    /// Leave it to the OS
+    Never,
+    /// After each Critical record, as written by the drain (directly or
+    /// through a `BackgroundWriter`)
+    Critical,
+    /// After each record once at least this many bytes are unsynced
+    Bytes(u64),
+    /// After every record
+    Always,
+}
+
+/// Whether `stamp` is a segment's timestamp, e.g. `20240131T235959.123Z`
+fn is_stamp(stamp: &str) -> bool {
+    let bytes = stamp.as_bytes();
+    bytes.len() == 20
+        && bytes.iter().enumerate().all(|(i, &b)| match i {
+            8 => b == b'T',
+            15 => b == b'.',
+            19 => b == b'Z',
+            _ => b.is_ascii_digit(),
+        })
+}
+
+/// Chronological sort key of a segment's `suffix`: its timestamp and,
+/// for later segments completed within the same millisecond, a `-<n>`
+/// counter, e.g. `20240131T235959.123Z-1`
+fn segment_key(suffix: &str) -> Option<(&str, u32)> {
+    let (stamp, n) = match suffix.find('-') {
+        Some(pos) => {
+            let n = &suffix[pos + 1..];
+            if n.is_empty() || !n.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            (&suffix[..pos], n.parse().ok()?)
+        }
+        None => (suffix, 0),
+    };
+    if is_stamp(stamp) {
+        Some((stamp, n))
+    } else {
+        None
+    }
+}
+
+fn open_file(path: &Path) -> io::Result<fs::File> {
+    fs::OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// Size based rotating file `io::Write` sink
+///
+/// Records are appended to `path`. Once it grows past the maximum size, at
+/// a record boundary, the file is renamed to `path.<UTC timestamp>` (with a
+/// `-<n>` suffix if taken), a new one is started and the optional
+/// `RotateHook` is run on the completed segment. Only the newest
+/// `max_files` segments are kept, besides any the hook is still running
+/// on. Optionally the
+/// file is `fsync`ed at record boundaries according to a `SyncPolicy`.
+/// A failed sync or rotation doesn't fail the write: it's retried at the
+/// next record boundary, and the error returned by the next `flush`.
+pub struct RotatingFileWriter {
+    path: PathBuf,
+    file: fs::File,
+    size: u64,
+    max_bytes: u64,
+    max_files: usize,
+    hook: Option<Arc<dyn RotateHook>>,
+    sync_policy: SyncPolicy,
+    unsynced: u64,
+    critical: bool,
+    /// Sync or rotation failure not yet reported by `flush`
+    error: Option<io::Error>,
+    /// Segments the hook is running on, which aren't pruned
+    in_flight: Arc<Mutex<HashSet<PathBuf>>>,
+}
+
+impl RotatingFileWriter {
+    /// New `RotatingFileWriter` appending to `path`
+    pub fn new<P: Into<PathBuf>>(path: P) -> io::Result<Self> {
+        let path = path.into();
+        let file = open_file(&path)?;
+        Ok(RotatingFileWriter {
+            size: file.metadata()?.len(),
+            path,
+            file,
+            max_bytes: 100 * 1024 * 1024,
+            max_files: 10,
+            hook: None,
+            sync_policy: SyncPolicy::Never,
+            unsynced: 0,
+            critical: false,
+            error: None,
+            in_flight: Arc::new(Mutex::new(HashSet::new())),
+        })
+    }
+
+    /// Set the size in bytes past which the file is rotated
+    pub fn set_max_bytes(mut self, bytes: u64) -> Self {
+        self.max_bytes = bytes;
+        self
+    }
+
+    /// Set the number of completed segments kept on disk (0 for no limit)
+    pub fn set_max_files(mut self, files: usize) -> Self {
+        self.max_files = files;
+        self
+    }
+
+    /// Set a hook run on each completed segment
+    pub fn set_hook<H: RotateHook + 'static>(mut self, hook: H) -> Self {
+        self.hook = Some(Arc::new(hook));
+        self
+    }
+
+    /// Set when the file is `fsync`ed (default `Never`)
+    pub fn set_sync_policy(mut self, policy: SyncPolicy) -> Self {
+        self.sync_policy = policy;
+        self
+    }
+
+    /// Complete the current segment and start a new file
+    ///
+    /// On failure the current file is kept, under its own name.
+    pub fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.sync_policy != SyncPolicy::Never {
+            self.file.sync_all()?;
+            self.unsynced = 0;
+        }
+        let segment = self.segment_path();
+        fs::rename(&self.path, &segment)?;
+        match open_file(&self.path) {
+            Ok(file) => {
+                self.size = file.metadata().map(|meta| meta.len()).unwrap_or(0);
+                self.file = file;
+            }
+            Err(e) => {
+                let _ = fs::rename(&segment, &self.path);
+                return Err(e);
+            }
+        }
+        if let Some(ref hook) = self.hook {
+            self.in_flight
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .insert(segment.clone());
+            let hook = hook.clone();
+            let in_flight = self.in_flight.clone();
+            let spawned = thread::Builder::new()
+                .name("mozlog-rotate".to_owned())
+                .spawn({
+                    let segment = segment.clone();
+                    move || {
+                        let _ = hook.rotated(&segment);
+                        in_flight.lock().unwrap_or_else(PoisonError::into_inner).remove(&segment);
+                    }
+                });
+            if let Err(e) = spawned {
+                self.in_flight.lock().unwrap_or_else(PoisonError::into_inner).remove(&segment);
+                return Err(e);
+            }
+        }
+        self.prune()
+    }
+
+    /// Name for the next segment, unique even when rotating more than once
+    /// a millisecond
+    fn segment_path(&self) -> PathBuf {
+        let stamp = file_stamp();
+        let mut n = 0;
+        loop {
+            let mut segment = self.path.clone().into_os_string();
+            if n == 0 {
+                segment.push(format!(".{}", stamp));
+            } else {
+                segment.push(format!(".{}-{}", stamp, n));
+            }
+            let segment = PathBuf::from(segment);
+            if fs::symlink_metadata(&segment).is_err() {
+                return segment;
+            }
+            n += 1;
+        }
+    }
+
+    /// Remove the oldest segments beyond `max_files`, other than those the
+    /// hook is still running on
+    fn prune(&self) -> io::Result<()> {
+        if self.max_files == 0 {
+            return Ok(());
+        }
+        let dir = match self.path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_owned(),
+            _ => PathBuf::from("."),
+        };
+        let prefix = match self.path.file_name() {
+            Some(name) => format!("{}.", name.to_string_lossy()),
+            None => return Ok(()),
+        };
+        let mut segments: Vec<((String, u32), PathBuf)> = fs::read_dir(&dir)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter_map(|path| {
+                let key = {
+                    let name = path.file_name()?.to_string_lossy();
+                    if !name.starts_with(&prefix) {
+                        return None;
+                    }
+                    let (stamp, n) = segment_key(&name[prefix.len()..])?;
+                    (stamp.to_owned(), n)
+                };
+                Some((key, path))
+            })
+            .collect();
+        // Timestamps sort chronologically
+        segments.sort();
+        let excess = segments.len().saturating_sub(self.max_files);
+        let in_flight = self.in_flight.lock().unwrap_or_else(PoisonError::into_inner);
+        for (_, path) in &segments[..excess] {
+            if !in_flight.contains(path) {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Sync and rotate as due once a record is complete
+    fn end_record(&mut self) -> io::Result<()> {
+        let sync = match self.sync_policy {
+            SyncPolicy::Never => false,
+            SyncPolicy::Critical => self.critical,
+            SyncPolicy::Bytes(bytes) => self.unsynced >= bytes,
+            SyncPolicy::Always => true,
+        };
+        if sync {
+            self.file.sync_data()?;
+            self.unsynced = 0;
+        }
+        self.critical = false;
+        if self.size >= self.max_bytes {
+            self.rotate()?;
+        }
+        Ok(())
+    }
+}
+
+impl io::Write for RotatingFileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.file.write(buf)?;
+        let chunk = &buf[..written];
+        self.size += written as u64;
+        self.unsynced += written as u64;
+        if self.sync_policy == SyncPolicy::Critical && !self.critical {
+            self.critical = context::record_level() == Some(Level::Critical);
+        }
+        // The bytes are written either way, so a failed sync or rotation
+        // is retried at the next record boundary and reported by `flush`
+        if chunk.ends_with(b"\n") {
+            if let Err(e) = self.end_record() {
+                self.error = Some(e);
+            }
+        }
+        Ok(written)
+    }
+
+    /// Flush the file, or fail with the last sync or rotation error since
+    /// the previous `flush`
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process, thread, io::Write, path::{Path, PathBuf},
+              sync::{mpsc, Mutex}, time::Duration};
+
+    use slog::Level;
+
+    use context;
+    use super::{RotatingFileWriter, SyncPolicy};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("mozlog-rotate-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn names(dir: &PathBuf) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn rotates_at_record_boundary() {
+        let dir = temp_dir("boundary");
+        let mut writer = RotatingFileWriter::new(dir.join("app.log")).unwrap().set_max_bytes(10);
+        writer.write_all(b"0123456789").unwrap();
+        assert_eq!(names(&dir), vec!["app.log"]);
+        writer.write_all(b"\n").unwrap();
+        let names = names(&dir);
+        assert_eq!(names.len(), 2);
+        assert!(names[1].starts_with("app.log."));
+        assert_eq!(fs::read(dir.join(&names[1])).unwrap(), b"0123456789\n");
+        assert_eq!(fs::read(dir.join("app.log")).unwrap(), b"");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn prunes_only_segments() {
+        let dir = temp_dir("prune");
+        for name in &["app.log.20000101T000000.000Z", "app.log.bak", "app.log.1"] {
+            fs::write(dir.join(name), b"").unwrap();
+        }
+        let mut writer = RotatingFileWriter::new(dir.join("app.log"))
+            .unwrap()
+            .set_max_bytes(1)
+            .set_max_files(1);
+        writer.write_all(b"a\n").unwrap();
+        let names = names(&dir);
+        assert_eq!(names.len(), 4);
+        assert!(names.contains(&"app.log.bak".to_owned()));
+        assert!(names.contains(&"app.log.1".to_owned()));
+        assert!(!names.contains(&"app.log.20000101T000000.000Z".to_owned()));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn names_segments_uniquely() {
+        let dir = temp_dir("unique");
+        let mut writer = RotatingFileWriter::new(dir.join("app.log"))
+            .unwrap()
+            .set_max_bytes(1)
+            .set_max_files(0);
+        // Many of these land within the same millisecond
+        for _ in 0..20 {
+            writer.write_all(b"a\n").unwrap();
+        }
+        assert_eq!(names(&dir).len(), 21);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn keeps_segments_being_uploaded() {
+        let dir = temp_dir("in-flight");
+        let (release, released) = mpsc::channel::<()>();
+        let released = Mutex::new(released);
+        let mut writer = RotatingFileWriter::new(dir.join("app.log"))
+            .unwrap()
+            .set_max_bytes(1)
+            .set_max_files(1)
+            .set_hook(move |_: &Path| {
+                let _ = released.lock().unwrap().recv();
+                Ok(())
+            });
+        writer.write_all(b"a\n").unwrap();
+        writer.write_all(b"b\n").unwrap();
+        assert_eq!(names(&dir).len(), 3);
+        release.send(()).unwrap();
+        release.send(()).unwrap();
+        while !writer.in_flight.lock().unwrap().is_empty() {
+            thread::sleep(Duration::from_millis(1));
+        }
+        writer.write_all(b"c\n").unwrap();
+        assert_eq!(names(&dir).len(), 2);
+        release.send(()).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn keeps_writing_after_failed_rotation() {
+        let dir = temp_dir("failed");
+        let mut writer = RotatingFileWriter::new(dir.join("app.log")).unwrap().set_max_bytes(1);
+        // The open file outlives its directory, but can't be renamed
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(writer.write(b"a\n").unwrap(), 2);
+        writer.write_all(b"b").unwrap();
+        assert!(writer.flush().is_err());
+        // Reported once
+        writer.flush().unwrap();
+        assert!(writer.rotate().is_err());
+    }
+
+    #[test]
+    fn syncs_after_failed_rotation() {
+        let dir = temp_dir("sync");
+        let mut writer = RotatingFileWriter::new(dir.join("app.log"))
+            .unwrap()
+            .set_max_bytes(1)
+            .set_sync_policy(SyncPolicy::Always);
+        fs::remove_dir_all(&dir).unwrap();
+        writer.write_all(b"a\n").unwrap();
+        // Synced on the old handle rather than panicking
+        writer.write_all(b"b\n").unwrap();
+        writer.write_all(b"c").unwrap();
+        assert!(writer.flush().is_err());
+    }
+
+    #[test]
+    fn syncs_critical_records_by_level() {
+        let dir = temp_dir("critical");
+        let mut writer = RotatingFileWriter::new(dir.join("app.log"))
+            .unwrap()
+            .set_sync_policy(SyncPolicy::Critical);
+        // Only the drain knows a record's level
+        writer.write_all(b"{\"msg\":\"\\\"Severity\\\":0\"}\n").unwrap();
+        assert!(writer.unsynced > 0);
+        context::with_record_level(Level::Error, || writer.write_all(b"{}\n")).unwrap();
+        assert!(writer.unsynced > 0);
+        context::with_record_level(Level::Critical, || writer.write_all(b"{}\n")).unwrap();
+        assert_eq!(writer.unsynced, 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
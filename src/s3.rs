@@ -0,0 +1,105 @@
+use std::{fs, io, path::Path, time::Duration};
+
+use ureq;
+
+use aws::{self, CredentialsProvider};
+use rotate::RotateHook;
+
+/// Percent-encode an S3 object key for the request path
+fn encode_key(key: &str) -> String {
+    key.bytes()
+        .map(|b| {
+            if b.is_ascii_alphanumeric() || b"-_.~/".contains(&b) {
+                (b as char).to_string()
+            } else {
+                format!("%{:02X}", b)
+            }
+        })
+        .collect()
+}
+
+/// `RotateHook` uploading completed segments to Amazon S3
+///
+/// Each segment is stored as `<prefix><file name>` and, by default,
+/// removed locally once uploaded.
+pub struct S3Upload {
+    agent: ureq::Agent,
+    region: String,
+    bucket: String,
+    prefix: String,
+    credentials: CredentialsProvider,
+    delete: bool,
+}
+
+impl S3Upload {
+    /// New `S3Upload` to `bucket` in `region`, using the default
+    /// credentials chain (environment, ECS container credentials, then EC2
+    /// instance role)
+    pub fn new(region: String, bucket: String) -> Self {
+        S3Upload {
+            agent: ureq::AgentBuilder::new().timeout(Duration::from_secs(300)).build(),
+            region,
+            bucket,
+            prefix: String::new(),
+            credentials: CredentialsProvider::default_chain(),
+            delete: true,
+        }
+    }
+
+    /// Set the object key prefix (e.g. `logs/myservice/`)
+    pub fn set_prefix(mut self, prefix: String) -> Self {
+        self.prefix = prefix;
+        self
+    }
+
+    /// Use fixed credentials rather than the default chain
+    pub fn set_credentials(
+        mut self,
+        access_key_id: String,
+        secret_access_key: String,
+        session_token: Option<String>,
+    ) -> Self {
+        self.credentials =
+            CredentialsProvider::fixed(access_key_id, secret_access_key, session_token);
+        self
+    }
+
+    /// Set whether segments are deleted locally after upload
+    pub fn set_delete_after_upload(mut self, enabled: bool) -> Self {
+        self.delete = enabled;
+        self
+    }
+}
+
+impl RotateHook for S3Upload {
+    fn rotated(&self, path: &Path) -> io::Result<()> {
+        let name = path.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let key = encode_key(&format!("{}{}", self.prefix, name));
+        let body = fs::read(path)?;
+        let host = format!("{}.s3.{}.amazonaws.com", self.bucket, self.region);
+        let path_part = format!("/{}", key.trim_start_matches('/'));
+        let creds = self.credentials.get()?;
+        let signed = aws::sign(
+            &creds,
+            &self.region,
+            "s3",
+            "PUT",
+            &path_part,
+            &[("host", &host)],
+            &body,
+        );
+        let mut request = self.agent.put(&format!("https://{}{}", host, path_part));
+        for (name, value) in &signed {
+            request = request.set(name, value);
+        }
+        request
+            .send_bytes(&body)
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        if self.delete {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
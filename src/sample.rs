@@ -0,0 +1,70 @@
+use std::fmt;
+
+use slog::{self, Key, OwnedKVList, Record, KV};
+
+/// Finds the value of one key, formatted
+struct FindKey<'a> {
+    key: &'a str,
+    value: Option<String>,
+}
+
+impl<'a> slog::Serializer for FindKey<'a> {
+    fn emit_arguments(&mut self, key: Key, val: &fmt::Arguments) -> slog::Result {
+        if self.value.is_none() && key == self.key {
+            self.value = Some(fmt::format(*val));
+        }
+        Ok(())
+    }
+}
+
+/// The formatted value of `key` in a record, or failing that its logger
+pub(crate) fn find_key(key: &str, rinfo: &Record, logger_values: &OwnedKVList) -> Option<String> {
+    let mut find = FindKey {
+        key,
+        value: None,
+    };
+    let _ = rinfo.kv().serialize(rinfo, &mut find);
+    if find.value.is_none() {
+        let _ = logger_values.serialize(rinfo, &mut find);
+    }
+    find.value
+}
+
+/// 64-bit FNV-1a: stable across processes and releases, unlike std's hashers
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ u64::from(*b)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// Consistent sampling on the value of a key, so either all or none of an
+/// entity's records are kept
+pub(crate) struct Sampler {
+    key: String,
+    /// Records whose value hashes below this are kept
+    threshold: u64,
+}
+
+impl Sampler {
+    pub(crate) fn new(key: String, rate: f64) -> Self {
+        let rate = rate.clamp(0.0, 1.0);
+        Sampler {
+            key,
+            threshold: if rate >= 1.0 {
+                u64::MAX
+            } else {
+                (rate * u64::MAX as f64) as u64
+            },
+        }
+    }
+
+    /// Whether `rinfo` is kept: records without the key always are
+    pub(crate) fn keep(&self, rinfo: &Record, logger_values: &OwnedKVList) -> bool {
+        match find_key(&self.key, rinfo, logger_values) {
+            Some(value) => {
+                self.threshold == u64::MAX || fnv1a(value.as_bytes()) < self.threshold
+            }
+            None => true,
+        }
+    }
+}
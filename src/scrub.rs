@@ -0,0 +1,373 @@
+use std::fmt;
+use std::borrow::Cow;
+use std::collections::HashSet;
+
+#[cfg(feature = "redact")]
+use regex::Regex;
+#[cfg(feature = "pseudonymize")]
+use sha2::{Digest, Sha256};
+use slog::{self, Key};
+
+use filter::FieldFilter;
+
+const REDACTED: &str = "[REDACTED]";
+
+/// Keys whose values are masked unless overridden
+const DEFAULT_SECRET_KEYS: &[&str] =
+    &["password", "token", "authorization", "secret", "api_key"];
+
+/// Kinds of personal data masked by the built-in patterns
+#[cfg(feature = "redact")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pii {
+    /// Email addresses
+    Email,
+    /// Dotted-quad IPv4 addresses
+    Ipv4,
+    /// IPv6 addresses, full or `::` compressed
+    Ipv6,
+    /// Phone numbers of 10 or more digits, optionally with a `+` country
+    /// code and separators
+    Phone,
+}
+
+#[cfg(feature = "redact")]
+impl Pii {
+    /// Every kind of personal data
+    pub const ALL: &'static [Pii] = &[Pii::Email, Pii::Ipv4, Pii::Ipv6, Pii::Phone];
+
+    pub(crate) fn pattern(self) -> Regex {
+        let pattern = match self {
+            Pii::Email => r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}",
+            Pii::Ipv4 => {
+                r"\b(?:(?:25[0-5]|2[0-4][0-9]|1[0-9]{2}|[1-9]?[0-9])\.){3}(?:25[0-5]|2[0-4][0-9]|1[0-9]{2}|[1-9]?[0-9])\b"
+            }
+            Pii::Ipv6 => {
+                r"(?i)\b(?:[0-9a-f]{1,4}:){7}[0-9a-f]{1,4}\b|(?:\b[0-9a-f]{1,4}(?::[0-9a-f]{1,4}){0,5})?::(?:[0-9a-f]{1,4}:){0,5}[0-9a-f]{1,4}\b"
+            }
+            Pii::Phone => r"(?:\+\d{1,3}[ .-]?)?\(?\b\d{3}\)?[ .-]?\d{3}[ .-]?\d{4}\b",
+        };
+        Regex::new(pattern).expect("built-in PII pattern is valid")
+    }
+}
+
+/// What to do with a key's value
+enum Action {
+    Emit,
+    Mask,
+    #[cfg(feature = "pseudonymize")]
+    Pseudonymize,
+    Skip,
+}
+
+/// How `Fields` are scrubbed before serialization
+pub(crate) struct FieldRules {
+    pub(crate) filter: Option<FieldFilter>,
+    /// Lowercased keys whose values are masked, whatever their type
+    pub(crate) secret_keys: HashSet<String>,
+    pub(crate) mask_secrets: bool,
+    /// Longest string value, in bytes, kept whole
+    pub(crate) max_value_len: Option<usize>,
+    /// Most logger and record key-values kept per record
+    pub(crate) max_fields: Option<usize>,
+    /// Patterns replaced with `[REDACTED]` in string values
+    #[cfg(feature = "redact")]
+    pub(crate) redactions: Vec<Regex>,
+    /// Keys whose values are replaced with a salted SHA-256 digest
+    #[cfg(feature = "pseudonymize")]
+    pub(crate) pseudonymized: HashSet<String>,
+    #[cfg(feature = "pseudonymize")]
+    pub(crate) pseudonym_salt: Vec<u8>,
+}
+
+impl Default for FieldRules {
+    fn default() -> Self {
+        FieldRules {
+            filter: None,
+            secret_keys: DEFAULT_SECRET_KEYS.iter().map(|key| key.to_string()).collect(),
+            mask_secrets: true,
+            max_value_len: None,
+            max_fields: None,
+            #[cfg(feature = "redact")]
+            redactions: Vec::new(),
+            #[cfg(feature = "pseudonymize")]
+            pseudonymized: HashSet::new(),
+            #[cfg(feature = "pseudonymize")]
+            pseudonym_salt: Vec::new(),
+        }
+    }
+}
+
+impl FieldRules {
+    /// Whether there is nothing to scrub
+    pub(crate) fn is_empty(&self) -> bool {
+        if (self.mask_secrets && !self.secret_keys.is_empty()) || self.max_value_len.is_some() {
+            return false;
+        }
+        if self.max_fields.is_some() {
+            return false;
+        }
+        #[cfg(feature = "redact")]
+        {
+            if !self.redactions.is_empty() {
+                return false;
+            }
+        }
+        #[cfg(feature = "pseudonymize")]
+        {
+            if !self.pseudonymized.is_empty() {
+                return false;
+            }
+        }
+        self.filter.is_none()
+    }
+
+    fn action(&self, key: &str) -> Action {
+        if !self.filter.as_ref().is_none_or(|filter| filter.allows(key)) {
+            Action::Skip
+        } else if self.mask_secrets && self.secret_keys.contains(&key.to_lowercase()) {
+            Action::Mask
+        } else {
+            #[cfg(feature = "pseudonymize")]
+            {
+                if self.pseudonymized.contains(key) {
+                    return Action::Pseudonymize;
+                }
+            }
+            Action::Emit
+        }
+    }
+
+    /// Hex SHA-256 digest of the salt followed by `value`
+    #[cfg(feature = "pseudonymize")]
+    fn pseudonym(&self, value: &str) -> String {
+        let mut salted = self.pseudonym_salt.clone();
+        salted.extend_from_slice(value.as_bytes());
+        Sha256::digest(&salted)
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    /// Redact then truncate a string value
+    pub(crate) fn scrub<'s>(&self, value: &'s str) -> Cow<'s, str> {
+        #[cfg_attr(not(feature = "redact"), allow(unused_mut))]
+        let mut value = Cow::Borrowed(value);
+        #[cfg(feature = "redact")]
+        {
+            for pattern in &self.redactions {
+                if let Cow::Owned(redacted) = pattern.replace_all(&value, REDACTED) {
+                    value = Cow::Owned(redacted);
+                }
+            }
+        }
+        match self.max_value_len {
+            Some(max) if value.len() > max => {
+                let mut end = max;
+                while !value.is_char_boundary(end) {
+                    end -= 1;
+                }
+                Cow::Owned(format!("{}…[{} bytes]", &value[..end], value.len()))
+            }
+            _ => value,
+        }
+    }
+}
+
+/// `slog::Serializer` adapter applying `FieldRules`
+pub(crate) struct Scrubbed<'a, S: 'a> {
+    pub(crate) inner: &'a mut S,
+    rules: &'a FieldRules,
+    emitted: usize,
+    /// Key-values dropped past `max_fields`
+    pub(crate) overflow: usize,
+}
+
+impl<'a, S> Scrubbed<'a, S> {
+    pub(crate) fn new(inner: &'a mut S, rules: &'a FieldRules) -> Self {
+        Scrubbed {
+            inner,
+            rules,
+            emitted: 0,
+            overflow: 0,
+        }
+    }
+
+    fn action(&mut self, key: &str) -> Action {
+        match self.rules.action(key) {
+            Action::Skip => Action::Skip,
+            _ if self.rules.max_fields.is_some_and(|max| self.emitted >= max) => {
+                self.overflow += 1;
+                Action::Skip
+            }
+            action => {
+                self.emitted += 1;
+                action
+            }
+        }
+    }
+}
+
+macro_rules! impl_scrubbed(
+    ($($emit:ident: $t:ty),*) => {
+        $(
+            fn $emit(&mut self, key: Key, val: $t) -> slog::Result {
+                match self.action(key) {
+                    Action::Emit => self.inner.$emit(key, val),
+                    Action::Mask => self.inner.emit_str(key, REDACTED),
+                    #[cfg(feature = "pseudonymize")]
+                    Action::Pseudonymize => {
+                        self.inner.emit_str(key, &self.rules.pseudonym(&val.to_string()))
+                    }
+                    Action::Skip => Ok(()),
+                }
+            }
+        )*
+    };
+);
+
+impl<'a, S: slog::Serializer> slog::Serializer for Scrubbed<'a, S> {
+    impl_scrubbed!(
+        emit_bool: bool,
+        emit_char: char,
+        emit_u8: u8,
+        emit_i8: i8,
+        emit_u16: u16,
+        emit_i16: i16,
+        emit_usize: usize,
+        emit_isize: isize,
+        emit_u32: u32,
+        emit_i32: i32,
+        emit_f32: f32,
+        emit_u64: u64,
+        emit_i64: i64,
+        emit_f64: f64
+    );
+
+    fn emit_str(&mut self, key: Key, val: &str) -> slog::Result {
+        match self.action(key) {
+            Action::Emit => self.inner.emit_str(key, &self.rules.scrub(val)),
+            Action::Mask => self.inner.emit_str(key, REDACTED),
+            #[cfg(feature = "pseudonymize")]
+            Action::Pseudonymize => self.inner.emit_str(key, &self.rules.pseudonym(val)),
+            Action::Skip => Ok(()),
+        }
+    }
+
+    fn emit_arguments(&mut self, key: Key, val: &fmt::Arguments) -> slog::Result {
+        match self.action(key) {
+            Action::Emit => {
+                let val = fmt::format(*val);
+                self.inner.emit_str(key, &self.rules.scrub(&val))
+            }
+            Action::Mask => self.inner.emit_str(key, REDACTED),
+            #[cfg(feature = "pseudonymize")]
+            Action::Pseudonymize => {
+                self.inner.emit_str(key, &self.rules.pseudonym(&fmt::format(*val)))
+            }
+            Action::Skip => Ok(()),
+        }
+    }
+
+    fn emit_unit(&mut self, key: Key) -> slog::Result {
+        match self.action(key) {
+            Action::Mask => self.inner.emit_str(key, REDACTED),
+            Action::Skip => Ok(()),
+            _ => self.inner.emit_unit(key),
+        }
+    }
+
+    fn emit_none(&mut self, key: Key) -> slog::Result {
+        match self.action(key) {
+            Action::Mask => self.inner.emit_str(key, REDACTED),
+            Action::Skip => Ok(()),
+            _ => self.inner.emit_none(key),
+        }
+    }
+
+    fn emit_serde(&mut self, key: Key, value: &dyn slog::SerdeValue) -> slog::Result {
+        match self.action(key) {
+            Action::Emit => self.inner.emit_serde(key, value),
+            Action::Mask => self.inner.emit_str(key, REDACTED),
+            // Nested values have no canonical string form to digest
+            #[cfg(feature = "pseudonymize")]
+            Action::Pseudonymize => self.inner.emit_str(key, REDACTED),
+            Action::Skip => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Action, FieldRules};
+
+    #[cfg(feature = "redact")]
+    #[test]
+    fn redacts_matches() {
+        use regex::Regex;
+
+        let rules = FieldRules {
+            redactions: vec![Regex::new(r"Bearer \S+").unwrap()],
+            ..FieldRules::default()
+        };
+        assert_eq!(rules.scrub("auth Bearer abc.def ok"), "auth [REDACTED] ok");
+        assert_eq!(rules.scrub("no credentials"), "no credentials");
+    }
+
+    #[test]
+    fn masks_secret_keys() {
+        let mut rules = FieldRules::default();
+        assert!(matches!(rules.action("Password"), Action::Mask));
+        assert!(matches!(rules.action("user"), Action::Emit));
+        rules.secret_keys.insert("session".to_owned());
+        assert!(matches!(rules.action("Session"), Action::Mask));
+        rules.mask_secrets = false;
+        assert!(matches!(rules.action("password"), Action::Emit));
+    }
+
+    #[cfg(feature = "pseudonymize")]
+    #[test]
+    fn pseudonymizes_with_salted_digest() {
+        let rules = FieldRules {
+            pseudonymized: vec!["email".to_owned()].into_iter().collect(),
+            pseudonym_salt: b"salt".to_vec(),
+            ..FieldRules::default()
+        };
+        assert!(matches!(rules.action("email"), Action::Pseudonymize));
+        // SHA-256 of "saltjdoe@example.com"
+        assert_eq!(
+            rules.pseudonym("jdoe@example.com"),
+            "65a261050d4f7dbf898d0af7073659229d88fbefc371561885c9f78c6a5c0734"
+        );
+    }
+
+    #[test]
+    fn truncates_at_char_boundary() {
+        let rules = FieldRules {
+            max_value_len: Some(4),
+            ..FieldRules::default()
+        };
+        assert_eq!(rules.scrub("/a"), "/a");
+        assert_eq!(rules.scrub("long message"), "long…[12 bytes]");
+        // "ñ" spans bytes 3 and 4
+        assert_eq!(rules.scrub("/abñ"), "/ab…[5 bytes]");
+    }
+
+    #[cfg(feature = "redact")]
+    #[test]
+    fn masks_builtin_pii() {
+        use super::Pii;
+
+        let rules = FieldRules {
+            redactions: Pii::ALL.iter().map(|pii| pii.pattern()).collect(),
+            ..FieldRules::default()
+        };
+        assert_eq!(
+            rules.scrub("mail jdoe@example.com from 10.0.0.1:443"),
+            "mail [REDACTED] from [REDACTED]:443"
+        );
+        assert_eq!(rules.scrub("call +1 555-123-4567"), "call [REDACTED]");
+        assert_eq!(rules.scrub("peer fe80::1"), "peer [REDACTED]");
+        assert_eq!(rules.scrub("version 1.2.3"), "version 1.2.3");
+    }
+}
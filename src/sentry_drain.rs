@@ -0,0 +1,87 @@
+use std::{collections::BTreeMap, fmt};
+
+use sentry::{self, protocol::{Event, Value}};
+use slog::{self, Drain, Key, Level, OwnedKVList, Record, KV};
+
+/// `Drain` forwarding Error and Critical records to Sentry as events, then
+/// passing every record on to `drain` (typically a `MozLogJson`)
+///
+/// Events carry the record's message, level (Critical as `fatal`), module
+/// as `logger` and its key-values (the logger's too) as `extra`. They go to
+/// the current Sentry hub, so a client must already be bound with
+/// `sentry::init`.
+pub struct SentryDrain<D> {
+    drain: D,
+}
+
+impl<D: Drain> SentryDrain<D> {
+    /// Forward Error and Critical records to Sentry ahead of `drain`
+    pub fn new(drain: D) -> Self {
+        SentryDrain { drain }
+    }
+}
+
+impl<D: Drain> Drain for SentryDrain<D> {
+    type Ok = D::Ok;
+    type Err = D::Err;
+
+    fn log(&self, rinfo: &Record, logger_values: &OwnedKVList) -> Result<D::Ok, D::Err> {
+        if rinfo.level().is_at_least(Level::Error) {
+            sentry::capture_event(event(rinfo, logger_values));
+        }
+        self.drain.log(rinfo, logger_values)
+    }
+}
+
+fn event(rinfo: &Record, logger_values: &OwnedKVList) -> Event<'static> {
+    // the record's values win over its logger's
+    let mut extra = Extra(BTreeMap::new());
+    let _ = logger_values.serialize(rinfo, &mut extra);
+    let _ = rinfo.kv().serialize(rinfo, &mut extra);
+    Event {
+        message: Some(rinfo.msg().to_string()),
+        level: if rinfo.level() == Level::Critical {
+            sentry::Level::Fatal
+        } else {
+            sentry::Level::Error
+        },
+        logger: Some(rinfo.module().to_owned()),
+        extra: extra.0,
+        ..Default::default()
+    }
+}
+
+/// Collects key-values as Sentry `extra`
+struct Extra(BTreeMap<String, Value>);
+
+impl slog::Serializer for Extra {
+    fn emit_arguments(&mut self, key: Key, val: &fmt::Arguments) -> slog::Result {
+        self.0.insert(key.to_string(), Value::String(val.to_string()));
+        Ok(())
+    }
+
+    fn emit_bool(&mut self, key: Key, val: bool) -> slog::Result {
+        self.0.insert(key.to_string(), Value::Bool(val));
+        Ok(())
+    }
+
+    fn emit_i64(&mut self, key: Key, val: i64) -> slog::Result {
+        self.0.insert(key.to_string(), Value::from(val));
+        Ok(())
+    }
+
+    fn emit_u64(&mut self, key: Key, val: u64) -> slog::Result {
+        self.0.insert(key.to_string(), Value::from(val));
+        Ok(())
+    }
+
+    fn emit_f64(&mut self, key: Key, val: f64) -> slog::Result {
+        self.0.insert(key.to_string(), Value::from(val));
+        Ok(())
+    }
+
+    fn emit_none(&mut self, key: Key) -> slog::Result {
+        self.0.insert(key.to_string(), Value::Null);
+        Ok(())
+    }
+}
@@ -0,0 +1,153 @@
+use std::{fs, io, cmp::Reverse, collections::BinaryHeap, io::{BufRead, Write},
+          path::{Path, PathBuf}, sync::{Mutex, PoisonError}, sync::atomic::{AtomicUsize, Ordering}};
+
+use slog::{self, OwnedKVList, Record};
+
+use drain::MozLogJson;
+use stats::DrainStats;
+
+thread_local! {
+    /// This thread's shard index, before reducing modulo the shard count
+    static TL_SHARD: usize = NEXT_SHARD.fetch_add(1, Ordering::Relaxed)
+}
+
+static NEXT_SHARD: AtomicUsize = AtomicUsize::new(0);
+
+/// Path of shard `n` of `path`
+fn shard_path(path: &Path, n: usize) -> PathBuf {
+    let mut shard = path.to_owned().into_os_string();
+    shard.push(format!(".{}", n));
+    PathBuf::from(shard)
+}
+
+/// The `Timestamp` of a record, or 0 if it has none
+fn timestamp(record: &[u8]) -> i64 {
+    let key = b"\"Timestamp\":";
+    let rest = match record.windows(key.len()).position(|w| w == key) {
+        Some(pos) => &record[pos + key.len()..],
+        None => return 0,
+    };
+    rest.iter()
+        .skip_while(|b| **b == b' ')
+        .take_while(|b| b.is_ascii_digit())
+        .fold(0i64, |ts, b| ts.wrapping_mul(10).wrapping_add(i64::from(b - b'0')))
+}
+
+/// `Drain` writing each thread's records to its own shard file
+///
+/// For extreme-throughput batch jobs where a single writer is the
+/// bottleneck: every thread is assigned one of the shards, `path.0` to
+/// `path.<shards - 1>`, so with at least as many shards as threads no two
+/// threads ever contend on a lock. Records are serialized by the wrapped
+/// `MozLogJson` (whose own writer is unused, so build it over `io::sink()`)
+/// and always newline terminated. Combine the shards afterwards with
+/// `ShardedDrain::merge`.
+pub struct ShardedDrain {
+    drain: MozLogJson<io::Sink>,
+    shards: Vec<Mutex<io::BufWriter<fs::File>>>,
+}
+
+impl ShardedDrain {
+    /// New `ShardedDrain` appending to `shards` (at least 1) files named
+    /// after `path`
+    pub fn new<P: AsRef<Path>>(
+        drain: MozLogJson<io::Sink>,
+        path: P,
+        shards: usize,
+    ) -> io::Result<Self> {
+        let shards = (0..shards.max(1))
+            .map(|n| {
+                fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(shard_path(path.as_ref(), n))
+                    .map(|file| Mutex::new(io::BufWriter::new(file)))
+            })
+            .collect::<io::Result<_>>()?;
+        Ok(ShardedDrain {
+            drain,
+            shards,
+        })
+    }
+
+    /// Current counters
+    pub fn stats(&self) -> DrainStats {
+        self.drain.stats()
+    }
+
+    /// Flush every shard
+    pub fn flush(&self) -> io::Result<()> {
+        for shard in &self.shards {
+            shard.lock().unwrap_or_else(PoisonError::into_inner).flush()?;
+        }
+        Ok(())
+    }
+
+    /// Merge the shard files of `path` into `out`, ordered by `Timestamp`,
+    /// returning the number of records written
+    ///
+    /// Each shard is assumed to be in order already, as it is when written
+    /// by a single thread.
+    pub fn merge<P: AsRef<Path>, W: io::Write>(path: P, out: &mut W) -> io::Result<u64> {
+        let mut readers = vec![];
+        for n in 0.. {
+            match fs::File::open(shard_path(path.as_ref(), n)) {
+                Ok(file) => readers.push(io::BufReader::new(file)),
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        let mut heads: Vec<Vec<u8>> = vec![vec![]; readers.len()];
+        let mut heap = BinaryHeap::new();
+        for (n, reader) in readers.iter_mut().enumerate() {
+            if reader.read_until(b'\n', &mut heads[n])? > 0 {
+                heap.push(Reverse((timestamp(&heads[n]), n)));
+            }
+        }
+        let mut merged = 0;
+        while let Some(Reverse((_, n))) = heap.pop() {
+            out.write_all(&heads[n])?;
+            if !heads[n].ends_with(b"\n") {
+                // A final record cut short, e.g. by a crash
+                out.write_all(b"\n")?;
+            }
+            merged += 1;
+            heads[n].clear();
+            if readers[n].read_until(b'\n', &mut heads[n])? > 0 {
+                heap.push(Reverse((timestamp(&heads[n]), n)));
+            }
+        }
+        out.flush()?;
+        Ok(merged)
+    }
+}
+
+impl slog::Drain for ShardedDrain {
+    type Ok = ();
+    type Err = io::Error;
+
+    fn log(&self, rinfo: &Record, logger_values: &OwnedKVList) -> io::Result<()> {
+        let shard = &self.shards[TL_SHARD.with(|n| *n) % self.shards.len()];
+        self.drain.with_record(rinfo, logger_values, |payload| {
+            payload.push(b'\n');
+            // A panic mid-write leaves at worst a partial record, so carry
+            // on past a poisoned lock
+            let res = shard
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .write_all(payload);
+            match res {
+                Ok(_) => self.drain.counters().written(rinfo.level(), payload.len()),
+                Err(_) => self.drain.counters().write_error(),
+            }
+            res
+        })
+    }
+}
+
+impl Drop for ShardedDrain {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
@@ -0,0 +1,90 @@
+use std::{fs, io, mem, ptr, thread, io::Read, os::unix::io::FromRawFd,
+          sync::{Mutex, Once, PoisonError}, sync::atomic::{AtomicI32, Ordering}};
+
+use libc::{self, c_int, c_void};
+use slog::Level;
+
+use filter::LevelHandle;
+
+/// Write end of the pipe the signal handler forwards signals through
+static PIPE: AtomicI32 = AtomicI32::new(-1);
+/// Handles toggled by the signals, with their configured levels
+static HANDLES: Mutex<Vec<(LevelHandle, Level)>> = Mutex::new(Vec::new());
+static START: Once = Once::new();
+
+extern "C" fn forward(signal: c_int) {
+    // Only async-signal-safe calls here: hand the signal to the watcher
+    let fd = PIPE.load(Ordering::Relaxed);
+    if fd >= 0 {
+        let byte = signal as u8;
+        unsafe {
+            libc::write(fd, &byte as *const u8 as *const c_void, 1);
+        }
+    }
+}
+
+fn watch(mut pipe: fs::File) {
+    let mut byte = [0; 1];
+    loop {
+        match pipe.read(&mut byte) {
+            Ok(1) => (),
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            _ => return,
+        }
+        let handles = HANDLES.lock().unwrap_or_else(PoisonError::into_inner);
+        for &(ref handle, configured) in handles.iter() {
+            match c_int::from(byte[0]) {
+                libc::SIGUSR1 if !configured.is_at_least(Level::Debug) => {
+                    // Already more verbose than Debug
+                    handle.set_level(configured)
+                }
+                libc::SIGUSR1 => handle.set_level(Level::Debug),
+                libc::SIGUSR2 => handle.set_level(configured),
+                _ => (),
+            }
+        }
+    }
+}
+
+fn start() -> io::Result<()> {
+    let mut fds = [0 as c_int; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let pipe = unsafe { fs::File::from_raw_fd(fds[0]) };
+    PIPE.store(fds[1], Ordering::Relaxed);
+    thread::Builder::new()
+        .name("mozlog-signals".to_owned())
+        .spawn(move || watch(pipe))?;
+
+    let mut action: libc::sigaction = unsafe { mem::zeroed() };
+    action.sa_sigaction = forward as extern "C" fn(c_int) as libc::sighandler_t;
+    action.sa_flags = libc::SA_RESTART;
+    unsafe {
+        libc::sigemptyset(&mut action.sa_mask);
+    }
+    for &signal in &[libc::SIGUSR1, libc::SIGUSR2] {
+        if unsafe { libc::sigaction(signal, &action, ptr::null_mut()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+impl LevelHandle {
+    /// Let on-call engineers toggle verbosity of a live process: `SIGUSR1`
+    /// lowers the minimum level to Debug, `SIGUSR2` restores the current
+    /// one
+    ///
+    /// Replaces any other handlers of those signals.
+    pub fn toggle_on_signals(&self) -> io::Result<()> {
+        let mut res = Ok(());
+        START.call_once(|| res = start());
+        res?;
+        HANDLES
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push((self.clone(), self.level()));
+        Ok(())
+    }
+}
@@ -0,0 +1,232 @@
+use std::{io, io::Write, thread, time::{Duration, Instant}};
+
+use flate2::{Compression, write::GzEncoder};
+use serde_json::{self, Value};
+use ureq;
+use uuid::Uuid;
+
+use batch::{BatchConfig, BatchSink, BatchWriter, SendError};
+use http::ureq_error;
+
+fn other<E: ToString>(e: E) -> io::Error {
+    io::Error::other(e.to_string())
+}
+
+struct SplunkSink {
+    agent: ureq::Agent,
+    url: String,
+    token: String,
+    channel: String,
+    source: Option<String>,
+    sourcetype: String,
+    index: Option<String>,
+    gzip: bool,
+    ack_timeout: Option<Duration>,
+}
+
+impl SplunkSink {
+    /// HEC event envelope for one record
+    fn envelope(&self, record: &[u8]) -> Value {
+        let event: Value = serde_json::from_slice(record)
+            .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(record).into_owned()));
+        let mut envelope = json!({ "sourcetype": self.sourcetype });
+        if let Some(ns) = event.get("Timestamp").and_then(Value::as_i64) {
+            envelope["time"] = json!(ns as f64 / 1e9);
+        }
+        if let Some(host) = event.get("Hostname") {
+            envelope["host"] = host.clone();
+        }
+        if let Some(ref source) = self.source {
+            envelope["source"] = json!(source);
+        }
+        if let Some(ref index) = self.index {
+            envelope["index"] = json!(index);
+        }
+        envelope["event"] = event;
+        envelope
+    }
+
+    fn post(&self, path: &str, body: &[u8], compress: bool) -> Result<Value, SendError> {
+        let request = self.agent
+            .post(&format!("{}{}", self.url, path))
+            .set("Authorization", &format!("Splunk {}", self.token))
+            .set("X-Splunk-Request-Channel", &self.channel)
+            .set("Content-Type", "application/json");
+        let resp = if compress {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(body).map_err(SendError::Permanent)?;
+            let gzipped = encoder.finish().map_err(SendError::Permanent)?;
+            request.set("Content-Encoding", "gzip").send_bytes(&gzipped)
+        } else {
+            request.send_bytes(body)
+        };
+        let resp = resp.map_err(ureq_error)?;
+        let body = resp.into_string().map_err(SendError::Transient)?;
+        Ok(serde_json::from_str(&body).unwrap_or(Value::Null))
+    }
+
+    /// Poll the ack endpoint until `ack_id` is indexed
+    fn wait_for_ack(&self, ack_id: u64, timeout: Duration) -> Result<(), SendError> {
+        let deadline = Instant::now() + timeout;
+        let body = json!({ "acks": [ack_id] }).to_string();
+        loop {
+            let resp = self.post("/services/collector/ack", body.as_bytes(), false)?;
+            let acked = resp.get("acks")
+                .and_then(|acks| acks.get(ack_id.to_string()))
+                .and_then(Value::as_bool)
+                .unwrap_or(false);
+            if acked {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                return Err(SendError::Transient(other(format!(
+                    "HEC ack {} not received",
+                    ack_id
+                ))));
+            }
+            thread::sleep(Duration::from_secs(1));
+        }
+    }
+}
+
+impl BatchSink for SplunkSink {
+    fn send(&mut self, batch: &[Vec<u8>]) -> Result<(), SendError> {
+        let mut body = Vec::new();
+        for record in batch {
+            serde_json::to_writer(&mut body, &self.envelope(record))
+                .map_err(|e| SendError::Permanent(e.into()))?;
+        }
+        let resp = self.post("/services/collector/event", &body, self.gzip)?;
+        if let Some(timeout) = self.ack_timeout {
+            let ack_id = resp.get("ackId").and_then(Value::as_u64).ok_or_else(|| {
+                SendError::Permanent(other("HEC response has no ackId, is indexer acknowledgment enabled?"))
+            })?;
+            self.wait_for_ack(ack_id, timeout)?;
+        }
+        Ok(())
+    }
+}
+
+/// Splunk HTTP Event Collector `io::Write` sink
+///
+/// Records are batched on a background thread (see `BatchConfig`), wrapped
+/// in HEC event envelopes (`time` from the record `Timestamp`, `host` from
+/// its `Hostname`) and POSTed to the collector. Requires compact output
+/// with newlines enabled.
+pub struct SplunkWriter {
+    inner: BatchWriter,
+}
+
+impl SplunkWriter {
+    /// Build a `SplunkWriter` for the HEC at `url` (e.g.
+    /// `https://splunk:8088`) authenticating with `token`
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(url: String, token: String) -> SplunkWriterBuilder {
+        SplunkWriterBuilder {
+            url: url.trim_end_matches('/').to_owned(),
+            token,
+            source: None,
+            sourcetype: "_json".to_owned(),
+            index: None,
+            gzip: true,
+            ack_timeout: None,
+            timeout: Duration::from_secs(10),
+            batching: BatchConfig::default(),
+        }
+    }
+
+    /// Number of records dropped on a full queue or failed delivery
+    pub fn dropped(&self) -> usize {
+        self.inner.dropped()
+    }
+}
+
+impl io::Write for SplunkWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// `SplunkWriter` builder
+///
+/// Create with `SplunkWriter::new`.
+pub struct SplunkWriterBuilder {
+    url: String,
+    token: String,
+    source: Option<String>,
+    sourcetype: String,
+    index: Option<String>,
+    gzip: bool,
+    ack_timeout: Option<Duration>,
+    timeout: Duration,
+    batching: BatchConfig,
+}
+
+impl SplunkWriterBuilder {
+    /// Build `SplunkWriter`, starting its sender thread
+    pub fn build(self) -> io::Result<SplunkWriter> {
+        let sink = SplunkSink {
+            agent: ureq::AgentBuilder::new().timeout(self.timeout).build(),
+            url: self.url,
+            token: self.token,
+            channel: Uuid::new_v4().to_string(),
+            source: self.source,
+            sourcetype: self.sourcetype,
+            index: self.index,
+            gzip: self.gzip,
+            ack_timeout: self.ack_timeout,
+        };
+        Ok(SplunkWriter {
+            inner: BatchWriter::spawn("mozlog-splunk", sink, self.batching)?,
+        })
+    }
+
+    /// Set the `source` of each event
+    pub fn set_source(mut self, source: String) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    /// Set the `sourcetype` of each event (default `_json`)
+    pub fn set_sourcetype(mut self, sourcetype: String) -> Self {
+        self.sourcetype = sourcetype;
+        self
+    }
+
+    /// Set the index events are written to, instead of the token's default
+    pub fn set_index(mut self, index: String) -> Self {
+        self.index = Some(index);
+        self
+    }
+
+    /// Set whether request bodies are gzip compressed
+    pub fn set_gzip(mut self, enabled: bool) -> Self {
+        self.gzip = enabled;
+        self
+    }
+
+    /// Wait up to `timeout` for indexer acknowledgment of each batch,
+    /// retrying the batch if it isn't acknowledged
+    ///
+    /// Requires indexer acknowledgment to be enabled on the HEC token.
+    pub fn set_ack_timeout(mut self, timeout: Duration) -> Self {
+        self.ack_timeout = Some(timeout);
+        self
+    }
+
+    /// Set the timeout for each request
+    pub fn set_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set batching and backpressure behavior
+    pub fn set_batching(mut self, batching: BatchConfig) -> Self {
+        self.batching = batching;
+        self
+    }
+}
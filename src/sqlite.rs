@@ -0,0 +1,165 @@
+use std::{io, path::{Path, PathBuf}};
+
+use rusqlite::{self, Connection, types::ToSql};
+use serde_json::{self, Value};
+
+use batch::{BatchConfig, BatchSink, BatchWriter, SendError};
+
+fn other<E: ToString>(e: E) -> io::Error {
+    io::Error::other(e.to_string())
+}
+
+struct SqliteSink {
+    conn: Connection,
+    insert: String,
+}
+
+impl SqliteSink {
+    fn insert(&mut self, batch: &[Vec<u8>]) -> rusqlite::Result<()> {
+        let tx = self.conn.transaction()?;
+        {
+            let mut stmt = tx.prepare_cached(&self.insert)?;
+            for record in batch {
+                let parsed: Value = serde_json::from_slice(record).unwrap_or(Value::Null);
+                let text = |key: &str| parsed.get(key).and_then(Value::as_str).map(str::to_owned);
+                let mut fields = parsed.get("Fields").cloned().unwrap_or(Value::Null);
+                let msg = fields
+                    .as_object_mut()
+                    .and_then(|fields| fields.remove("msg"))
+                    .and_then(|msg| msg.as_str().map(str::to_owned));
+                let timestamp = parsed.get("Timestamp").and_then(Value::as_i64);
+                let severity = parsed.get("Severity").and_then(Value::as_i64);
+                let pid = parsed.get("Pid").and_then(Value::as_i64);
+                let logger = text("Logger");
+                let msg_type = text("Type");
+                let hostname = text("Hostname");
+                let fields = if fields.is_null() {
+                    None
+                } else {
+                    Some(fields.to_string())
+                };
+                stmt.execute(&[
+                    &timestamp as &dyn ToSql,
+                    &severity,
+                    &logger,
+                    &msg_type,
+                    &hostname,
+                    &pid,
+                    &msg,
+                    &fields,
+                ][..])?;
+            }
+        }
+        tx.commit()
+    }
+}
+
+impl BatchSink for SqliteSink {
+    fn send(&mut self, batch: &[Vec<u8>]) -> Result<(), SendError> {
+        // Usually the database being locked by a reader
+        self.insert(batch).map_err(|e| SendError::Transient(other(e)))
+    }
+}
+
+/// SQLite `io::Write` sink
+///
+/// Records are batched on a background thread (see `BatchConfig`) and
+/// inserted, one transaction per batch, into a table with `timestamp`
+/// (nanoseconds), `severity`, `logger`, `type`, `hostname`, `pid`, `msg`
+/// and `fields` (JSON) columns, for ad-hoc querying with `sqlite3`.
+/// Requires compact output with newlines enabled.
+pub struct SqliteWriter {
+    inner: BatchWriter,
+}
+
+impl SqliteWriter {
+    /// Build a `SqliteWriter` for the database at `path`, created if
+    /// missing
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new<P: AsRef<Path>>(path: P) -> SqliteWriterBuilder {
+        SqliteWriterBuilder {
+            path: path.as_ref().to_owned(),
+            table: "mozlog".to_owned(),
+            batching: BatchConfig::default(),
+        }
+    }
+
+    /// Number of records dropped on a full queue or failed insert
+    pub fn dropped(&self) -> usize {
+        self.inner.dropped()
+    }
+}
+
+impl io::Write for SqliteWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// `SqliteWriter` builder
+///
+/// Create with `SqliteWriter::new`.
+pub struct SqliteWriterBuilder {
+    path: PathBuf,
+    table: String,
+    batching: BatchConfig,
+}
+
+impl SqliteWriterBuilder {
+    /// Build `SqliteWriter`, opening the database and creating the table if
+    /// needed
+    pub fn build(self) -> io::Result<SqliteWriter> {
+        if self.table.is_empty()
+            || !self.table.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "table name must be alphanumeric",
+            ));
+        }
+        let conn = Connection::open(&self.path).map_err(other)?;
+        conn.execute_batch(&format!(
+            "PRAGMA journal_mode = WAL;
+             CREATE TABLE IF NOT EXISTS {table} (
+                 id INTEGER PRIMARY KEY,
+                 timestamp INTEGER,
+                 severity INTEGER,
+                 logger TEXT,
+                 type TEXT,
+                 hostname TEXT,
+                 pid INTEGER,
+                 msg TEXT,
+                 fields TEXT
+             );
+             CREATE INDEX IF NOT EXISTS {table}_timestamp ON {table} (timestamp);",
+            table = self.table
+        )).map_err(other)?;
+        let sink = SqliteSink {
+            conn,
+            insert: format!(
+                "INSERT INTO {} (timestamp, severity, logger, type, hostname, pid, msg, fields)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                self.table
+            ),
+        };
+        Ok(SqliteWriter {
+            inner: BatchWriter::spawn("mozlog-sqlite", sink, self.batching)?,
+        })
+    }
+
+    /// Set the table name (default `mozlog`)
+    pub fn set_table(mut self, table: String) -> Self {
+        self.table = table;
+        self
+    }
+
+    /// Set batching and backpressure behavior
+    pub fn set_batching(mut self, batching: BatchConfig) -> Self {
+        self.batching = batching;
+        self
+    }
+}
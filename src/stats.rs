@@ -0,0 +1,88 @@
+use std::sync::{Arc, atomic::{AtomicU64, Ordering}};
+
+use slog::Level;
+
+/// Snapshot of a `MozLogJson` drain's counters, e.g. for exporting as
+/// metrics and alerting on log loss
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DrainStats {
+    /// Records written
+    pub records: u64,
+    /// Records written per level, most severe first (see `records_at`)
+    pub levels: [u64; 6],
+    /// Bytes written, including newlines
+    pub bytes: u64,
+    /// Records that failed to serialize or write
+    pub write_errors: u64,
+    /// Records lost in total: to write errors, full queues and filters
+    pub dropped: u64,
+}
+
+impl DrainStats {
+    /// Records written at `level`
+    pub fn records_at(&self, level: Level) -> u64 {
+        self.levels[level.as_usize() - 1]
+    }
+}
+
+/// A drain's live counters
+#[derive(Default)]
+pub(crate) struct Counters {
+    records: AtomicU64,
+    levels: [AtomicU64; 6],
+    bytes: AtomicU64,
+    write_errors: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl Counters {
+    pub(crate) fn written(&self, level: Level, bytes: usize) {
+        self.records.fetch_add(1, Ordering::Relaxed);
+        self.levels[level.as_usize() - 1].fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn write_error(&self) {
+        self.write_errors.fetch_add(1, Ordering::Relaxed);
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn dropped(&self) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> DrainStats {
+        let mut levels = [0; 6];
+        for (count, counter) in levels.iter_mut().zip(&self.levels) {
+            *count = counter.load(Ordering::Relaxed);
+        }
+        DrainStats {
+            records: self.records.load(Ordering::Relaxed),
+            levels,
+            bytes: self.bytes.load(Ordering::Relaxed),
+            write_errors: self.write_errors.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Handle reading a `MozLogJson` drain's counters once it's been moved
+/// into a `Logger`
+///
+/// Create with `MozLogJson::stats_handle`. Keeps only the counters alive,
+/// not the drain.
+#[derive(Clone)]
+pub struct StatsHandle {
+    counters: Arc<Counters>,
+}
+
+impl StatsHandle {
+    pub(crate) fn new(counters: Arc<Counters>) -> Self {
+        StatsHandle { counters }
+    }
+
+    /// Current counters
+    pub fn stats(&self) -> DrainStats {
+        self.counters.snapshot()
+    }
+}
@@ -0,0 +1,84 @@
+use std::time::Duration;
+
+use slog::{self, Key, Logger, Record, Value, KV};
+
+/// The Fields of a `request.summary` access log record, per Mozilla's
+/// operational logging conventions
+///
+/// As a `KV` it emits `method`, `path`, `code`, `t` (milliseconds), and those
+/// of `agent` (User-Agent), `remote_ip`, `uid` and `rid` (request id) that
+/// are known. As a `Value` its key is ignored and the same fields are
+/// spliced in.
+///
+/// ```ignore
+/// let mut summary = RequestSummary::new("GET", "/", 200, started.elapsed());
+/// summary.uid = Some(uid);
+/// summary.log(&logger);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RequestSummary {
+    pub method: String,
+    pub path: String,
+    pub code: u16,
+    pub t_ms: u64,
+    pub agent: Option<String>,
+    pub remote_ip: Option<String>,
+    pub uid: Option<String>,
+    pub rid: Option<String>,
+}
+
+impl RequestSummary {
+    /// A summary of a `method` request for `path` answered with `code` after
+    /// `elapsed`
+    pub fn new<M, P>(method: M, path: P, code: u16, elapsed: Duration) -> Self
+    where
+        M: Into<String>,
+        P: Into<String>,
+    {
+        let mut summary = RequestSummary {
+            method: method.into(),
+            path: path.into(),
+            code,
+            ..Default::default()
+        };
+        summary.set_elapsed(elapsed);
+        summary
+    }
+
+    /// Set `t_ms` from the time taken to answer the request
+    pub fn set_elapsed(&mut self, elapsed: Duration) {
+        self.t_ms = elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_millis());
+    }
+
+    /// Log the summary to `logger` as an info `request.summary` record
+    pub fn log(self, logger: &Logger) {
+        info!(logger, #"request.summary", ""; self);
+    }
+}
+
+impl KV for RequestSummary {
+    fn serialize(&self, _rinfo: &Record, serializer: &mut dyn slog::Serializer) -> slog::Result {
+        serializer.emit_str("method", &self.method)?;
+        serializer.emit_str("path", &self.path)?;
+        serializer.emit_u16("code", self.code)?;
+        serializer.emit_u64("t", self.t_ms)?;
+        let optional = [
+            ("agent", &self.agent),
+            ("remote_ip", &self.remote_ip),
+            ("uid", &self.uid),
+            ("rid", &self.rid),
+        ];
+        for &(key, value) in &optional {
+            if let Some(ref value) = *value {
+                serializer.emit_str(key, value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Value for RequestSummary {
+    fn serialize(&self, rinfo: &Record, _key: Key, serializer: &mut dyn slog::Serializer) -> slog::Result {
+        KV::serialize(self, rinfo, serializer)
+    }
+}
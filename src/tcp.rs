@@ -0,0 +1,153 @@
+use std::{io, net::{TcpStream, ToSocketAddrs}, time::Duration};
+
+use reconnect::{Connect, Reconnecting};
+#[cfg(feature = "tls")]
+use tls::{TlsConfig, TlsConnector, TlsStream};
+
+struct TcpConnector {
+    addr: String,
+    connect_timeout: Duration,
+    write_timeout: Duration,
+    #[cfg(feature = "tls")]
+    tls: Option<TlsConnector>,
+}
+
+impl TcpConnector {
+    fn connect_tcp(&self) -> io::Result<TcpStream> {
+        let mut last_err = None;
+        for addr in self.addr.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, self.connect_timeout) {
+                Ok(stream) => {
+                    stream.set_nodelay(true)?;
+                    // A relay that stops reading mustn't block logging
+                    // threads
+                    stream.set_write_timeout(Some(self.write_timeout))?;
+                    return Ok(stream);
+                }
+                Err(e) => last_err = Some(e),
+            }
+        }
+        Err(last_err.unwrap_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "no addresses to connect to")
+        }))
+    }
+}
+
+enum TcpTransport {
+    Plain(TcpStream),
+    #[cfg(feature = "tls")]
+    Tls(Box<TlsStream>),
+}
+
+impl io::Write for TcpTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match *self {
+            TcpTransport::Plain(ref mut stream) => stream.write(buf),
+            #[cfg(feature = "tls")]
+            TcpTransport::Tls(ref mut stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match *self {
+            TcpTransport::Plain(ref mut stream) => stream.flush(),
+            #[cfg(feature = "tls")]
+            TcpTransport::Tls(ref mut stream) => stream.flush(),
+        }
+    }
+}
+
+impl Connect for TcpConnector {
+    type Stream = TcpTransport;
+
+    fn connect(&self) -> io::Result<TcpTransport> {
+        let stream = self.connect_tcp()?;
+        #[cfg(feature = "tls")]
+        {
+            if let Some(ref tls) = self.tls {
+                return Ok(TcpTransport::Tls(Box::new(tls.wrap(stream)?)));
+            }
+        }
+        Ok(TcpTransport::Plain(stream))
+    }
+}
+
+/// TCP `io::Write` sink
+///
+/// Records are sent newline framed to `host:port`. The connection is made
+/// lazily and re-established with exponential backoff after a failure;
+/// while disconnected, complete records are buffered up to a byte limit,
+/// dropping the oldest first.
+///
+/// With the `tls` feature the connection may be wrapped in TLS, see
+/// `set_tls`.
+pub struct TcpWriter {
+    inner: Reconnecting<TcpConnector>,
+}
+
+impl TcpWriter {
+    /// New `TcpWriter` for `addr` (`host:port`)
+    pub fn new(addr: String) -> Self {
+        TcpWriter {
+            inner: Reconnecting::new(TcpConnector {
+                addr,
+                connect_timeout: Duration::from_secs(5),
+                write_timeout: Duration::from_secs(5),
+                #[cfg(feature = "tls")]
+                tls: None,
+            }),
+        }
+    }
+
+    /// Set the maximum number of bytes buffered while disconnected
+    pub fn set_buffer_limit(mut self, bytes: usize) -> Self {
+        self.inner.set_buffer_limit(bytes);
+        self
+    }
+
+    /// Set the initial and maximum delay between reconnection attempts
+    pub fn set_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.inner.set_backoff(initial, max);
+        self
+    }
+
+    /// Set the timeout for each connection attempt
+    pub fn set_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.inner.connector_mut().connect_timeout = timeout;
+        self
+    }
+
+    /// Set the timeout for each write, after which the connection is
+    /// re-established (default 5s)
+    pub fn set_write_timeout(mut self, timeout: Duration) -> Self {
+        self.inner.connector_mut().write_timeout = timeout;
+        self
+    }
+
+    /// Connect over TLS
+    ///
+    /// Fails if the configured client certificate or server name is invalid.
+    #[cfg(feature = "tls")]
+    pub fn set_tls(mut self, tls: TlsConfig) -> io::Result<Self> {
+        {
+            let connector = self.inner.connector_mut();
+            connector.tls = Some(tls.connector(&connector.addr)?);
+        }
+        Ok(self)
+    }
+
+    /// Number of records dropped because the buffer limit was reached
+    pub fn dropped(&self) -> u64 {
+        self.inner.dropped()
+    }
+}
+
+impl io::Write for TcpWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
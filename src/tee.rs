@@ -0,0 +1,116 @@
+use std::{io, panic::{RefUnwindSafe, UnwindSafe}, sync::atomic::{AtomicUsize, Ordering}};
+
+use slog::{Drain, Level, OwnedKVList, Record};
+
+/// Destination `Drain`, unwind safe so the `Tee` can still go in a `Logger`
+type BoxedDrain =
+    Box<dyn Drain<Ok = (), Err = io::Error> + Send + Sync + RefUnwindSafe + UnwindSafe>;
+
+struct Destination {
+    level: Level,
+    drain: BoxedDrain,
+    errors: AtomicUsize,
+}
+
+/// `Drain` fanning each record out to several destinations
+///
+/// Each destination (typically a `MozLogJson`) has its own minimum level.
+/// A failing destination doesn't prevent delivery to the others: its
+/// errors are counted, and an error is only returned when every
+/// destination the record was meant for failed.
+pub struct Tee {
+    destinations: Vec<Destination>,
+}
+
+impl Tee {
+    /// New `Tee` with no destinations
+    pub fn new() -> Self {
+        Tee {
+            destinations: vec![],
+        }
+    }
+
+    /// Add a destination receiving records at `level` or more severe
+    pub fn add<D>(mut self, level: Level, drain: D) -> Self
+    where
+        D: Drain<Ok = (), Err = io::Error> + Send + Sync + RefUnwindSafe + UnwindSafe + 'static,
+    {
+        self.destinations.push(Destination {
+            level,
+            drain: Box::new(drain),
+            errors: AtomicUsize::new(0),
+        });
+        self
+    }
+
+    /// Number of failed writes per destination, in the order they were
+    /// added
+    pub fn errors(&self) -> Vec<usize> {
+        self.destinations
+            .iter()
+            .map(|dest| dest.errors.load(Ordering::Relaxed))
+            .collect()
+    }
+}
+
+impl Default for Tee {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drain for Tee {
+    type Ok = ();
+    type Err = io::Error;
+
+    fn log(&self, rinfo: &Record, logger_values: &OwnedKVList) -> io::Result<()> {
+        let mut delivered = false;
+        let mut last_err = None;
+        for dest in &self.destinations {
+            if !rinfo.level().is_at_least(dest.level) {
+                continue;
+            }
+            match dest.drain.log(rinfo, logger_values) {
+                Ok(()) => delivered = true,
+                Err(e) => {
+                    dest.errors.fetch_add(1, Ordering::Relaxed);
+                    last_err = Some(e);
+                }
+            }
+        }
+        match last_err {
+            Some(e) if !delivered => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    fn is_enabled(&self, level: Level) -> bool {
+        self.destinations
+            .iter()
+            .any(|dest| level.is_at_least(dest.level) && dest.drain.is_enabled(level))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use slog::{Drain, Level, Logger};
+
+    use test_drain::TestDrain;
+    use super::Tee;
+
+    #[test]
+    fn fans_out_by_level() {
+        let all = TestDrain::new();
+        let all_records = all.records_handle();
+        let errors = TestDrain::new();
+        let error_records = errors.records_handle();
+        let tee = Tee::new().add(Level::Debug, all).add(Level::Error, errors);
+        let logger = Logger::root(tee.fuse(), o!());
+        info!(logger, "info");
+        error!(logger, "error");
+        assert_eq!(all_records.len(), 2);
+        let error_records = error_records.records();
+        assert_eq!(error_records.len(), 1);
+        assert_eq!(error_records[0]["Fields"]["msg"], "error");
+    }
+}
@@ -0,0 +1,222 @@
+use std::{io, sync::{Arc, Mutex, PoisonError}};
+
+use serde_json::{self, Value};
+use slog::{Drain, Level, OwnedKVList, Record};
+
+use drain::{MozLogJson, MozLogJsonBuilder};
+
+/// `MozLogJson` drain keeping the records it writes, parsed, for tests to
+/// assert on
+///
+/// Read them through a `RecordsHandle`, as the drain itself is moved into
+/// a `Logger`:
+///
+/// ```ignore
+/// let drain = TestDrain::with_builder(|builder| builder.set_clock(FixedClock(0)));
+/// let records = drain.records_handle();
+/// let logger = Logger::root(drain.fuse(), o!());
+/// info!(logger, "hello"; "user" => "jdoe");
+/// assert_eq!(records.records()[0]["Fields"]["user"], "jdoe");
+/// ```
+pub struct TestDrain {
+    drain: MozLogJson<TestWriter>,
+    records: RecordsHandle,
+}
+
+impl TestDrain {
+    /// New `TestDrain` with the default settings
+    pub fn new() -> Self {
+        TestDrain::with_builder(|builder| builder)
+    }
+
+    /// New `TestDrain` with the settings `configure` applies to the builder
+    ///
+    /// The encoding must be `Encoding::Json` (pretty printed or not). The
+    /// `MOZLOG_*` environment variables are ignored.
+    pub fn with_builder<F>(configure: F) -> Self
+    where
+        F: FnOnce(MozLogJsonBuilder<TestWriter>) -> MozLogJsonBuilder<TestWriter>,
+    {
+        let writer = TestWriter::new();
+        let records = writer.records_handle();
+        TestDrain {
+            drain: configure(MozLogJsonBuilder::new_without_env(writer)).build(),
+            records,
+        }
+    }
+
+    /// Handle for reading the records once the drain has been moved into
+    /// a `Logger`
+    pub fn records_handle(&self) -> RecordsHandle {
+        self.records.clone()
+    }
+}
+
+impl Default for TestDrain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drain for TestDrain {
+    type Ok = ();
+    type Err = io::Error;
+
+    fn log(&self, rinfo: &Record, logger_values: &OwnedKVList) -> io::Result<()> {
+        self.drain.log(rinfo, logger_values)
+    }
+
+    fn is_enabled(&self, level: Level) -> bool {
+        self.drain.is_enabled(level)
+    }
+}
+
+/// Handle reading the records written to a `TestDrain`, see
+/// `TestDrain::records_handle`
+#[derive(Clone)]
+pub struct RecordsHandle {
+    records: Arc<Mutex<Vec<Value>>>,
+}
+
+impl RecordsHandle {
+    /// The records written so far
+    pub fn records(&self) -> Vec<Value> {
+        self.records.lock().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// Remove and return the records written so far
+    pub fn take(&self) -> Vec<Value> {
+        let mut records = self.records.lock().unwrap_or_else(PoisonError::into_inner);
+        records.drain(..).collect()
+    }
+
+    /// Number of records written so far
+    pub fn len(&self) -> usize {
+        self.records.lock().unwrap_or_else(PoisonError::into_inner).len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// `TestDrain`'s writer, parsing records as they're written
+pub struct TestWriter {
+    /// Bytes of the record being written
+    buf: Vec<u8>,
+    records: RecordsHandle,
+}
+
+impl TestWriter {
+    /// New `TestWriter`, for a drain's other destinations (e.g. a route)
+    pub(crate) fn new() -> Self {
+        TestWriter {
+            buf: vec![],
+            records: RecordsHandle {
+                records: Arc::new(Mutex::new(vec![])),
+            },
+        }
+    }
+
+    /// Handle for reading the records once the writer has been moved into
+    /// a drain
+    pub(crate) fn records_handle(&self) -> RecordsHandle {
+        self.records.clone()
+    }
+}
+
+impl io::Write for TestWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        let mut parsed = vec![];
+        let mut stream = serde_json::Deserializer::from_slice(&self.buf).into_iter::<Value>();
+        let consumed = loop {
+            let offset = stream.byte_offset();
+            match stream.next() {
+                Some(Ok(record)) => parsed.push(record),
+                // Wait for the rest of the record
+                Some(Err(ref e)) if e.is_eof() => break Ok(offset),
+                Some(Err(e)) => break Err(e),
+                None => break Ok(stream.byte_offset()),
+            }
+        };
+        match consumed {
+            Ok(consumed) => {
+                self.buf.drain(..consumed);
+            }
+            Err(e) => {
+                self.buf.clear();
+                return Err(io::Error::new(io::ErrorKind::InvalidData, e));
+            }
+        }
+        self.records
+            .records
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .extend(parsed);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use slog::{Drain, Logger};
+
+    use super::{TestDrain, TestWriter};
+
+    #[test]
+    fn reassembles_split_writes() {
+        let mut writer = TestWriter::new();
+        let records = writer.records_handle();
+        writer.write_all(b"{\"a\": [1, ").unwrap();
+        assert!(records.is_empty());
+        writer.write_all(b"2]}\n{\"b\"").unwrap();
+        assert_eq!(records.len(), 1);
+        writer.write_all(b": true}\n").unwrap();
+        assert_eq!(records.records(), vec![json!({"a": [1, 2]}), json!({"b": true})]);
+    }
+
+    #[test]
+    fn rejects_invalid_json() {
+        let mut writer = TestWriter::new();
+        let records = writer.records_handle();
+        assert!(writer.write_all(b"{\"a\" 1}\n").is_err());
+        // Recovers at the next record
+        writer.write_all(b"{\"a\": 1}\n").unwrap();
+        assert_eq!(records.records(), vec![json!({"a": 1})]);
+    }
+
+    #[test]
+    fn take_clears() {
+        let drain = TestDrain::new();
+        let records = drain.records_handle();
+        let logger = Logger::root(drain.fuse(), o!());
+        info!(logger, "one");
+        info!(logger, "two");
+        let taken = records.take();
+        assert_eq!(taken.len(), 2);
+        assert_eq!(taken[1]["Fields"]["msg"], "two");
+        assert!(records.is_empty());
+        info!(logger, "three");
+        assert_eq!(records.records()[0]["Fields"]["msg"], "three");
+    }
+
+    #[test]
+    fn parses_pretty_records() {
+        let drain = TestDrain::with_builder(|builder| builder.set_pretty(true));
+        let records = drain.records_handle();
+        let logger = Logger::root(drain.fuse(), o!("app" => "test"));
+        info!(logger, "hello"; "user" => "jdoe");
+        info!(logger, "again");
+        let records = records.records();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["Fields"]["user"], "jdoe");
+        assert_eq!(records[0]["Fields"]["app"], "test");
+    }
+}
@@ -0,0 +1,131 @@
+use std::{fs, io, convert::TryFrom, path::Path, sync::Arc};
+
+use rustls::{self, Certificate, ClientConfig, OwnedTrustAnchor, PrivateKey, RootCertStore, ServerName};
+use rustls_pemfile;
+use webpki_roots;
+
+fn invalid_data<E: ToString>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+fn read_certs<P: AsRef<Path>>(path: P) -> io::Result<Vec<Certificate>> {
+    let mut reader = io::BufReader::new(fs::File::open(path)?);
+    let certs = rustls_pemfile::certs(&mut reader)?;
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+/// TLS settings for `TcpWriter`
+///
+/// Server certificates are verified against the Mozilla root store
+/// (`webpki-roots`) plus any additional roots added here.
+pub struct TlsConfig {
+    roots: RootCertStore,
+    client_auth: Option<(Vec<Certificate>, PrivateKey)>,
+    server_name: Option<String>,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TlsConfig {
+    /// New `TlsConfig` trusting the `webpki-roots` certificates
+    pub fn new() -> Self {
+        let mut roots = RootCertStore::empty();
+        roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+            OwnedTrustAnchor::from_subject_spki_name_constraints(
+                ta.subject,
+                ta.spki,
+                ta.name_constraints,
+            )
+        }));
+        TlsConfig {
+            roots,
+            client_auth: None,
+            server_name: None,
+        }
+    }
+
+    /// Trust the PEM encoded CA certificates in `path`
+    pub fn add_root_certs<P: AsRef<Path>>(mut self, path: P) -> io::Result<Self> {
+        for cert in read_certs(path)? {
+            self.roots.add(&cert).map_err(invalid_data)?;
+        }
+        Ok(self)
+    }
+
+    /// Authenticate with the PEM encoded client certificate chain and
+    /// PKCS#8 or RSA private key
+    pub fn set_client_cert<P, K>(mut self, cert_path: P, key_path: K) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+        K: AsRef<Path>,
+    {
+        let certs = read_certs(cert_path)?;
+        let mut reader = io::BufReader::new(fs::File::open(key_path)?);
+        let key = loop {
+            match rustls_pemfile::read_one(&mut reader)? {
+                Some(rustls_pemfile::Item::PKCS8Key(key))
+                | Some(rustls_pemfile::Item::RSAKey(key))
+                | Some(rustls_pemfile::Item::ECKey(key)) => break PrivateKey(key),
+                Some(_) => continue,
+                None => return Err(invalid_data("no private key found")),
+            }
+        };
+        self.client_auth = Some((certs, key));
+        Ok(self)
+    }
+
+    /// Set the name to verify the server certificate against, instead of
+    /// the host in the connection address
+    pub fn set_server_name(mut self, name: String) -> Self {
+        self.server_name = Some(name);
+        self
+    }
+
+    pub(crate) fn connector(self, addr: &str) -> io::Result<TlsConnector> {
+        let builder = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(self.roots);
+        let config = match self.client_auth {
+            Some((certs, key)) => builder
+                .with_client_auth_cert(certs, key)
+                .map_err(invalid_data)?,
+            None => builder.with_no_client_auth(),
+        };
+        let name = match self.server_name {
+            Some(name) => name,
+            None => host(addr).to_owned(),
+        };
+        Ok(TlsConnector {
+            config: Arc::new(config),
+            server_name: ServerName::try_from(name.as_str()).map_err(invalid_data)?,
+        })
+    }
+}
+
+/// Host portion of a `host:port` address
+fn host(addr: &str) -> &str {
+    let host = match addr.rfind(':') {
+        Some(pos) => &addr[..pos],
+        None => addr,
+    };
+    host.trim_start_matches('[').trim_end_matches(']')
+}
+
+pub(crate) type TlsStream = rustls::StreamOwned<rustls::ClientConnection, ::std::net::TcpStream>;
+
+pub(crate) struct TlsConnector {
+    config: Arc<ClientConfig>,
+    server_name: ServerName,
+}
+
+impl TlsConnector {
+    pub(crate) fn wrap(&self, stream: ::std::net::TcpStream) -> io::Result<TlsStream> {
+        let conn = rustls::ClientConnection::new(self.config.clone(), self.server_name.clone())
+            .map_err(invalid_data)?;
+        Ok(rustls::StreamOwned::new(conn, stream))
+    }
+}
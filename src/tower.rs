@@ -0,0 +1,173 @@
+use std::{future::Future, pin::Pin, sync::Arc, task::{Context, Poll}, time::Instant};
+
+use http1::{Extensions, Request, Response, header::{AsHeaderName, USER_AGENT}};
+use slog::{self, Logger, Record, KV};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use summary::RequestSummary;
+
+type FieldHook = Arc<dyn Fn(&Extensions) -> Option<String> + Send + Sync>;
+
+/// tower `Layer` logging a `request.summary` record per request (e.g. for
+/// axum or hyper services)
+///
+/// Records have Fields `method`, `path`, `code`, `t` (milliseconds), if known
+/// `agent` (User-Agent) and `rid` (X-Request-Id), plus any fields added via
+/// `add_field`.
+///
+/// ```ignore
+/// let app = Router::new()
+///     .route("/", get(root))
+///     .layer(TowerSummaryLayer::new(logger.clone())
+///         .add_field("uid", |ext| ext.get::<UserId>().map(|uid| uid.0.clone())));
+/// ```
+#[derive(Clone)]
+pub struct TowerSummaryLayer {
+    logger: Logger,
+    fields: Vec<(&'static str, FieldHook)>,
+}
+
+impl TowerSummaryLayer {
+    /// Log request summaries to `logger`
+    pub fn new(logger: Logger) -> Self {
+        TowerSummaryLayer {
+            logger,
+            fields: Vec::new(),
+        }
+    }
+
+    /// Add a `key` field extracted from the request's extensions, or if `f`
+    /// finds nothing there, from the response's (for values only set by
+    /// inner services, e.g. the authenticated user's id)
+    pub fn add_field<F>(mut self, key: &'static str, f: F) -> Self
+    where
+        F: Fn(&Extensions) -> Option<String> + Send + Sync + 'static,
+    {
+        self.fields.push((key, Arc::new(f)));
+        self
+    }
+}
+
+impl<S> Layer<S> for TowerSummaryLayer {
+    type Service = TowerSummary<S>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        TowerSummary {
+            service,
+            logger: self.logger.clone(),
+            fields: self.fields.clone(),
+        }
+    }
+}
+
+/// Service created by `TowerSummaryLayer`
+#[derive(Clone)]
+pub struct TowerSummary<S> {
+    service: S,
+    logger: Logger,
+    fields: Vec<(&'static str, FieldHook)>,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for TowerSummary<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = TowerSummaryFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
+        let extensions = req.extensions();
+        let started = Started {
+            at: Instant::now(),
+            summary: RequestSummary {
+                method: req.method().to_string(),
+                path: req.uri().path().to_owned(),
+                agent: header(&req, USER_AGENT),
+                rid: header(&req, "x-request-id"),
+                ..Default::default()
+            },
+            fields: self.fields
+                .iter()
+                .map(|&(key, ref f)| (key, f.clone(), f(extensions)))
+                .collect(),
+        };
+        TowerSummaryFuture {
+            inner: Box::pin(self.service.call(req)),
+            started: Some(started),
+            logger: self.logger.clone(),
+        }
+    }
+}
+
+fn header<B, K: AsHeaderName>(req: &Request<B>, name: K) -> Option<String> {
+    req.headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned)
+}
+
+struct Started {
+    at: Instant,
+    summary: RequestSummary,
+    fields: Vec<(&'static str, FieldHook, Option<String>)>,
+}
+
+/// The `add_field` fields found for a request
+struct Extracted(Vec<(&'static str, String)>);
+
+impl KV for Extracted {
+    fn serialize(&self, _rinfo: &Record, serializer: &mut dyn slog::Serializer) -> slog::Result {
+        for &(key, ref value) in &self.0 {
+            serializer.emit_str(key, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Response future of `TowerSummary`, logging once it completes
+pub struct TowerSummaryFuture<F> {
+    inner: Pin<Box<F>>,
+    started: Option<Started>,
+    logger: Logger,
+}
+
+impl<F, B, E> Future for TowerSummaryFuture<F>
+where
+    F: Future<Output = Result<Response<B>, E>>,
+{
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let res = match self.inner.as_mut().poll(cx) {
+            Poll::Ready(res) => res,
+            Poll::Pending => return Poll::Pending,
+        };
+        if let Some(started) = self.started.take() {
+            let resp = res.as_ref().ok();
+            let mut summary = started.summary;
+            // no response to log the status of: report what a server would
+            // most likely answer with
+            summary.code = resp.map_or(500, |resp| resp.status().as_u16());
+            summary.set_elapsed(started.at.elapsed());
+            let extracted = Extracted(
+                started
+                    .fields
+                    .into_iter()
+                    .filter_map(|(key, f, value)| {
+                        value
+                            .or_else(|| resp.and_then(|resp| f(resp.extensions())))
+                            .map(|value| (key, value))
+                    })
+                    .collect(),
+            );
+            info!(self.logger, #"request.summary", ""; summary, extracted);
+        }
+        Poll::Ready(res)
+    }
+}
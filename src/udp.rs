@@ -0,0 +1,98 @@
+use std::{io, net::{ToSocketAddrs, UdpSocket}};
+
+use util::LineBuffer;
+
+/// What to do with a record larger than the maximum datagram size
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TruncationPolicy {
+    /// Send the first `max_datagram_size` bytes of the record
+    Truncate,
+    /// Discard the record
+    Drop,
+}
+
+/// UDP `io::Write` sink
+///
+/// Each newline terminated record is sent as a single datagram, without its
+/// trailing newline. Send failures are counted rather than returned, as
+/// with any fire-and-forget transport.
+pub struct UdpWriter {
+    socket: UdpSocket,
+    max_datagram_size: usize,
+    policy: TruncationPolicy,
+    lines: LineBuffer,
+    dropped: u64,
+}
+
+impl UdpWriter {
+    /// New `UdpWriter` sending to `addr` (`host:port`)
+    pub fn new(addr: String) -> io::Result<Self> {
+        let target = addr.to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "no addresses to send to")
+        })?;
+        let local = if target.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(target)?;
+        Ok(UdpWriter {
+            socket,
+            max_datagram_size: 8192,
+            policy: TruncationPolicy::Truncate,
+            lines: LineBuffer::new(),
+            dropped: 0,
+        })
+    }
+
+    /// Set the maximum datagram size in bytes
+    pub fn set_max_datagram_size(mut self, bytes: usize) -> Self {
+        self.max_datagram_size = bytes;
+        self
+    }
+
+    /// Set how records exceeding the maximum datagram size are handled
+    pub fn set_truncation_policy(mut self, policy: TruncationPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Number of records dropped for being oversized or failing to send
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    fn send(&mut self, record: &[u8]) {
+        let mut datagram = match record.last() {
+            Some(&b'\n') => &record[..record.len() - 1],
+            _ => record,
+        };
+        if datagram.len() > self.max_datagram_size {
+            if self.policy == TruncationPolicy::Drop {
+                self.dropped += 1;
+                return;
+            }
+            // Don't split a UTF-8 sequence
+            let mut end = self.max_datagram_size;
+            while end > 0 && datagram[end] & 0xC0 == 0x80 {
+                end -= 1;
+            }
+            datagram = &datagram[..end];
+        }
+        if self.socket.send(datagram).is_err() {
+            self.dropped += 1;
+        }
+    }
+}
+
+impl io::Write for UdpWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut records = vec![];
+        self.lines.push(buf, |record| records.push(record));
+        for record in records {
+            self.send(&record);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,125 @@
+use std::{io, os::unix::net::{UnixDatagram, UnixStream}, path::PathBuf, time::Duration};
+
+use reconnect::{Connect, Reconnecting};
+
+#[derive(Clone, Copy)]
+enum SocketType {
+    Stream,
+    Datagram,
+}
+
+struct UnixConnector {
+    path: PathBuf,
+    socket_type: SocketType,
+    write_timeout: Duration,
+}
+
+enum UnixSocket {
+    Stream(UnixStream),
+    Datagram(UnixDatagram),
+}
+
+impl Connect for UnixConnector {
+    type Stream = UnixSocket;
+
+    fn connect(&self) -> io::Result<UnixSocket> {
+        // A collector that stops reading mustn't block logging threads
+        match self.socket_type {
+            SocketType::Stream => {
+                let stream = UnixStream::connect(&self.path)?;
+                stream.set_write_timeout(Some(self.write_timeout))?;
+                Ok(UnixSocket::Stream(stream))
+            }
+            SocketType::Datagram => {
+                let socket = UnixDatagram::unbound()?;
+                socket.set_write_timeout(Some(self.write_timeout))?;
+                socket.connect(&self.path)?;
+                Ok(UnixSocket::Datagram(socket))
+            }
+        }
+    }
+}
+
+impl io::Write for UnixSocket {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match *self {
+            UnixSocket::Stream(ref mut stream) => stream.write(buf),
+            // Records are written whole, so each becomes one datagram
+            UnixSocket::Datagram(ref socket) => socket.send(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match *self {
+            UnixSocket::Stream(ref mut stream) => stream.flush(),
+            UnixSocket::Datagram(_) => Ok(()),
+        }
+    }
+}
+
+/// Unix domain socket `io::Write` sink
+///
+/// Writes newline terminated records to a stream or datagram socket (e.g.
+/// `/run/vector.sock`), reconnecting with exponential backoff when the
+/// socket goes away. While disconnected, complete records are buffered up
+/// to a byte limit, dropping the oldest first.
+pub struct UnixWriter {
+    inner: Reconnecting<UnixConnector>,
+}
+
+impl UnixWriter {
+    fn new(path: PathBuf, socket_type: SocketType) -> Self {
+        UnixWriter {
+            inner: Reconnecting::new(UnixConnector {
+                path,
+                socket_type,
+                write_timeout: Duration::from_secs(5),
+            }),
+        }
+    }
+
+    /// New `UnixWriter` for a `SOCK_STREAM` socket at `path`
+    pub fn stream<P: Into<PathBuf>>(path: P) -> Self {
+        UnixWriter::new(path.into(), SocketType::Stream)
+    }
+
+    /// New `UnixWriter` for a `SOCK_DGRAM` socket at `path`, sending one
+    /// record per datagram
+    pub fn datagram<P: Into<PathBuf>>(path: P) -> Self {
+        UnixWriter::new(path.into(), SocketType::Datagram)
+    }
+
+    /// Set the maximum number of bytes buffered while disconnected
+    pub fn set_buffer_limit(mut self, bytes: usize) -> Self {
+        self.inner.set_buffer_limit(bytes);
+        self
+    }
+
+    /// Set the initial and maximum delay between reconnection attempts
+    pub fn set_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.inner.set_backoff(initial, max);
+        self
+    }
+
+    /// Set the timeout for each write, after which the connection is
+    /// re-established (default 5s)
+    pub fn set_write_timeout(mut self, timeout: Duration) -> Self {
+        self.inner.connector_mut().write_timeout = timeout;
+        self
+    }
+
+    /// Number of records dropped because the buffer limit was reached
+    pub fn dropped(&self) -> u64 {
+        self.inner.dropped()
+    }
+}
+
+impl io::Write for UnixWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,165 @@
+use std::{fs, io, mem, collections::HashMap, io::Seek, os::unix::io::AsRawFd, path::Path};
+
+use io_uring::{opcode, types, IoUring};
+use libc;
+
+/// A submitted write: its data, file offset, and how much has landed
+struct InFlight {
+    buf: Vec<u8>,
+    offset: u64,
+    written: usize,
+}
+
+/// Linux `io_uring` file `io::Write` sink
+///
+/// Records are buffered and submitted in batches as asynchronous writes,
+/// so logging rarely makes a syscall and never waits on the disk unless
+/// the submission queue is full. Each batch is written at an explicit
+/// offset, so the file must not be written by anything else. `flush` and
+/// dropping the writer wait for every submitted write to complete.
+pub struct UringFileWriter {
+    file: fs::File,
+    ring: IoUring,
+    buf: Vec<u8>,
+    batch_bytes: usize,
+    /// Offset of the next batch
+    offset: u64,
+    next_id: u64,
+    in_flight: HashMap<u64, InFlight>,
+    depth: usize,
+    failed: u64,
+}
+
+impl UringFileWriter {
+    /// New `UringFileWriter` appending to `path`, with up to `depth`
+    /// batches in flight
+    pub fn new<P: AsRef<Path>>(path: P, depth: u32) -> io::Result<Self> {
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(false)
+            .open(path)?;
+        let offset = file.seek(io::SeekFrom::End(0))?;
+        Ok(UringFileWriter {
+            file,
+            ring: IoUring::new(depth.max(1))?,
+            buf: vec![],
+            batch_bytes: 64 * 1024,
+            offset,
+            next_id: 0,
+            in_flight: HashMap::new(),
+            depth: depth.max(1) as usize,
+            failed: 0,
+        })
+    }
+
+    /// Set the buffered size that triggers submitting a batch
+    pub fn set_batch_bytes(mut self, bytes: usize) -> Self {
+        self.batch_bytes = bytes;
+        self
+    }
+
+    /// Number of batches that failed to be written
+    pub fn failed(&self) -> u64 {
+        self.failed
+    }
+
+    fn push(&mut self, id: u64) -> io::Result<()> {
+        let entry = {
+            let write = &self.in_flight[&id];
+            let rest = &write.buf[write.written..];
+            opcode::Write::new(
+                types::Fd(self.file.as_raw_fd()),
+                rest.as_ptr(),
+                rest.len() as u32,
+            ).offset(write.offset + write.written as u64)
+                .build()
+                .user_data(id)
+        };
+        // The buffer stays in `in_flight` until its completion is reaped
+        unsafe { self.ring.submission().push(&entry) }
+            .map_err(|_| io::Error::other("io_uring submission queue full"))?;
+        self.ring.submit()?;
+        Ok(())
+    }
+
+    /// Submit the buffered records as one write
+    fn submit(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        while self.in_flight.len() >= self.depth {
+            self.reap(1)?;
+        }
+        let buf = mem::take(&mut self.buf);
+        let id = self.next_id;
+        self.next_id += 1;
+        self.in_flight.insert(
+            id,
+            InFlight {
+                offset: self.offset,
+                buf,
+                written: 0,
+            },
+        );
+        self.offset += self.in_flight[&id].buf.len() as u64;
+        self.push(id)
+    }
+
+    /// Wait for at least `want` completions and process all available
+    fn reap(&mut self, want: usize) -> io::Result<()> {
+        self.ring.submit_and_wait(want)?;
+        let completed: Vec<(u64, i32)> = self.ring
+            .completion()
+            .map(|cqe| (cqe.user_data(), cqe.result()))
+            .collect();
+        for (id, result) in completed {
+            let done = match self.in_flight.get_mut(&id) {
+                Some(write) if result > 0 => {
+                    write.written += result as usize;
+                    write.written >= write.buf.len()
+                }
+                Some(_) if result == -libc::EINTR || result == -libc::EAGAIN => false,
+                Some(_) => {
+                    self.failed += 1;
+                    true
+                }
+                None => continue,
+            };
+            if done {
+                self.in_flight.remove(&id);
+            } else {
+                // Short or interrupted write: submit the rest
+                self.push(id)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl io::Write for UringFileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        if self.buf.len() >= self.batch_bytes && buf.ends_with(b"\n") {
+            self.submit()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.submit()?;
+        while !self.in_flight.is_empty() {
+            self.reap(1)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for UringFileWriter {
+    fn drop(&mut self) {
+        if io::Write::flush(self).is_err() {
+            // Leak rather than free buffers the kernel may still be reading
+            mem::forget(mem::take(&mut self.in_flight));
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use std::{cmp, io, mem, process, collections::VecDeque, io::IoSlice, time::{Duration, Instant}};
+
 use slog::Level;
 
 pub(crate) fn level_to_severity(level: Level) -> u8 {
@@ -9,3 +11,177 @@ pub(crate) fn level_to_severity(level: Level) -> u8 {
         Level::Debug | Level::Trace => 7,
     }
 }
+
+/// This process's id, the default `Pid`, if there are processes
+pub(crate) fn process_id() -> Option<u32> {
+    // WebAssembly has no processes, and `process::id` panics there
+    if cfg!(target_family = "wasm") {
+        None
+    } else {
+        Some(process::id())
+    }
+}
+
+/// A `Duration` in fractional seconds
+pub(crate) fn secs(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + f64::from(duration.subsec_nanos()) / 1e9
+}
+
+/// Splits a written byte stream into newline terminated records
+///
+/// Bytes following the last newline are held until a later write completes
+/// the record.
+pub(crate) struct LineBuffer {
+    partial: Vec<u8>,
+}
+
+impl LineBuffer {
+    pub(crate) fn new() -> Self {
+        LineBuffer { partial: vec![] }
+    }
+
+    /// Append `buf`, passing each completed record (including its newline)
+    /// to `f`
+    pub(crate) fn push<F>(&mut self, buf: &[u8], mut f: F)
+    where
+        F: FnMut(Vec<u8>),
+    {
+        let mut rest = buf;
+        while let Some(pos) = rest.iter().position(|b| *b == b'\n') {
+            self.partial.extend_from_slice(&rest[..pos + 1]);
+            f(mem::take(&mut self.partial));
+            rest = &rest[pos + 1..];
+        }
+        self.partial.extend_from_slice(rest);
+    }
+}
+
+/// FIFO of serialized records capped at a total number of bytes
+///
+/// The oldest records are dropped to make room for new ones.
+pub(crate) struct RecordQueue {
+    records: VecDeque<Vec<u8>>,
+    bytes: usize,
+    limit: usize,
+    dropped: u64,
+}
+
+impl RecordQueue {
+    pub(crate) fn new(limit: usize) -> Self {
+        RecordQueue {
+            records: VecDeque::new(),
+            bytes: 0,
+            limit,
+            dropped: 0,
+        }
+    }
+
+    pub(crate) fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+    }
+
+    pub(crate) fn push(&mut self, record: Vec<u8>) {
+        if record.len() > self.limit {
+            self.dropped += 1;
+            return;
+        }
+        while self.bytes + record.len() > self.limit {
+            self.pop_front();
+            self.dropped += 1;
+        }
+        self.bytes += record.len();
+        self.records.push_back(record);
+    }
+
+    pub(crate) fn front(&self) -> Option<&Vec<u8>> {
+        self.records.front()
+    }
+
+    pub(crate) fn pop_front(&mut self) -> Option<Vec<u8>> {
+        let record = self.records.pop_front();
+        if let Some(ref record) = record {
+            self.bytes -= record.len();
+        }
+        record
+    }
+
+    /// Discard the oldest record, e.g. as it can never be sent
+    pub(crate) fn reject_front(&mut self) {
+        if self.pop_front().is_some() {
+            self.dropped += 1;
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Number of records discarded because the queue was full or they were
+    /// rejected
+    pub(crate) fn dropped(&self) -> u64 {
+        self.dropped
+    }
+}
+
+/// Exponential backoff between reconnection attempts
+pub(crate) struct Backoff {
+    initial: Duration,
+    max: Duration,
+    current: Duration,
+    next_attempt: Option<Instant>,
+}
+
+impl Backoff {
+    pub(crate) fn new(initial: Duration, max: Duration) -> Self {
+        Backoff {
+            initial,
+            max,
+            current: initial,
+            next_attempt: None,
+        }
+    }
+
+    /// Whether enough time has passed since the last failure to try again
+    pub(crate) fn ready(&self) -> bool {
+        self.next_attempt.is_none_or(|at| Instant::now() >= at)
+    }
+
+    /// Record a failed attempt, doubling the delay up to the maximum
+    pub(crate) fn fail(&mut self) {
+        self.next_attempt = Some(Instant::now() + self.current);
+        self.current = cmp::min(self.current * 2, self.max);
+    }
+
+    /// Record a successful attempt
+    pub(crate) fn reset(&mut self) {
+        self.current = self.initial;
+        self.next_attempt = None;
+    }
+}
+
+/// Write all of `head` followed by `tail`, using vectored writes so both
+/// usually go out in a single call
+pub(crate) fn write_all_vectored(
+    io: &mut dyn io::Write,
+    mut head: &[u8],
+    mut tail: &[u8],
+) -> io::Result<()> {
+    while !head.is_empty() {
+        match io.write_vectored(&[IoSlice::new(head), IoSlice::new(tail)]) {
+            Ok(0) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::WriteZero,
+                    "failed to write whole record",
+                ))
+            }
+            Ok(n) if n < head.len() => head = &head[n..],
+            Ok(n) => {
+                tail = &tail[n - head.len()..];
+                head = &[];
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
+    }
+    io.write_all(tail)
+}
@@ -0,0 +1,32 @@
+use slog::Logger;
+use warp::log::{custom, Info, Log};
+
+use summary::RequestSummary;
+
+/// warp filter logging a `request.summary` record per request
+///
+/// Records have Fields `method`, `path`, `code`, `t` (milliseconds), and if
+/// known `agent` (User-Agent), `remote_ip` (the peer address) and
+/// `rid` (X-Request-Id).
+///
+/// ```ignore
+/// let routes = warp::any().map(|| "hello").with(warp_summary(logger.clone()));
+/// ```
+pub fn warp_summary(logger: Logger) -> Log<impl Fn(Info) + Clone + Send + Sync + 'static> {
+    custom(move |info: Info| {
+        let mut summary = RequestSummary::new(
+            info.method().as_str(),
+            info.path(),
+            info.status().as_u16(),
+            info.elapsed(),
+        );
+        summary.agent = info.user_agent().map(str::to_owned);
+        summary.remote_ip = info.remote_addr().map(|addr| addr.ip().to_string());
+        summary.rid = info
+            .request_headers()
+            .get("x-request-id")
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned);
+        summary.log(&logger);
+    })
+}